@@ -0,0 +1,175 @@
+// Удаление аккаунта по запросу пользователя: сначала планируется с периодом отмены,
+// по истечении срока пользователь удаляется окончательно вместе со всеми связанными записями
+use std::sync::Arc;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+use crate::config::{AccountDeletionPolicy, AppConfig};
+use crate::login_throttle::{account_key, SCOPE_ACCOUNT};
+use crate::mail::{EmailMessage, MailSender};
+use crate::models::User;
+
+#[derive(Debug)]
+pub enum AccountDeletionError {
+    DatabaseError(sqlx::Error),
+    AlreadyScheduled,
+    NotScheduled,
+}
+
+impl std::fmt::Display for AccountDeletionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccountDeletionError::DatabaseError(e) => write!(f, "Database error: {}", e),
+            AccountDeletionError::AlreadyScheduled => write!(f, "Account deletion is already scheduled"),
+            AccountDeletionError::NotScheduled => write!(f, "Account deletion is not scheduled"),
+        }
+    }
+}
+
+impl std::error::Error for AccountDeletionError {}
+
+impl From<sqlx::Error> for AccountDeletionError {
+    fn from(e: sqlx::Error) -> Self {
+        AccountDeletionError::DatabaseError(e)
+    }
+}
+
+// Запланированное удаление
+#[derive(Debug, Clone, Copy, Serialize, sqlx::FromRow)]
+pub struct AccountDeletionStatus {
+    pub requested_at: DateTime<Utc>,
+    pub scheduled_for: DateTime<Utc>,
+}
+
+pub struct AccountDeletionService {
+    pool: Pool<Postgres>,
+    mailer: Arc<dyn MailSender>,
+    issuer: String,
+    policy: AccountDeletionPolicy,
+}
+
+impl AccountDeletionService {
+    pub fn new(pool: Pool<Postgres>, mailer: Arc<dyn MailSender>, config: &AppConfig) -> Self {
+        Self {
+            pool,
+            mailer,
+            issuer: config.issuer.clone(),
+            policy: config.account_deletion,
+        }
+    }
+
+    pub fn purge_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.policy.purge_interval.max(1) as u64)
+    }
+
+    pub async fn get_status(&self, user_id: Uuid) -> Result<Option<AccountDeletionStatus>, AccountDeletionError> {
+        let status = sqlx::query_as::<_, AccountDeletionStatus>(
+            r#"
+            SELECT deletion_requested_at AS requested_at, deletion_scheduled_for AS scheduled_for
+            FROM users
+            WHERE id = $1 AND deletion_scheduled_for IS NOT NULL
+            "#
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(status)
+    }
+
+    // Планирование удаления и письмо с датой удаления. До этой даты пользователь
+    // может войти и отменить удаление
+    pub async fn schedule(&self, user: &User) -> Result<AccountDeletionStatus, AccountDeletionError> {
+        let now = Utc::now();
+
+        let status = sqlx::query_as::<_, AccountDeletionStatus>(
+            r#"
+            UPDATE users SET deletion_requested_at = $1, deletion_scheduled_for = $2, updated_at = $1
+            WHERE id = $3 AND deletion_scheduled_for IS NULL
+            RETURNING deletion_requested_at AS requested_at, deletion_scheduled_for AS scheduled_for
+            "#
+        )
+        .bind(now)
+        .bind(now + Duration::seconds(self.policy.grace_period))
+        .bind(user.id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(AccountDeletionError::AlreadyScheduled)?;
+
+        let notice = EmailMessage {
+            to: user.email.clone(),
+            subject: "Удаление аккаунта".to_string(),
+            body: format!(
+                "Здравствуйте, {}!\n\nВаш аккаунт и все его данные будут удалены {} (UTC).\n\nЧтобы отменить удаление, войдите и откройте {}/account/privacy до этой даты.\n",
+                user.username,
+                status.scheduled_for.format("%Y-%m-%d %H:%M"),
+                self.issuer
+            ),
+        };
+        // Удаление уже запланировано, недоставленное письмо его не отменяет
+        if let Err(e) = self.mailer.send(notice).await {
+            eprintln!("Failed to send account deletion notice: {}", e);
+        }
+
+        Ok(status)
+    }
+
+    pub async fn cancel(&self, user_id: Uuid) -> Result<(), AccountDeletionError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE users SET deletion_requested_at = NULL, deletion_scheduled_for = NULL, updated_at = $1
+            WHERE id = $2 AND deletion_scheduled_for IS NOT NULL
+            "#
+        )
+        .bind(Utc::now())
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AccountDeletionError::NotScheduled);
+        }
+
+        Ok(())
+    }
+
+    // Окончательное удаление аккаунтов с истекшим сроком отмены. Коды, токены, согласия,
+    // сессии и остальные записи пользователя удаляются каскадно (ON DELETE CASCADE);
+    // счетчики неудачных входов хранятся по email и удаляются отдельно
+    pub async fn purge_due(&self) -> Result<u64, AccountDeletionError> {
+        let mut tx = self.pool.begin().await?;
+
+        let emails: Vec<String> = sqlx::query_scalar(
+            "DELETE FROM users WHERE deletion_scheduled_for <= $1 RETURNING email"
+        )
+        .bind(Utc::now())
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let keys: Vec<String> = emails.iter().map(|email| account_key(email)).collect();
+        sqlx::query("DELETE FROM login_failures WHERE scope = $1 AND key = ANY($2)")
+            .bind(SCOPE_ACCOUNT)
+            .bind(&keys)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(emails.len() as u64)
+    }
+}
+
+// Фоновая задача окончательного удаления
+pub fn spawn_purge_task(service: Arc<AccountDeletionService>) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(service.purge_interval());
+        loop {
+            interval.tick().await;
+            match service.purge_due().await {
+                Ok(0) => {}
+                Ok(deleted) => eprintln!("Purged {} accounts scheduled for deletion", deleted),
+                Err(e) => eprintln!("Account purge error: {}", e),
+            }
+        }
+    });
+}
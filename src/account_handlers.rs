@@ -0,0 +1,280 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use actix_session::Session;
+use uuid::Uuid;
+use crate::login_throttle::{client_ip, LoginThrottle};
+use crate::models::{ErrorResponse, User};
+use crate::services::UserService;
+use crate::consent_service::ConsentService;
+use crate::token_service::TokenService;
+use crate::profile_handlers;
+use crate::privacy_handlers;
+
+// Получение user_id из сессии; при ошибке возвращается готовый HTTP ответ
+pub(crate) fn session_user_id(session: &Session) -> Result<Uuid, HttpResponse> {
+    match session.get::<String>("user_id") {
+        Ok(Some(user_id_str)) => user_id_str.parse::<Uuid>().map_err(|_| {
+            HttpResponse::BadRequest().json(ErrorResponse {
+                error: "Invalid user ID in session".to_string(),
+            })
+        }),
+        Ok(None) => Err(HttpResponse::Unauthorized().json(ErrorResponse {
+            error: "Not authenticated".to_string(),
+        })),
+        Err(e) => {
+            eprintln!("Session error: {}", e);
+            Err(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            }))
+        }
+    }
+}
+
+// Пользователь сессии из БД; при ошибке возвращается готовый HTTP ответ
+pub(crate) async fn session_user(user_service: &UserService, session: &Session) -> Result<User, HttpResponse> {
+    let user_id = session_user_id(session)?;
+    match user_service.get_user_by_id(user_id).await {
+        Ok(Some(user)) => Ok(user),
+        Ok(None) => Err(HttpResponse::NotFound().json(ErrorResponse {
+            error: "User not found".to_string(),
+        })),
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            Err(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            }))
+        }
+    }
+}
+
+// Проверка текущего пароля перед изменением аккаунта. Неверный пароль учитывается
+// как неудачный вход, чтобы украденная сессия не позволяла подбирать пароль
+pub(crate) async fn verify_current_password(
+    req: &HttpRequest,
+    user_service: &UserService,
+    login_throttle: &LoginThrottle,
+    user: &User,
+    password: &str,
+) -> Result<(), HttpResponse> {
+    let invalid_password = || HttpResponse::Forbidden().json(ErrorResponse {
+        error: "Invalid current password".to_string(),
+    });
+    let internal_error = |e: &dyn std::fmt::Display| {
+        eprintln!("Password verification error: {}", e);
+        HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        })
+    };
+    let ip_address = client_ip(req);
+
+    match login_throttle.is_locked(&user.email, ip_address.as_deref()).await {
+        Ok(false) => {}
+        Ok(true) => return Err(invalid_password()),
+        Err(e) => return Err(internal_error(&e)),
+    }
+
+    match user_service.verify_password(user, password).await {
+        Ok(true) => Ok(()),
+        Ok(false) => {
+            match login_throttle.record_failure(&user.email, ip_address.as_deref()).await {
+                Ok(delay) => actix_web::rt::time::sleep(delay).await,
+                Err(e) => eprintln!("Database error: {}", e),
+            }
+            Err(invalid_password())
+        }
+        Err(e) => Err(internal_error(&e)),
+    }
+}
+
+// GET /account/apps - страница подключенных приложений
+pub async fn connected_apps_page(session: Session) -> impl Responder {
+    if session_user_id(&session).is_err() {
+        return HttpResponse::Found()
+            .append_header(("Location", "/auth/login?return_to=%2Faccount%2Fapps"))
+            .finish();
+    }
+
+    let html = r#"
+<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="UTF-8">
+    <title>Подключенные приложения</title>
+    <style>
+        body { font-family: Arial, sans-serif; max-width: 700px; margin: 50px auto; padding: 20px; }
+        h1 { text-align: center; }
+        .app { border: 1px solid #ddd; border-radius: 8px; padding: 15px; margin: 10px 0; }
+        .app h3 { margin: 0 0 10px 0; }
+        .meta { color: #666; font-size: 14px; }
+        .scopes span { display: inline-block; padding: 4px 8px; background: #e3f2fd; margin: 3px; border-radius: 4px; }
+        button { padding: 8px 16px; border: none; border-radius: 4px; cursor: pointer; background-color: #dc3545; color: white; }
+        .empty, .error { text-align: center; color: #666; }
+        .error { color: red; }
+    </style>
+</head>
+<body>
+    <h1>Подключенные приложения</h1>
+    <div id="apps"></div>
+    <div class="error" id="error"></div>
+
+    <script>
+        function formatDate(value) {
+            return value ? new Date(value).toLocaleString() : '—';
+        }
+
+        async function loadApps() {
+            const container = document.getElementById('apps');
+            const response = await fetch('/account/api/apps');
+            if (!response.ok) {
+                document.getElementById('error').textContent = 'Не удалось загрузить список приложений';
+                return;
+            }
+
+            const apps = await response.json();
+            container.innerHTML = '';
+            if (apps.length === 0) {
+                container.innerHTML = '<p class="empty">Нет приложений с доступом к аккаунту</p>';
+                return;
+            }
+
+            for (const app of apps) {
+                const item = document.createElement('div');
+                item.className = 'app';
+
+                const title = document.createElement('h3');
+                title.textContent = app.client_name;
+                item.appendChild(title);
+
+                const scopes = document.createElement('div');
+                scopes.className = 'scopes';
+                for (const scope of app.scopes) {
+                    const tag = document.createElement('span');
+                    tag.textContent = scope;
+                    scopes.appendChild(tag);
+                }
+                item.appendChild(scopes);
+
+                const meta = document.createElement('p');
+                meta.className = 'meta';
+                meta.textContent = 'Доступ выдан: ' + formatDate(app.authorized_at)
+                    + ' · Последнее использование: ' + formatDate(app.last_used_at);
+                item.appendChild(meta);
+
+                const button = document.createElement('button');
+                button.textContent = 'Отозвать доступ';
+                button.addEventListener('click', () => revokeApp(app.client_id, app.client_name));
+                item.appendChild(button);
+
+                container.appendChild(item);
+            }
+        }
+
+        async function revokeApp(clientId, clientName) {
+            if (!confirm('Отозвать доступ приложения ' + clientName + '?')) {
+                return;
+            }
+
+            const response = await fetch('/account/api/apps/' + encodeURIComponent(clientId), { method: 'DELETE' });
+            if (response.ok) {
+                loadApps();
+            } else {
+                document.getElementById('error').textContent = 'Не удалось отозвать доступ';
+            }
+        }
+
+        loadApps();
+    </script>
+</body>
+</html>
+    "#;
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html)
+}
+
+// GET /account/api/apps - список подключенных приложений
+pub async fn list_connected_apps(
+    consent_service: web::Data<ConsentService>,
+    session: Session,
+) -> impl Responder {
+    let user_id = match session_user_id(&session) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    match consent_service.list_connected_apps(user_id).await {
+        Ok(apps) => HttpResponse::Ok().json(apps),
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            })
+        }
+    }
+}
+
+// DELETE /account/api/apps/{client_id} - отзыв доступа приложения
+pub async fn revoke_connected_app(
+    path: web::Path<String>,
+    consent_service: web::Data<ConsentService>,
+    token_service: web::Data<TokenService>,
+    session: Session,
+) -> impl Responder {
+    let user_id = match session_user_id(&session) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+    let client_id = path.into_inner();
+
+    // Отзыв всех access и refresh токенов пары пользователь–клиент
+    let revoked_tokens = match token_service.revoke_user_client_tokens(user_id, &client_id).await {
+        Ok(count) => count,
+        Err(e) => {
+            eprintln!("Failed to revoke tokens: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            });
+        }
+    };
+
+    // Удаление согласия
+    let consent_removed = match consent_service.revoke_consent(user_id, &client_id).await {
+        Ok(removed) => removed,
+        Err(e) => {
+            eprintln!("Failed to revoke consent: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            });
+        }
+    };
+
+    if !consent_removed && revoked_tokens == 0 {
+        return HttpResponse::NotFound().json(ErrorResponse {
+            error: "Application not found".to_string(),
+        });
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "message": "Access revoked",
+        "client_id": client_id,
+        "revoked_tokens": revoked_tokens
+    }))
+}
+
+// Конфигурация маршрутов личного кабинета
+pub fn configure_account_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/account")
+            .route("/apps", web::get().to(connected_apps_page))
+            .route("/api/apps", web::get().to(list_connected_apps))
+            .route("/api/apps/{client_id}", web::delete().to(revoke_connected_app))
+            .route("/profile", web::get().to(profile_handlers::profile_page))
+            .route("/api/profile", web::get().to(profile_handlers::get_session_profile))
+            .route("/api/profile", web::patch().to(profile_handlers::update_session_profile))
+            .route("/privacy", web::get().to(privacy_handlers::privacy_page))
+            .route("/api/export", web::get().to(privacy_handlers::export_data))
+            .route("/api/deletion", web::get().to(privacy_handlers::deletion_status))
+            .route("/api/deletion", web::post().to(privacy_handlers::schedule_deletion))
+            .route("/api/deletion", web::delete().to(privacy_handlers::cancel_deletion))
+    );
+}
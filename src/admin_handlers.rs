@@ -0,0 +1,473 @@
+use actix_web::{web, HttpResponse, Responder};
+use validator::Validate;
+use crate::models::{
+    ErrorResponse, Scope, ScopeResponse, CreateScopeRequest, UpdateScopeRequest,
+    ScopeTranslationRequest, UpdateClientRequest, AdminUserQuery, SuspendUserRequest, UserStatus,
+};
+use crate::scope_service::{ScopeService, ScopeError};
+use crate::client_service::{ClientService, ClientError};
+use crate::scope_utils::is_valid_scope_token;
+use crate::services::{UserError, UserService};
+use crate::login_throttle::LoginThrottle;
+use crate::hashing_pool::HashingPool;
+use crate::session_service::SessionService;
+use crate::token_service::TokenService;
+use crate::password_reset::PasswordResetService;
+
+fn internal_error(e: &dyn std::fmt::Display) -> HttpResponse {
+    eprintln!("Database error: {}", e);
+    HttpResponse::InternalServerError().json(ErrorResponse {
+        error: "Internal server error".to_string(),
+    })
+}
+
+fn user_not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(ErrorResponse {
+        error: "User not found".to_string(),
+    })
+}
+
+// Выход пользователя на всех устройствах и отзыв токенов, выданных приложениям
+async fn revoke_user_access(
+    session_service: &SessionService,
+    token_service: &TokenService,
+    user_id: uuid::Uuid,
+) -> Result<(u64, u64), sqlx::Error> {
+    let sessions = session_service.revoke_user_sessions(user_id).await?;
+    let tokens = token_service.revoke_user_tokens(user_id).await?;
+    Ok((sessions, tokens))
+}
+
+// Преобразование ошибки реестра scopes в HTTP ответ
+fn scope_error_response(e: ScopeError) -> HttpResponse {
+    match e {
+        ScopeError::NotFound => HttpResponse::NotFound().json(ErrorResponse {
+            error: e.to_string(),
+        }),
+        ScopeError::AlreadyExists | ScopeError::InUse => HttpResponse::Conflict().json(ErrorResponse {
+            error: e.to_string(),
+        }),
+        ScopeError::UnknownScope(_) | ScopeError::RestrictedScope(_) => HttpResponse::BadRequest().json(ErrorResponse {
+            error: e.to_string(),
+        }),
+        ScopeError::DatabaseError(e) => {
+            eprintln!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            })
+        }
+    }
+}
+
+// Scope вместе с переводами описания
+async fn scope_with_translations(scope_service: &ScopeService, scope: Scope) -> Result<ScopeResponse, ScopeError> {
+    let translations = scope_service.get_translations(&[scope.scope_name.as_str()])
+        .await?
+        .into_iter()
+        .map(|t| (t.locale, t.description))
+        .collect();
+
+    Ok(ScopeResponse { scope, translations })
+}
+
+// GET /api/admin/scopes - список scopes
+pub async fn list_scopes(scope_service: web::Data<ScopeService>) -> impl Responder {
+    let scopes = match scope_service.list_scopes().await {
+        Ok(scopes) => scopes,
+        Err(e) => return scope_error_response(e),
+    };
+
+    let names: Vec<&str> = scopes.iter().map(|s| s.scope_name.as_str()).collect();
+    let translations = match scope_service.get_translations(&names).await {
+        Ok(translations) => translations,
+        Err(e) => return scope_error_response(e),
+    };
+
+    let response: Vec<ScopeResponse> = scopes.into_iter()
+        .map(|scope| {
+            let translations = translations.iter()
+                .filter(|t| t.scope_name == scope.scope_name)
+                .map(|t| (t.locale.clone(), t.description.clone()))
+                .collect();
+            ScopeResponse { scope, translations }
+        })
+        .collect();
+
+    HttpResponse::Ok().json(response)
+}
+
+// GET /api/admin/scopes/{scope_name}
+pub async fn get_scope(
+    path: web::Path<String>,
+    scope_service: web::Data<ScopeService>,
+) -> impl Responder {
+    let scope = match scope_service.get_scope(&path).await {
+        Ok(Some(scope)) => scope,
+        Ok(None) => return scope_error_response(ScopeError::NotFound),
+        Err(e) => return scope_error_response(e),
+    };
+
+    match scope_with_translations(&scope_service, scope).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(e) => scope_error_response(e),
+    }
+}
+
+// POST /api/admin/scopes - создание scope
+pub async fn create_scope(
+    scope_service: web::Data<ScopeService>,
+    request: web::Json<CreateScopeRequest>,
+) -> impl Responder {
+    if let Err(errors) = request.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Validation error: {}", errors),
+        });
+    }
+
+    if !is_valid_scope_token(&request.scope_name) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "Invalid scope name".to_string(),
+        });
+    }
+
+    let scope = match scope_service.create_scope(&request).await {
+        Ok(scope) => scope,
+        Err(e) => return scope_error_response(e),
+    };
+
+    match scope_with_translations(&scope_service, scope).await {
+        Ok(response) => HttpResponse::Created().json(response),
+        Err(e) => scope_error_response(e),
+    }
+}
+
+// PATCH /api/admin/scopes/{scope_name} - изменение описания и флагов
+pub async fn update_scope(
+    path: web::Path<String>,
+    scope_service: web::Data<ScopeService>,
+    request: web::Json<UpdateScopeRequest>,
+) -> impl Responder {
+    if let Err(errors) = request.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Validation error: {}", errors),
+        });
+    }
+
+    let scope = match scope_service.update_scope(&path, &request).await {
+        Ok(scope) => scope,
+        Err(e) => return scope_error_response(e),
+    };
+
+    match scope_with_translations(&scope_service, scope).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(e) => scope_error_response(e),
+    }
+}
+
+// DELETE /api/admin/scopes/{scope_name}
+pub async fn delete_scope(
+    path: web::Path<String>,
+    scope_service: web::Data<ScopeService>,
+) -> impl Responder {
+    match scope_service.delete_scope(&path).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => scope_error_response(e),
+    }
+}
+
+// PUT /api/admin/scopes/{scope_name}/translations/{locale}
+pub async fn put_translation(
+    path: web::Path<(String, String)>,
+    scope_service: web::Data<ScopeService>,
+    request: web::Json<ScopeTranslationRequest>,
+) -> impl Responder {
+    if let Err(errors) = request.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Validation error: {}", errors),
+        });
+    }
+
+    let (scope_name, locale) = path.into_inner();
+    match scope_service.set_translation(&scope_name, &locale, &request.description).await {
+        Ok(translation) => HttpResponse::Ok().json(translation),
+        Err(e) => scope_error_response(e),
+    }
+}
+
+// DELETE /api/admin/scopes/{scope_name}/translations/{locale}
+pub async fn delete_translation(
+    path: web::Path<(String, String)>,
+    scope_service: web::Data<ScopeService>,
+) -> impl Responder {
+    let (scope_name, locale) = path.into_inner();
+    match scope_service.delete_translation(&scope_name, &locale).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => scope_error_response(e),
+    }
+}
+
+// PATCH /api/admin/clients/{client_id} - изменение признака first-party
+pub async fn update_client(
+    path: web::Path<String>,
+    client_service: web::Data<ClientService>,
+    request: web::Json<UpdateClientRequest>,
+) -> impl Responder {
+    let is_first_party = match request.is_first_party {
+        Some(value) => value,
+        None => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                error: "Nothing to update".to_string(),
+            });
+        }
+    };
+
+    match client_service.set_first_party(&path, is_first_party).await {
+        Ok(client) => HttpResponse::Ok().json(serde_json::json!({
+            "client_id": client.client_id,
+            "client_name": client.client_name,
+            "is_first_party": client.is_first_party,
+        })),
+        Err(ClientError::ClientNotFound) => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Client not found".to_string(),
+        }),
+        Err(e) => {
+            eprintln!("Error updating client: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            })
+        }
+    }
+}
+
+// POST /api/admin/users/{user_id}/unlock - снятие блокировки входа после неудачных попыток
+pub async fn unlock_user(
+    path: web::Path<uuid::Uuid>,
+    user_service: web::Data<UserService>,
+    login_throttle: web::Data<LoginThrottle>,
+) -> impl Responder {
+    let user = match user_service.get_user_by_id(path.into_inner()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "User not found".to_string(),
+            });
+        }
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            });
+        }
+    };
+
+    let locked_until = match login_throttle.account_locked_until(&user.email).await {
+        Ok(locked_until) => locked_until,
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            });
+        }
+    };
+
+    match login_throttle.unlock_account(&user.email).await {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
+            "user_id": user.id,
+            "was_locked": locked_until.is_some(),
+            "locked_until": locked_until,
+        })),
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            })
+        }
+    }
+}
+
+// GET /api/admin/users?q=&status=&email_verified=&page=&per_page= - поиск пользователей
+pub async fn list_users(
+    user_service: web::Data<UserService>,
+    query: web::Query<AdminUserQuery>,
+) -> impl Responder {
+    if let Err(errors) = query.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Validation error: {}", errors),
+        });
+    }
+
+    match user_service.list_users(&query).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(e) => internal_error(&e),
+    }
+}
+
+// GET /api/admin/users/{user_id} - пользователь и блокировка входа после неудачных попыток
+pub async fn get_user(
+    path: web::Path<uuid::Uuid>,
+    user_service: web::Data<UserService>,
+    login_throttle: web::Data<LoginThrottle>,
+) -> impl Responder {
+    let user = match user_service.get_admin_user(path.into_inner()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return user_not_found(),
+        Err(e) => return internal_error(&e),
+    };
+
+    match login_throttle.account_locked_until(&user.email).await {
+        Ok(locked_until) => HttpResponse::Ok().json(serde_json::json!({
+            "user": user,
+            "locked_until": locked_until,
+        })),
+        Err(e) => internal_error(&e),
+    }
+}
+
+// POST /api/admin/users/{user_id}/suspend - приостановка: вход и выдача токенов запрещены,
+// сессии завершаются, токены отзываются
+pub async fn suspend_user(
+    path: web::Path<uuid::Uuid>,
+    user_service: web::Data<UserService>,
+    session_service: web::Data<SessionService>,
+    token_service: web::Data<TokenService>,
+    request: web::Json<SuspendUserRequest>,
+) -> impl Responder {
+    if let Err(errors) = request.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Validation error: {}", errors),
+        });
+    }
+    let reason = request.reason.as_deref().map(str::trim).filter(|r| !r.is_empty());
+
+    let user = match user_service.set_status(path.into_inner(), UserStatus::Suspended, reason).await {
+        Ok(user) => user,
+        Err(UserError::NotFound) => return user_not_found(),
+        Err(e) => return internal_error(&e),
+    };
+
+    match revoke_user_access(&session_service, &token_service, user.id).await {
+        Ok((sessions, tokens)) => HttpResponse::Ok().json(serde_json::json!({
+            "user": user,
+            "sessions_revoked": sessions,
+            "tokens_revoked": tokens,
+        })),
+        Err(e) => internal_error(&e),
+    }
+}
+
+// POST /api/admin/users/{user_id}/reactivate - снятие приостановки
+pub async fn reactivate_user(
+    path: web::Path<uuid::Uuid>,
+    user_service: web::Data<UserService>,
+) -> impl Responder {
+    match user_service.set_status(path.into_inner(), UserStatus::Active, None).await {
+        Ok(user) => HttpResponse::Ok().json(user),
+        Err(UserError::NotFound) => user_not_found(),
+        Err(e) => internal_error(&e),
+    }
+}
+
+// POST /api/admin/users/{user_id}/force-password-reset - вход по паролю запрещен до сброса,
+// сессии и токены отзываются, на email отправляется ссылка сброса
+pub async fn force_password_reset(
+    path: web::Path<uuid::Uuid>,
+    user_service: web::Data<UserService>,
+    session_service: web::Data<SessionService>,
+    token_service: web::Data<TokenService>,
+    reset_service: web::Data<PasswordResetService>,
+) -> impl Responder {
+    let user = match user_service.require_password_reset(path.into_inner()).await {
+        Ok(user) => user,
+        Err(UserError::NotFound) => return user_not_found(),
+        Err(e) => return internal_error(&e),
+    };
+
+    let (sessions, tokens) = match revoke_user_access(&session_service, &token_service, user.id).await {
+        Ok(counts) => counts,
+        Err(e) => return internal_error(&e),
+    };
+
+    // Требование действует и без письма: пользователь может запросить ссылку сам
+    let reset_link_sent = match reset_service.request_reset(&user).await {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Password reset error: {}", e);
+            false
+        }
+    };
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "user_id": user.id,
+        "reset_link_sent": reset_link_sent,
+        "sessions_revoked": sessions,
+        "tokens_revoked": tokens,
+    }))
+}
+
+// POST /api/admin/users/{user_id}/force-logout - завершение всех сессий и отзыв всех токенов
+pub async fn force_logout(
+    path: web::Path<uuid::Uuid>,
+    user_service: web::Data<UserService>,
+    session_service: web::Data<SessionService>,
+    token_service: web::Data<TokenService>,
+) -> impl Responder {
+    let user = match user_service.get_admin_user(path.into_inner()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return user_not_found(),
+        Err(e) => return internal_error(&e),
+    };
+
+    match revoke_user_access(&session_service, &token_service, user.id).await {
+        Ok((sessions, tokens)) => HttpResponse::Ok().json(serde_json::json!({
+            "user_id": user.id,
+            "sessions_revoked": sessions,
+            "tokens_revoked": tokens,
+        })),
+        Err(e) => internal_error(&e),
+    }
+}
+
+// POST /api/admin/tokens/{jti}/revoke - отзыв токена по jti (например, найденному в логах API)
+pub async fn revoke_token(
+    path: web::Path<String>,
+    token_service: web::Data<TokenService>,
+) -> impl Responder {
+    let jti = path.into_inner();
+
+    match token_service.revoke_by_jti(&jti).await {
+        Ok(true) => HttpResponse::Ok().json(serde_json::json!({
+            "jti": jti,
+            "revoked": true,
+        })),
+        Ok(false) => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Token not found".to_string(),
+        }),
+        Err(e) => internal_error(&e),
+    }
+}
+
+// GET /api/admin/metrics/hashing - загрузка пула хеширования и время ожидания в очереди
+pub async fn hashing_metrics(hashing_pool: web::Data<HashingPool>) -> impl Responder {
+    HttpResponse::Ok().json(hashing_pool.metrics())
+}
+
+// Конфигурация административных маршрутов (монтируются в /api/admin за AuthMiddleware)
+pub fn configure_admin_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/scopes", web::get().to(list_scopes))
+       .route("/scopes", web::post().to(create_scope))
+       .route("/scopes/{scope_name}", web::get().to(get_scope))
+       .route("/scopes/{scope_name}", web::patch().to(update_scope))
+       .route("/scopes/{scope_name}", web::delete().to(delete_scope))
+       .route("/scopes/{scope_name}/translations/{locale}", web::put().to(put_translation))
+       .route("/scopes/{scope_name}/translations/{locale}", web::delete().to(delete_translation))
+       .route("/clients/{client_id}", web::patch().to(update_client))
+       .route("/users", web::get().to(list_users))
+       .route("/users/{user_id}", web::get().to(get_user))
+       .route("/users/{user_id}/suspend", web::post().to(suspend_user))
+       .route("/users/{user_id}/reactivate", web::post().to(reactivate_user))
+       .route("/users/{user_id}/force-password-reset", web::post().to(force_password_reset))
+       .route("/users/{user_id}/force-logout", web::post().to(force_logout))
+       .route("/users/{user_id}/unlock", web::post().to(unlock_user))
+       .route("/tokens/{jti}/revoke", web::post().to(revoke_token))
+       .route("/metrics/hashing", web::get().to(hashing_metrics));
+}
@@ -1,9 +1,25 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, http::StatusCode, HttpRequest, HttpResponse, Responder};
 use actix_session::Session;
 use validator::Validate;
-use bcrypt::verify;
-use crate::models::{LoginRequest, ErrorResponse, RegisterUserResponse};
+use crate::models::{
+    LoginRequest, ErrorResponse, RegisterUserResponse, AuthContext, VerifyEmailQuery, ResendVerificationRequest,
+    ForgotPasswordRequest, ResetPasswordRequest, ChangePasswordRequest, MfaCodeRequest, PasskeyAssertionRequest,
+};
+use chrono::Utc;
 use crate::services::UserService;
+use crate::config::AppConfig;
+use crate::email_verification::{EmailVerificationService, VerificationError};
+use crate::session_service::SessionService;
+use crate::password_reset::{PasswordResetService, PasswordResetError};
+use crate::token_service::TokenService;
+use crate::mfa_service::{MfaService, MfaError};
+use crate::pending_login::{PendingAttempt, PendingLoginService};
+use crate::passkey_service::{LoginChallenge, PasskeyService, PasskeyError};
+use crate::login_throttle::{client_ip, LoginThrottle};
+use crate::password_policy::PasswordPolicy;
+use crate::handlers::password_policy_error_response;
+use crate::account_handlers::{session_user, verify_current_password};
+use crate::webauthn::{self, Assertion};
 
 // Login page (HTML form)
 pub async fn login_page() -> impl Responder {
@@ -22,6 +38,8 @@ pub async fn login_page() -> impl Responder {
         button:hover { background-color: #0056b3; }
         .error { color: red; text-align: center; }
         .register-link { text-align: center; margin-top: 20px; }
+        .passkey { background-color: #6c757d; }
+        #mfaPasskey { display: flex; flex-direction: column; margin-top: 15px; }
     </style>
 </head>
 <body>
@@ -30,9 +48,19 @@ pub async fn login_page() -> impl Responder {
         <input type="email" name="email" placeholder="Email" required>
         <input type="password" name="password" placeholder="Пароль" required>
         <button type="submit">Войти</button>
+        <button type="button" class="passkey" id="passkeyLogin">Войти с ключом доступа</button>
     </form>
+    <form id="mfaForm" style="display: none;">
+        <p>Введите код из приложения-аутентификатора или код восстановления</p>
+        <input type="text" name="code" placeholder="Код" autocomplete="one-time-code" required>
+        <button type="submit">Подтвердить</button>
+    </form>
+    <div id="mfaPasskey" style="display: none;">
+        <button type="button" class="passkey" id="passkeySecondFactor">Подтвердить ключом доступа</button>
+    </div>
     <div class="register-link">
         <p>Нет аккаунта? <a href="/api/register">Зарегистрироваться</a></p>
+        <p><a href="/auth/password/forgot">Забыли пароль?</a></p>
     </div>
     <div class="error" id="error"></div>
 
@@ -60,9 +88,12 @@ pub async fn login_page() -> impl Responder {
 
                 if (response.ok) {
                     const result = await response.json();
-                    // Redirect to the original destination or default page
-                    const returnTo = urlParams.get('return_to') || '/api/health';
-                    window.location.href = returnTo;
+                    if (result.mfa_required) {
+                        // Пароль принят, требуется второй фактор
+                        showSecondFactor(result.mfa_methods || ['totp']);
+                        return;
+                    }
+                    redirectAfterLogin();
                 } else {
                     const error = await response.json();
                     document.getElementById('error').textContent = error.error || 'Ошибка входа';
@@ -71,6 +102,108 @@ pub async fn login_page() -> impl Responder {
                 document.getElementById('error').textContent = 'Ошибка соединения';
             }
         });
+
+        document.getElementById('mfaForm').addEventListener('submit', async (e) => {
+            e.preventDefault();
+            const formData = new FormData(e.target);
+
+            try {
+                const response = await fetch('/auth/login/mfa', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({ code: formData.get('code') })
+                });
+
+                if (response.ok) {
+                    redirectAfterLogin();
+                } else {
+                    const error = await response.json();
+                    document.getElementById('error').textContent = error.error || 'Неверный код';
+                    if (response.status === 401 && error.error !== 'Invalid verification code') {
+                        // Попытка входа истекла, начинаем заново
+                        showPasswordForm();
+                    }
+                }
+            } catch (err) {
+                document.getElementById('error').textContent = 'Ошибка соединения';
+            }
+        });
+
+        function showSecondFactor(methods) {
+            document.getElementById('loginForm').style.display = 'none';
+            document.getElementById('mfaForm').style.display = methods.includes('totp') ? 'flex' : 'none';
+            document.getElementById('mfaPasskey').style.display = methods.includes('passkey') ? 'flex' : 'none';
+            document.getElementById('error').textContent = '';
+        }
+
+        function showPasswordForm() {
+            document.getElementById('mfaForm').style.display = 'none';
+            document.getElementById('mfaPasskey').style.display = 'none';
+            document.getElementById('loginForm').style.display = 'flex';
+        }
+
+        function fromBase64url(value) {
+            const base64 = value.replace(/-/g, '+').replace(/_/g, '/');
+            return Uint8Array.from(atob(base64), c => c.charCodeAt(0));
+        }
+
+        function toBase64url(buffer) {
+            const bytes = String.fromCharCode(...new Uint8Array(buffer));
+            return btoa(bytes).replace(/\+/g, '-').replace(/\//g, '_').replace(/=+$/, '');
+        }
+
+        // Вход по ключу доступа: без пароля или вторым фактором после пароля
+        async function loginWithPasskey(secondFactor) {
+            if (!window.PublicKeyCredential) {
+                document.getElementById('error').textContent = 'Браузер не поддерживает ключи доступа';
+                return;
+            }
+
+            try {
+                const optionsResponse = await fetch('/auth/login/passkey/options', { method: 'POST' });
+                const options = await optionsResponse.json();
+                if (!optionsResponse.ok) {
+                    document.getElementById('error').textContent = options.error || 'Ошибка входа';
+                    return;
+                }
+                options.challenge = fromBase64url(options.challenge);
+                options.allowCredentials = options.allowCredentials.map(c => ({ ...c, id: fromBase64url(c.id) }));
+
+                const credential = await navigator.credentials.get({ publicKey: options });
+                const response = await fetch('/auth/login/passkey', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({
+                        id: credential.id,
+                        client_data_json: toBase64url(credential.response.clientDataJSON),
+                        authenticator_data: toBase64url(credential.response.authenticatorData),
+                        signature: toBase64url(credential.response.signature),
+                        user_handle: credential.response.userHandle ? toBase64url(credential.response.userHandle) : null
+                    })
+                });
+
+                if (response.ok) {
+                    redirectAfterLogin();
+                } else {
+                    const error = await response.json();
+                    document.getElementById('error').textContent = error.error || 'Ошибка входа';
+                    if (secondFactor && response.status === 401) {
+                        showPasswordForm();
+                    }
+                }
+            } catch (err) {
+                document.getElementById('error').textContent = 'Вход по ключу доступа отменен';
+            }
+        }
+
+        document.getElementById('passkeyLogin').addEventListener('click', () => loginWithPasskey(false));
+        document.getElementById('passkeySecondFactor').addEventListener('click', () => loginWithPasskey(true));
+
+        function redirectAfterLogin() {
+            // Redirect to the original destination or default page
+            const returnTo = urlParams.get('return_to') || '/api/health';
+            window.location.href = returnTo;
+        }
     </script>
 </body>
 </html>
@@ -81,9 +214,139 @@ pub async fn login_page() -> impl Responder {
         .body(html)
 }
 
+// Завершение входа: запись сессии на сервере и сохранение пользователя в cookie-сессии
+async fn establish_session(
+    req: &HttpRequest,
+    session_service: &SessionService,
+    session: &Session,
+    user_id: uuid::Uuid,
+    auth_context: AuthContext,
+) -> Result<(), HttpResponse> {
+    // Регистрация сессии на сервере, чтобы ее можно было отозвать
+    let user_agent = req.headers().get("User-Agent").and_then(|v| v.to_str().ok());
+    let ip_address = client_ip(req);
+    let session_id = session_service
+        .create_session(user_id, user_agent, ip_address.as_deref())
+        .await
+        .map_err(|e| {
+            eprintln!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to create session".to_string(),
+            })
+        })?;
+
+    // Сохранение user_id и контекста аутентификации в сессии
+    session.renew();
+    session.insert("user_id", user_id.to_string())
+        .and_then(|_| session.insert("session_id", session_id.to_string()))
+        .and_then(|_| session.insert("auth_context", auth_context))
+        .map_err(|e| {
+            eprintln!("Session error: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to create session".to_string(),
+            })
+        })
+}
+
+// Вторые факторы, подключенные пользователем: "totp" и/или "passkey"
+async fn second_factor_methods(
+    mfa_service: &MfaService,
+    passkey_service: &PasskeyService,
+    user_id: uuid::Uuid,
+) -> Result<Vec<&'static str>, sqlx::Error> {
+    let mut methods = Vec::new();
+    if mfa_service.is_enabled(user_id).await? {
+        methods.push("totp");
+    }
+    if passkey_service.has_passkeys(user_id).await? {
+        methods.push("passkey");
+    }
+    Ok(methods)
+}
+
+// Идентификатор незавершенного входа со вторым фактором; состояние входа хранится в БД
+fn pending_login_token(session: &Session) -> Option<String> {
+    session.get::<String>("mfa_login").ok().flatten()
+}
+
+// Завершение незавершенного входа: идентификатор из cookie больше не действует
+async fn clear_pending_mfa(pending_login_service: &PendingLoginService, session: &Session) {
+    if let Some(token) = pending_login_token(session) {
+        if let Err(e) = pending_login_service.finish(&token).await {
+            eprintln!("Database error: {}", e);
+        }
+    }
+    session.remove("mfa_login");
+}
+
+// Неудачная проверка второго фактора учитывается в блокировке аккаунта, как неверный пароль.
+// После исчерпания попыток вход нужно начинать заново с пароля
+async fn record_mfa_failure(
+    pending_login_service: &PendingLoginService,
+    login_throttle: &LoginThrottle,
+    session: &Session,
+    attempt: PendingAttempt,
+    email: &str,
+    ip_address: Option<&str>,
+) {
+    if attempt.is_last() {
+        clear_pending_mfa(pending_login_service, session).await;
+        session.purge();
+    }
+    match login_throttle.record_failure(email, ip_address).await {
+        Ok(delay) => actix_web::rt::time::sleep(delay).await,
+        Err(e) => eprintln!("Database error: {}", e),
+    }
+}
+
+fn invalid_credentials() -> HttpResponse {
+    HttpResponse::Unauthorized().json(ErrorResponse {
+        error: "Invalid credentials".to_string(),
+    })
+}
+
+// Приостановленный администратором аккаунт; сообщается только после проверки пароля или ключа
+fn account_suspended() -> HttpResponse {
+    HttpResponse::Forbidden().json(ErrorResponse {
+        error: "Account suspended".to_string(),
+    })
+}
+
+// Неудачная попытка входа (неверный пароль, несуществующий или заблокированный аккаунт):
+// учет неудачи и задержка ответа, растущая с числом неудач подряд
+async fn reject_login(login_throttle: &LoginThrottle, email: &str, ip_address: Option<&str>) -> HttpResponse {
+    match login_throttle.record_failure(email, ip_address).await {
+        Ok(delay) => actix_web::rt::time::sleep(delay).await,
+        Err(e) => eprintln!("Database error: {}", e),
+    }
+    invalid_credentials()
+}
+
+// Вход без проверки настоящего пароля: хеширование по фиктивному хешу и тот же ответ,
+// что и при неверном пароле
+async fn reject_without_password(
+    user_service: &UserService,
+    login_throttle: &LoginThrottle,
+    request: &LoginRequest,
+    ip_address: Option<&str>,
+) -> HttpResponse {
+    if let Err(e) = user_service.verify_dummy_password(&request.password).await {
+        eprintln!("Password verification error: {}", e);
+    }
+    reject_login(login_throttle, &request.email, ip_address).await
+}
+
 // Login handler
+#[allow(clippy::too_many_arguments)]
 pub async fn login(
+    req: HttpRequest,
     user_service: web::Data<UserService>,
+    session_service: web::Data<SessionService>,
+    mfa_service: web::Data<MfaService>,
+    passkey_service: web::Data<PasskeyService>,
+    pending_login_service: web::Data<PendingLoginService>,
+    login_throttle: web::Data<LoginThrottle>,
+    config: web::Data<AppConfig>,
     session: Session,
     request: web::Json<LoginRequest>,
 ) -> impl Responder {
@@ -94,25 +357,86 @@ pub async fn login(
         });
     }
 
+    let ip_address = client_ip(&req);
+
+    // Во время блокировки пароль не проверяется, ответ и задержка не отличаются от неверного пароля
+    match login_throttle.is_locked(&request.email, ip_address.as_deref()).await {
+        Ok(false) => {}
+        Ok(true) => {
+            return reject_without_password(&user_service, &login_throttle, &request, ip_address.as_deref()).await;
+        }
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            });
+        }
+    }
+
     // Получение пользователя по email
     match user_service.get_user_by_email(&request.email).await {
         Ok(Some(user)) => {
             // Проверка пароля
-            match verify(&request.password, &user.password_hash) {
+            match user_service.verify_password(&user, &request.password).await {
+                Ok(true) if user.is_suspended() => account_suspended(),
+                // Администратор потребовал сменить пароль: вход только после сброса по ссылке из письма
+                Ok(true) if user.password_reset_required => HttpResponse::Forbidden().json(ErrorResponse {
+                    error: "Password reset required".to_string(),
+                }),
+                Ok(true) if config.email_verification.required && !user.email_verified => {
+                    HttpResponse::Forbidden().json(ErrorResponse {
+                        error: "Email not verified".to_string(),
+                    })
+                }
                 Ok(true) => {
-                    // Сохранение user_id в сессии
-                    if let Err(e) = session.insert("user_id", user.id.to_string()) {
-                        eprintln!("Session error: {}", e);
-                        return HttpResponse::InternalServerError().json(ErrorResponse {
-                            error: "Failed to create session".to_string(),
-                        });
+                    let methods = match second_factor_methods(&mfa_service, &passkey_service, user.id).await {
+                        Ok(methods) => methods,
+                        Err(e) => {
+                            eprintln!("Database error: {}", e);
+                            return HttpResponse::InternalServerError().json(ErrorResponse {
+                                error: "Internal server error".to_string(),
+                            });
+                        }
+                    };
+
+                    if !methods.is_empty() {
+                        // Сессия остается не аутентифицированной до проверки второго фактора.
+                        // Счетчик неудач аккаунта сбрасывается только после него
+                        let token = match pending_login_service.start(user.id).await {
+                            Ok(token) => token,
+                            Err(e) => {
+                                eprintln!("Database error: {}", e);
+                                return HttpResponse::InternalServerError().json(ErrorResponse {
+                                    error: "Internal server error".to_string(),
+                                });
+                            }
+                        };
+                        session.renew();
+                        if let Err(e) = session.insert("mfa_login", token) {
+                            eprintln!("Session error: {}", e);
+                            return HttpResponse::InternalServerError().json(ErrorResponse {
+                                error: "Failed to create session".to_string(),
+                            });
+                        }
+
+                        return HttpResponse::Ok().json(serde_json::json!({
+                            "mfa_required": true,
+                            "mfa_methods": methods
+                        }));
+                    }
+
+                    if let Err(e) = login_throttle.record_success(&request.email).await {
+                        eprintln!("Database error: {}", e);
+                    }
+                    if let Err(response) = establish_session(
+                        &req, &session_service, &session, user.id, AuthContext::password(Utc::now()),
+                    ).await {
+                        return response;
                     }
 
                     HttpResponse::Ok().json(RegisterUserResponse::from(user))
                 }
-                Ok(false) => HttpResponse::Unauthorized().json(ErrorResponse {
-                    error: "Invalid credentials".to_string(),
-                }),
+                Ok(false) => reject_login(&login_throttle, &request.email, ip_address.as_deref()).await,
                 Err(e) => {
                     eprintln!("Password verification error: {}", e);
                     HttpResponse::InternalServerError().json(ErrorResponse {
@@ -121,9 +445,154 @@ pub async fn login(
                 }
             }
         }
-        Ok(None) => HttpResponse::Unauthorized().json(ErrorResponse {
-            error: "Invalid credentials".to_string(),
-        }),
+        Ok(None) => reject_without_password(&user_service, &login_throttle, &request, ip_address.as_deref()).await,
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            })
+        }
+    }
+}
+
+// POST /auth/login/mfa - второй шаг входа: проверка кода TOTP или кода восстановления
+#[allow(clippy::too_many_arguments)]
+pub async fn login_mfa(
+    req: HttpRequest,
+    user_service: web::Data<UserService>,
+    session_service: web::Data<SessionService>,
+    mfa_service: web::Data<MfaService>,
+    pending_login_service: web::Data<PendingLoginService>,
+    login_throttle: web::Data<LoginThrottle>,
+    session: Session,
+    request: web::Json<MfaCodeRequest>,
+) -> impl Responder {
+    if let Err(errors) = request.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Validation error: {}", errors),
+        });
+    }
+
+    let expired = || HttpResponse::Unauthorized().json(ErrorResponse {
+        error: "Login session expired".to_string(),
+    });
+    let internal_error = |e: &dyn std::fmt::Display| {
+        eprintln!("Database error: {}", e);
+        HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        })
+    };
+
+    let Some(token) = pending_login_token(&session) else {
+        return expired();
+    };
+    // Попытка расходуется до проверки кода
+    let attempt = match pending_login_service.begin_attempt(&token).await {
+        Ok(Some(attempt)) => attempt,
+        Ok(None) => {
+            session.purge();
+            return expired();
+        }
+        Err(e) => return internal_error(&e),
+    };
+
+    let user = match user_service.get_user_by_id(attempt.user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            clear_pending_mfa(&pending_login_service, &session).await;
+            session.purge();
+            return expired();
+        }
+        Err(e) => return internal_error(&e),
+    };
+
+    // Аккаунт заблокирован после проверки пароля (в том числе неудачами второго фактора)
+    let ip_address = client_ip(&req);
+    match login_throttle.is_locked(&user.email, ip_address.as_deref()).await {
+        Ok(false) => {}
+        Ok(true) => {
+            clear_pending_mfa(&pending_login_service, &session).await;
+            session.purge();
+            return expired();
+        }
+        Err(e) => return internal_error(&e),
+    }
+
+    match mfa_service.verify(user.id, &request.code).await {
+        Ok(()) => {}
+        Err(MfaError::InvalidCode) => {
+            record_mfa_failure(
+                &pending_login_service, &login_throttle, &session, attempt, &user.email, ip_address.as_deref(),
+            ).await;
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "Invalid verification code".to_string(),
+            });
+        }
+        // Второй фактор отключен между шагами входа
+        Err(MfaError::NotEnrolled) => {
+            clear_pending_mfa(&pending_login_service, &session).await;
+            session.purge();
+            return expired();
+        }
+        Err(e) => {
+            eprintln!("MFA verification error: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            });
+        }
+    }
+
+    clear_pending_mfa(&pending_login_service, &session).await;
+
+    // Аккаунт приостановлен между шагами входа
+    if user.is_suspended() {
+        session.purge();
+        return account_suspended();
+    }
+
+    if let Err(e) = login_throttle.record_success(&user.email).await {
+        eprintln!("Database error: {}", e);
+    }
+    if let Err(response) = establish_session(
+        &req, &session_service, &session, user.id, AuthContext::multi_factor(Utc::now()),
+    ).await {
+        return response;
+    }
+
+    HttpResponse::Ok().json(RegisterUserResponse::from(user))
+}
+
+// POST /auth/login/passkey/options - challenge для входа по ключу доступа.
+// После проверки пароля предлагаются ключи пользователя, иначе вход без пароля.
+// Challenge сохраняется в cookie-сессии: запрос без входа не пишет в БД
+pub async fn login_passkey_options(
+    passkey_service: web::Data<PasskeyService>,
+    pending_login_service: web::Data<PendingLoginService>,
+    session: Session,
+) -> impl Responder {
+    let user_id = match pending_login_token(&session) {
+        Some(token) => match pending_login_service.user_id(&token).await {
+            Ok(user_id) => user_id,
+            Err(e) => {
+                eprintln!("Database error: {}", e);
+                return HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "Internal server error".to_string(),
+                });
+            }
+        },
+        None => None,
+    };
+
+    match passkey_service.authentication_options(user_id).await {
+        Ok((options, challenge)) => {
+            if let Err(e) = session.insert("passkey_login", challenge) {
+                eprintln!("Session error: {}", e);
+                return HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "Internal server error".to_string(),
+                });
+            }
+            HttpResponse::Ok().json(options)
+        }
         Err(e) => {
             eprintln!("Database error: {}", e);
             HttpResponse::InternalServerError().json(ErrorResponse {
@@ -133,8 +602,182 @@ pub async fn login(
     }
 }
 
+// POST /auth/login/passkey - вход по ключу доступа: второй фактор или вход без пароля
+#[allow(clippy::too_many_arguments)]
+pub async fn login_passkey(
+    req: HttpRequest,
+    user_service: web::Data<UserService>,
+    session_service: web::Data<SessionService>,
+    passkey_service: web::Data<PasskeyService>,
+    pending_login_service: web::Data<PendingLoginService>,
+    login_throttle: web::Data<LoginThrottle>,
+    config: web::Data<AppConfig>,
+    session: Session,
+    request: web::Json<PasskeyAssertionRequest>,
+) -> impl Responder {
+    if let Err(errors) = request.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Validation error: {}", errors),
+        });
+    }
+
+    let decoded = (
+        webauthn::decode_b64url(&request.client_data_json),
+        webauthn::decode_b64url(&request.authenticator_data),
+        webauthn::decode_b64url(&request.signature),
+    );
+    let (client_data_json, authenticator_data, signature) = match decoded {
+        (Some(client_data_json), Some(authenticator_data), Some(signature)) => {
+            (client_data_json, authenticator_data, signature)
+        }
+        _ => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                error: "Invalid base64url encoding".to_string(),
+            });
+        }
+    };
+    let user_handle = match request.user_handle.as_deref().filter(|h| !h.is_empty()) {
+        Some(handle) => match webauthn::decode_b64url(handle) {
+            Some(handle) => Some(handle),
+            None => {
+                return HttpResponse::BadRequest().json(ErrorResponse {
+                    error: "Invalid base64url encoding".to_string(),
+                });
+            }
+        },
+        None => None,
+    };
+
+    // Challenge одноразовый: удаляется из сессии до проверки
+    let login_challenge = match session.remove_as::<LoginChallenge>("passkey_login") {
+        Some(Ok(challenge)) => challenge,
+        _ => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: PasskeyError::InvalidChallenge.to_string(),
+            });
+        }
+    };
+
+    // Второй фактор после пароля: попытка расходуется до проверки ключа.
+    // Истекший вход не мешает войти по ключу без пароля
+    let pending = match pending_login_token(&session) {
+        Some(token) => match pending_login_service.begin_attempt(&token).await {
+            Ok(Some(attempt)) => Some(attempt),
+            Ok(None) => {
+                session.remove("mfa_login");
+                None
+            }
+            Err(e) => {
+                eprintln!("Database error: {}", e);
+                return HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "Internal server error".to_string(),
+                });
+            }
+        },
+        None => None,
+    };
+    let assertion = Assertion {
+        client_data_json: &client_data_json,
+        authenticator_data: &authenticator_data,
+        signature: &signature,
+    };
+
+    let result = passkey_service
+        .finish_authentication(
+            &login_challenge,
+            pending.map(|attempt| attempt.user_id),
+            &request.id,
+            user_handle.as_deref(),
+            &assertion,
+        )
+        .await;
+    let user_id = match result {
+        Ok(user_id) => user_id,
+        Err(PasskeyError::DatabaseError(e)) => {
+            eprintln!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            });
+        }
+        Err(e) => {
+            if let Some(attempt) = pending {
+                match user_service.get_user_by_id(attempt.user_id).await {
+                    Ok(Some(user)) => {
+                        let ip_address = client_ip(&req);
+                        record_mfa_failure(
+                            &pending_login_service, &login_throttle, &session, attempt, &user.email, ip_address.as_deref(),
+                        ).await;
+                    }
+                    Ok(None) => {}
+                    Err(e) => eprintln!("Database error: {}", e),
+                }
+            }
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: e.to_string(),
+            });
+        }
+    };
+
+    let user = match user_service.get_user_by_id(user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "Unknown passkey".to_string(),
+            });
+        }
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            });
+        }
+    };
+
+    if user.is_suspended() {
+        clear_pending_mfa(&pending_login_service, &session).await;
+        return account_suspended();
+    }
+
+    // При входе по паролю email уже проверен до второго фактора
+    if pending.is_none() && config.email_verification.required && !user.email_verified {
+        return HttpResponse::Forbidden().json(ErrorResponse {
+            error: "Email not verified".to_string(),
+        });
+    }
+
+    let auth_context = if pending.is_some() {
+        AuthContext::password_and_passkey(Utc::now())
+    } else {
+        AuthContext::passkey(Utc::now())
+    };
+
+    clear_pending_mfa(&pending_login_service, &session).await;
+    if pending.is_some() {
+        if let Err(e) = login_throttle.record_success(&user.email).await {
+            eprintln!("Database error: {}", e);
+        }
+    }
+
+    if let Err(response) = establish_session(&req, &session_service, &session, user.id, auth_context).await {
+        return response;
+    }
+
+    HttpResponse::Ok().json(RegisterUserResponse::from(user))
+}
+
 // Logout handler
-pub async fn logout(session: Session) -> impl Responder {
+pub async fn logout(
+    session_service: web::Data<SessionService>,
+    session: Session,
+) -> impl Responder {
+    if let Some(session_id) = session.get::<String>("session_id").ok().flatten()
+        .and_then(|id| id.parse::<uuid::Uuid>().ok())
+    {
+        if let Err(e) = session_service.revoke_session(session_id).await {
+            eprintln!("Database error: {}", e);
+        }
+    }
+
     session.purge();
     HttpResponse::Ok().json(serde_json::json!({
         "message": "Logged out successfully"
@@ -183,14 +826,448 @@ pub async fn me(
     }
 }
 
+// GET /auth/verify-email?token=... - переход по ссылке из письма
+pub async fn verify_email(
+    verification_service: web::Data<EmailVerificationService>,
+    query: web::Query<VerifyEmailQuery>,
+) -> impl Responder {
+    let (status, message) = match verification_service.verify_email(&query.token).await {
+        Ok(_) => (StatusCode::OK, "Email подтвержден. Теперь вы можете войти в систему."),
+        Err(VerificationError::TokenExpired) => (
+            StatusCode::BAD_REQUEST,
+            "Срок действия ссылки истек. Запросите письмо повторно.",
+        ),
+        Err(VerificationError::TokenAlreadyUsed) => (
+            StatusCode::BAD_REQUEST,
+            "Ссылка уже была использована.",
+        ),
+        Err(VerificationError::InvalidToken) => (
+            StatusCode::BAD_REQUEST,
+            "Недействительная ссылка подтверждения.",
+        ),
+        Err(e) => {
+            eprintln!("Email verification error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Внутренняя ошибка сервера.")
+        }
+    };
+
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="UTF-8">
+    <title>Подтверждение email</title>
+    <style>
+        body {{ font-family: Arial, sans-serif; max-width: 400px; margin: 50px auto; padding: 20px; text-align: center; }}
+    </style>
+</head>
+<body>
+    <h1>Подтверждение email</h1>
+    <p>{}</p>
+    <p><a href="/auth/login">Вход</a></p>
+</body>
+</html>"#,
+        message
+    );
+
+    HttpResponse::build(status)
+        .content_type("text/html; charset=utf-8")
+        .body(html)
+}
+
+// POST /auth/verify-email/resend - повторная отправка письма подтверждения.
+// Ответ не зависит от существования аккаунта
+pub async fn resend_verification(
+    user_service: web::Data<UserService>,
+    verification_service: web::Data<EmailVerificationService>,
+    request: web::Json<ResendVerificationRequest>,
+) -> impl Responder {
+    if let Err(errors) = request.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Validation error: {}", errors),
+        });
+    }
+
+    match user_service.get_user_by_email(&request.email).await {
+        Ok(Some(user)) => {
+            // Как и при сбросе пароля, письмо отправляется в фоне: время ответа
+            // не выдает, существует ли аккаунт и подтвержден ли он
+            let verification_service = verification_service.into_inner();
+            actix_web::rt::spawn(async move {
+                if let Err(e) = verification_service.resend_verification(&user).await {
+                    eprintln!("Email verification error: {}", e);
+                }
+            });
+        }
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            });
+        }
+    }
+
+    HttpResponse::Accepted().json(serde_json::json!({
+        "message": "If the account exists and is not verified, a verification email has been sent"
+    }))
+}
+
+// GET /auth/password/forgot - форма запроса ссылки сброса пароля
+pub async fn forgot_password_page() -> impl Responder {
+    let html = r#"
+<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="UTF-8">
+    <title>Восстановление пароля</title>
+    <style>
+        body { font-family: Arial, sans-serif; max-width: 400px; margin: 50px auto; padding: 20px; }
+        h1 { text-align: center; }
+        form { display: flex; flex-direction: column; gap: 15px; }
+        input { padding: 10px; border: 1px solid #ddd; border-radius: 4px; }
+        button { padding: 10px; background-color: #007bff; color: white; border: none; border-radius: 4px; cursor: pointer; }
+        button:hover { background-color: #0056b3; }
+        .error { color: red; text-align: center; }
+        .message { color: #2e7d32; text-align: center; }
+        .register-link { text-align: center; margin-top: 20px; }
+    </style>
+</head>
+<body>
+    <h1>Восстановление пароля</h1>
+    <form id="forgotForm">
+        <input type="email" name="email" placeholder="Email" required>
+        <button type="submit">Отправить ссылку</button>
+    </form>
+    <div class="register-link">
+        <p><a href="/auth/login">Вернуться ко входу</a></p>
+    </div>
+    <div class="message" id="message"></div>
+    <div class="error" id="error"></div>
+
+    <script>
+        document.getElementById('forgotForm').addEventListener('submit', async (e) => {
+            e.preventDefault();
+            const formData = new FormData(e.target);
+            document.getElementById('error').textContent = '';
+
+            try {
+                const response = await fetch('/auth/password/forgot', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({ email: formData.get('email') })
+                });
+
+                if (response.ok) {
+                    document.getElementById('message').textContent =
+                        'Если аккаунт с таким email существует, на него отправлена ссылка для сброса пароля.';
+                    e.target.reset();
+                } else {
+                    const error = await response.json();
+                    document.getElementById('error').textContent = error.error || 'Ошибка запроса';
+                }
+            } catch (err) {
+                document.getElementById('error').textContent = 'Ошибка соединения';
+            }
+        });
+    </script>
+</body>
+</html>
+    "#;
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html)
+}
+
+// POST /auth/password/forgot - отправка ссылки сброса пароля.
+// Ответ не зависит от существования аккаунта
+pub async fn forgot_password(
+    user_service: web::Data<UserService>,
+    reset_service: web::Data<PasswordResetService>,
+    request: web::Json<ForgotPasswordRequest>,
+) -> impl Responder {
+    if let Err(errors) = request.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Validation error: {}", errors),
+        });
+    }
+
+    match user_service.get_user_by_email(&request.email).await {
+        Ok(Some(user)) => {
+            // Письмо отправляется в фоне, чтобы время ответа не выдавало существование аккаунта
+            let reset_service = reset_service.into_inner();
+            actix_web::rt::spawn(async move {
+                if let Err(e) = reset_service.request_reset(&user).await {
+                    eprintln!("Password reset error: {}", e);
+                }
+            });
+        }
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            });
+        }
+    }
+
+    HttpResponse::Accepted().json(serde_json::json!({
+        "message": "If the account exists, a password reset link has been sent"
+    }))
+}
+
+// GET /auth/password/reset?token=... - форма нового пароля
+pub async fn reset_password_page() -> impl Responder {
+    let html = r#"
+<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="UTF-8">
+    <title>Новый пароль</title>
+    <style>
+        body { font-family: Arial, sans-serif; max-width: 400px; margin: 50px auto; padding: 20px; }
+        h1 { text-align: center; }
+        form { display: flex; flex-direction: column; gap: 15px; }
+        input { padding: 10px; border: 1px solid #ddd; border-radius: 4px; }
+        button { padding: 10px; background-color: #007bff; color: white; border: none; border-radius: 4px; cursor: pointer; }
+        button:hover { background-color: #0056b3; }
+        .error { color: red; text-align: center; }
+        .message { color: #2e7d32; text-align: center; }
+    </style>
+</head>
+<body>
+    <h1>Новый пароль</h1>
+    <form id="resetForm">
+        <input type="password" name="password" placeholder="Новый пароль" minlength="8" required>
+        <input type="password" name="confirm" placeholder="Повторите пароль" minlength="8" required>
+        <button type="submit">Сохранить</button>
+    </form>
+    <div class="message" id="message"></div>
+    <div class="error" id="error"></div>
+
+    <script>
+        const token = new URLSearchParams(window.location.search).get('token') || '';
+
+        document.getElementById('resetForm').addEventListener('submit', async (e) => {
+            e.preventDefault();
+            const formData = new FormData(e.target);
+            document.getElementById('error').textContent = '';
+
+            if (formData.get('password') !== formData.get('confirm')) {
+                document.getElementById('error').textContent = 'Пароли не совпадают';
+                return;
+            }
+
+            try {
+                const response = await fetch('/auth/password/reset', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({ token: token, password: formData.get('password') })
+                });
+
+                if (response.ok) {
+                    e.target.remove();
+                    document.getElementById('message').innerHTML =
+                        'Пароль изменен. <a href="/auth/login">Войти</a>';
+                } else {
+                    const error = await response.json();
+                    const details = (error.violations || []).map(v => v.message).join('. ');
+                    document.getElementById('error').textContent =
+                        details || error.error || 'Ошибка сброса пароля';
+                }
+            } catch (err) {
+                document.getElementById('error').textContent = 'Ошибка соединения';
+            }
+        });
+    </script>
+</body>
+</html>
+    "#;
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html)
+}
+
+// POST /auth/password/reset - установка нового пароля по токену из письма.
+// Все сессии и токены пользователя отзываются
+pub async fn reset_password(
+    user_service: web::Data<UserService>,
+    reset_service: web::Data<PasswordResetService>,
+    session_service: web::Data<SessionService>,
+    token_service: web::Data<TokenService>,
+    password_policy: web::Data<PasswordPolicy>,
+    session: Session,
+    request: web::Json<ResetPasswordRequest>,
+) -> impl Responder {
+    if let Err(errors) = request.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Validation error: {}", errors),
+        });
+    }
+
+    // Новый пароль проверяется до погашения токена, чтобы ссылкой можно было воспользоваться повторно
+    let invalid_token = || HttpResponse::BadRequest().json(ErrorResponse {
+        error: "Invalid or expired reset token".to_string(),
+    });
+    let user = match reset_service.find_token_user(&request.token).await {
+        Ok(Some(user_id)) => match user_service.get_user_by_id(user_id).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return invalid_token(),
+            Err(e) => {
+                eprintln!("Database error: {}", e);
+                return HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "Internal server error".to_string(),
+                });
+            }
+        },
+        Ok(None) => return invalid_token(),
+        Err(e) => {
+            eprintln!("Password reset error: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            });
+        }
+    };
+
+    let violations = password_policy.check(&request.password, &user.username, &user.email).await;
+    if !violations.is_empty() {
+        return password_policy_error_response(violations);
+    }
+
+    let user_id = match reset_service.consume_token(&request.token).await {
+        Ok(user_id) => user_id,
+        Err(PasswordResetError::InvalidToken) => return invalid_token(),
+        Err(e) => {
+            eprintln!("Password reset error: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            });
+        }
+    };
+
+    if let Err(e) = user_service.update_password(user_id, &request.password).await {
+        eprintln!("Password reset error: {}", e);
+        return HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        });
+    }
+
+    // Выход на всех устройствах и отзыв токенов, выданных приложениям
+    let revoked = session_service.revoke_user_sessions(user_id).await
+        .and(token_service.revoke_user_tokens(user_id).await);
+    if let Err(e) = revoked {
+        eprintln!("Failed to revoke sessions: {}", e);
+        return HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        });
+    }
+
+    session.purge();
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "message": "Password has been reset"
+    }))
+}
+
+// POST /auth/password/change - смена пароля пользователем сессии по текущему паролю.
+// Текущая сессия сохраняется; остальные сессии и токены приложений отзываются по запросу
+#[allow(clippy::too_many_arguments)]
+pub async fn change_password(
+    req: HttpRequest,
+    user_service: web::Data<UserService>,
+    session_service: web::Data<SessionService>,
+    token_service: web::Data<TokenService>,
+    login_throttle: web::Data<LoginThrottle>,
+    password_policy: web::Data<PasswordPolicy>,
+    session: Session,
+    request: web::Json<ChangePasswordRequest>,
+) -> impl Responder {
+    let user = match session_user(&user_service, &session).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    if let Err(errors) = request.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Validation error: {}", errors),
+        });
+    }
+    if let Err(response) = verify_current_password(
+        &req, &user_service, &login_throttle, &user, &request.current_password,
+    ).await {
+        return response;
+    }
+
+    if request.new_password == request.current_password {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "New password must differ from the current one".to_string(),
+        });
+    }
+    let violations = password_policy.check(&request.new_password, &user.username, &user.email).await;
+    if !violations.is_empty() {
+        return password_policy_error_response(violations);
+    }
+
+    if let Err(e) = user_service.update_password(user.id, &request.new_password).await {
+        eprintln!("Password change error: {}", e);
+        return HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        });
+    }
+
+    let (mut sessions_revoked, mut tokens_revoked) = (0, 0);
+    if request.sign_out_other_sessions {
+        let current_session_id = session.get::<String>("session_id").ok().flatten()
+            .and_then(|id| id.parse::<uuid::Uuid>().ok());
+        let sessions = match current_session_id {
+            Some(session_id) => session_service.revoke_other_sessions(user.id, session_id).await,
+            None => session_service.revoke_user_sessions(user.id).await,
+        };
+        let revoked = match sessions {
+            Ok(sessions) => token_service.revoke_user_tokens(user.id).await
+                .map(|tokens| (sessions, tokens)),
+            Err(e) => Err(e),
+        };
+        match revoked {
+            Ok(counts) => (sessions_revoked, tokens_revoked) = counts,
+            Err(e) => {
+                eprintln!("Failed to revoke sessions: {}", e);
+                return HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "Internal server error".to_string(),
+                });
+            }
+        }
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "message": "Password has been changed",
+        "sessions_revoked": sessions_revoked,
+        "tokens_revoked": tokens_revoked
+    }))
+}
+
 // Конфигурация маршрутов для аутентификации
 pub fn configure_auth_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/auth")
             .route("/login", web::get().to(login_page))
             .route("/login", web::post().to(login))
+            .route("/login/mfa", web::post().to(login_mfa))
+            .route("/login/passkey/options", web::post().to(login_passkey_options))
+            .route("/login/passkey", web::post().to(login_passkey))
             .route("/logout", web::post().to(logout))
             .route("/me", web::get().to(me))
+            .route("/verify-email", web::get().to(verify_email))
+            .route("/verify-email/resend", web::post().to(resend_verification))
+            .route("/password/forgot", web::get().to(forgot_password_page))
+            .route("/password/forgot", web::post().to(forgot_password))
+            .route("/password/reset", web::get().to(reset_password_page))
+            .route("/password/reset", web::post().to(reset_password))
+            .route("/password/change", web::post().to(change_password))
+            .configure(crate::mfa_handlers::configure_mfa_routes)
+            .configure(crate::passkey_handlers::configure_passkey_routes)
+            .configure(crate::email_change_handlers::configure_email_change_routes)
     );
 }
 
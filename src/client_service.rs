@@ -1,10 +1,69 @@
 use sqlx::{Pool, Postgres};
 use uuid::Uuid;
 use chrono::Utc;
-use bcrypt::{hash, verify, DEFAULT_COST};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use base64::{engine::general_purpose, Engine as _};
+use bcrypt::verify;
+use hmac::{Hmac, Mac};
 use rand::{thread_rng, Rng};
 use rand::distributions::Alphanumeric;
+use sha2::Sha256;
 use crate::models::{OAuthClient, CreateClientRequest};
+use crate::redirect_uri::{RedirectUriPolicy, RedirectUriError};
+use crate::hashing_pool::HashingPool;
+use crate::config::ClientAuthCacheConfig;
+use crate::secrets::constant_time_eq;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Префикс хеша секрета клиента в формате HMAC-SHA256; хеши без него — bcrypt
+const HMAC_SECRET_PREFIX: &str = "hmac-sha256$";
+
+// Секрет клиента для хранения в БД: HMAC-SHA256 с ключом сервера.
+// Секреты — случайные строки высокой энтропии, медленный хеш для них не нужен
+pub fn hash_client_secret(key: &[u8], secret: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(secret.as_bytes());
+    format!("{}{}", HMAC_SECRET_PREFIX, general_purpose::URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+}
+
+// Ключи HMAC секретов клиентов: текущий для новых хешей и прежние, которые еще
+// принимаются при проверке. Хеш по прежнему ключу заменяется при успешной аутентификации
+#[derive(Clone)]
+pub struct ClientSecretKeys {
+    current: Vec<u8>,
+    previous: Vec<Vec<u8>>,
+}
+
+impl ClientSecretKeys {
+    pub fn new(current: &str) -> Self {
+        Self { current: current.as_bytes().to_vec(), previous: Vec::new() }
+    }
+
+    pub fn with_previous<'a>(mut self, previous: impl IntoIterator<Item = &'a str>) -> Self {
+        self.previous.extend(previous.into_iter().map(|key| key.as_bytes().to_vec()));
+        self
+    }
+
+    // Хеш секрета для хранения: всегда текущим ключом
+    pub fn hash(&self, secret: &str) -> String {
+        hash_client_secret(&self.current, secret)
+    }
+
+    // Совпадает ли сохраненный хеш с секретом по одному из прежних ключей
+    pub fn matches_previous(&self, stored_hash: &str, secret: &str) -> bool {
+        self.previous
+            .iter()
+            .any(|key| constant_time_eq(stored_hash.as_bytes(), hash_client_secret(key, secret).as_bytes()))
+    }
+}
+
+// Хеш bcrypt, оставшийся от прежнего формата; заменяется при первой успешной аутентификации
+pub fn is_legacy_secret_hash(hash: &str) -> bool {
+    !hash.starts_with(HMAC_SECRET_PREFIX)
+}
 
 #[derive(Debug)]
 pub enum ClientError {
@@ -12,6 +71,7 @@ pub enum ClientError {
     ClientNotFound,
     InvalidCredentials,
     InvalidRedirectUri,
+    RedirectUriRejected(String, RedirectUriError),
     InvalidScope,
     InvalidGrantType,
     HashError,
@@ -24,6 +84,7 @@ impl std::fmt::Display for ClientError {
             ClientError::ClientNotFound => write!(f, "Client not found"),
             ClientError::InvalidCredentials => write!(f, "Invalid client credentials"),
             ClientError::InvalidRedirectUri => write!(f, "Invalid redirect URI"),
+            ClientError::RedirectUriRejected(uri, reason) => write!(f, "Invalid redirect URI {}: {}", uri, reason),
             ClientError::InvalidScope => write!(f, "Invalid scope"),
             ClientError::InvalidGrantType => write!(f, "Invalid grant type"),
             ClientError::HashError => write!(f, "Error hashing client secret"),
@@ -33,13 +94,94 @@ impl std::fmt::Display for ClientError {
 
 impl std::error::Error for ClientError {}
 
+// Колонки oauth_clients, возвращаемые во всех запросах
+const CLIENT_COLUMNS: &str = "id, client_id, client_secret_hash, client_name, redirect_uris, \
+    allowed_scopes, grant_types, is_confidential, created_at, updated_at, access_token_format, \
+    require_pkce, allow_plain_pkce, access_token_ttl, refresh_token_ttl, refresh_token_absolute_ttl, \
+    authorization_code_ttl, is_first_party";
+
+// Успешная проверка секрета клиента, запомненная на короткое время. Данные клиента
+// не кешируются: они всегда читаются из БД, запоминается только результат проверки
+struct VerifiedSecret {
+    stored_hash: String,
+    secret_hash: String,
+    expires_at: Instant,
+}
+
+// Кеш проверки секретов конфиденциальных клиентов: повторные запросы к /oauth/token
+// не проверяют секрет заново (в том числе bcrypt прежнего формата). Запись действует,
+// пока хеш в БД не изменился; изменение и удаление клиента сбрасывают ее на этом экземпляре
+pub struct ClientAuthCache {
+    config: ClientAuthCacheConfig,
+    entries: Mutex<HashMap<String, VerifiedSecret>>,
+}
+
+impl ClientAuthCache {
+    pub fn new(config: ClientAuthCacheConfig) -> Self {
+        Self { config, entries: Mutex::new(HashMap::new()) }
+    }
+
+    // Проверялся ли недавно этот секрет для клиента в его текущем состоянии из БД
+    pub fn is_verified(&self, client: &OAuthClient, secret_hash: &str) -> bool {
+        let Ok(entries) = self.entries.lock() else { return false };
+        entries
+            .get(&client.client_id)
+            .filter(|entry| entry.expires_at > Instant::now())
+            .filter(|entry| entry.stored_hash == client.client_secret_hash)
+            .is_some_and(|entry| constant_time_eq(entry.secret_hash.as_bytes(), secret_hash.as_bytes()))
+    }
+
+    pub fn insert(&self, client: &OAuthClient, secret_hash: String) {
+        if self.config.ttl == 0 {
+            return;
+        }
+        let Ok(mut entries) = self.entries.lock() else { return };
+
+        let now = Instant::now();
+        if entries.len() >= self.config.max_entries {
+            entries.retain(|_, entry| entry.expires_at > now);
+        }
+        if entries.len() >= self.config.max_entries && !entries.contains_key(&client.client_id) {
+            return;
+        }
+
+        entries.insert(client.client_id.clone(), VerifiedSecret {
+            stored_hash: client.client_secret_hash.clone(),
+            secret_hash,
+            expires_at: now + Duration::from_secs(self.config.ttl),
+        });
+    }
+
+    pub fn invalidate(&self, client_id: &str) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.remove(client_id);
+        }
+    }
+}
+
 pub struct ClientService {
     pool: Pool<Postgres>,
+    redirect_uri_policy: RedirectUriPolicy,
+    hashing_pool: Arc<HashingPool>,
+    secret_keys: ClientSecretKeys,
+    auth_cache: ClientAuthCache,
 }
 
 impl ClientService {
-    pub fn new(pool: Pool<Postgres>) -> Self {
-        Self { pool }
+    pub fn new(
+        pool: Pool<Postgres>,
+        redirect_uri_policy: RedirectUriPolicy,
+        hashing_pool: Arc<HashingPool>,
+        secret_keys: ClientSecretKeys,
+        auth_cache: ClientAuthCacheConfig,
+    ) -> Self {
+        Self {
+            pool,
+            redirect_uri_policy,
+            hashing_pool,
+            secret_keys,
+            auth_cache: ClientAuthCache::new(auth_cache),
+        }
     }
 
     // Генерация client_id
@@ -63,25 +205,28 @@ impl ClientService {
 
     // Регистрация нового OAuth клиента
     pub async fn register_client(&self, request: CreateClientRequest) -> Result<(OAuthClient, String), ClientError> {
+        self.validate_registration_redirect_uris(&request)?;
+
         let client_id = Self::generate_client_id();
         let client_secret = Self::generate_client_secret();
-        let client_secret_hash = hash(&client_secret, DEFAULT_COST)
-            .map_err(|_| ClientError::HashError)?;
+        let client_secret_hash = self.secret_keys.hash(&client_secret);
 
         let id = Uuid::new_v4();
         let now = Utc::now();
 
-        let client = sqlx::query_as::<_, OAuthClient>(
+        let client = sqlx::query_as::<_, OAuthClient>(&format!(
             r#"
             INSERT INTO oauth_clients (
                 id, client_id, client_secret_hash, client_name, redirect_uris,
-                allowed_scopes, grant_types, is_confidential, created_at, updated_at
+                allowed_scopes, grant_types, is_confidential, created_at, updated_at,
+                access_token_format, require_pkce, allow_plain_pkce,
+                access_token_ttl, refresh_token_ttl, refresh_token_absolute_ttl, authorization_code_ttl
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-            RETURNING id, client_id, client_secret_hash, client_name, redirect_uris,
-                      allowed_scopes, grant_types, is_confidential, created_at, updated_at
-            "#
-        )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+            RETURNING {}
+            "#,
+            CLIENT_COLUMNS
+        ))
         .bind(id)
         .bind(&client_id)
         .bind(&client_secret_hash)
@@ -92,6 +237,13 @@ impl ClientService {
         .bind(request.is_confidential)
         .bind(now)
         .bind(now)
+        .bind(request.access_token_format.as_str())
+        .bind(request.require_pkce)
+        .bind(request.allow_plain_pkce)
+        .bind(request.access_token_ttl)
+        .bind(request.refresh_token_ttl)
+        .bind(request.refresh_token_absolute_ttl)
+        .bind(request.authorization_code_ttl)
         .fetch_one(&self.pool)
         .await
         .map_err(ClientError::DatabaseError)?;
@@ -101,14 +253,14 @@ impl ClientService {
 
     // Получение клиента по client_id
     pub async fn get_client_by_id(&self, client_id: &str) -> Result<Option<OAuthClient>, ClientError> {
-        let client = sqlx::query_as::<_, OAuthClient>(
+        let client = sqlx::query_as::<_, OAuthClient>(&format!(
             r#"
-            SELECT id, client_id, client_secret_hash, client_name, redirect_uris,
-                   allowed_scopes, grant_types, is_confidential, created_at, updated_at
+            SELECT {}
             FROM oauth_clients
             WHERE client_id = $1
-            "#
-        )
+            "#,
+            CLIENT_COLUMNS
+        ))
         .bind(client_id)
         .fetch_optional(&self.pool)
         .await
@@ -119,7 +271,7 @@ impl ClientService {
 
     // Валидация client credentials
     pub async fn validate_client_credentials(&self, client_id: &str, client_secret: &str) -> Result<OAuthClient, ClientError> {
-        let client = self.get_client_by_id(client_id)
+        let mut client = self.get_client_by_id(client_id)
             .await?
             .ok_or(ClientError::ClientNotFound)?;
 
@@ -127,24 +279,82 @@ impl ClientService {
             return Ok(client);
         }
 
-        let is_valid = verify(client_secret, &client.client_secret_hash)
-            .map_err(|_| ClientError::InvalidCredentials)?;
+        let secret_hash = self.secret_keys.hash(client_secret);
+        if self.auth_cache.is_verified(&client, &secret_hash) {
+            return Ok(client);
+        }
+
+        let is_valid = if is_legacy_secret_hash(&client.client_secret_hash) {
+            let (secret, legacy_hash) = (client_secret.to_string(), client.client_secret_hash.clone());
+            let is_valid = self.hashing_pool
+                .run(move || verify(&secret, &legacy_hash))
+                .await
+                .map_err(|_| ClientError::HashError)?
+                .map_err(|_| ClientError::InvalidCredentials)?;
+
+            if is_valid {
+                self.rehash_secret(&mut client, &secret_hash).await;
+            }
+            is_valid
+        } else if constant_time_eq(client.client_secret_hash.as_bytes(), secret_hash.as_bytes()) {
+            true
+        } else if self.secret_keys.matches_previous(&client.client_secret_hash, client_secret) {
+            self.rehash_secret(&mut client, &secret_hash).await;
+            true
+        } else {
+            false
+        };
 
         if !is_valid {
             return Err(ClientError::InvalidCredentials);
         }
 
+        self.auth_cache.insert(&client, secret_hash);
         Ok(client)
     }
 
-    // Валидация redirect_uri
+    // Перевод хеша секрета (bcrypt или HMAC прежним ключом) на HMAC текущим ключом
+    async fn rehash_secret(&self, client: &mut OAuthClient, secret_hash: &str) {
+        match self.upgrade_secret_hash(client, secret_hash).await {
+            Ok(()) => client.client_secret_hash = secret_hash.to_string(),
+            // Аутентификация не зависит от миграции, попытка повторится при следующем запросе
+            Err(e) => eprintln!("Client secret migration error: {}", e),
+        }
+    }
+
+    // Замена хеша секрета на HMAC текущим ключом; условие на старый хеш не дает затереть параллельную замену
+    async fn upgrade_secret_hash(&self, client: &OAuthClient, secret_hash: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE oauth_clients SET client_secret_hash = $1 WHERE id = $2 AND client_secret_hash = $3")
+            .bind(secret_hash)
+            .bind(client.id)
+            .bind(&client.client_secret_hash)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Валидация redirect_uri из запроса авторизации: точное совпадение,
+    // loopback с любым портом и wildcard (если разрешены политикой)
     pub fn validate_redirect_uri(&self, client: &OAuthClient, redirect_uri: &str) -> Result<(), ClientError> {
-        if !client.redirect_uris.contains(&redirect_uri.to_string()) {
+        let matched = client.redirect_uris.iter()
+            .any(|registered| self.redirect_uri_policy.matches(registered, redirect_uri));
+        if !matched {
             return Err(ClientError::InvalidRedirectUri);
         }
         Ok(())
     }
 
+    // Проверка redirect URI при регистрации клиента
+    pub fn validate_registration_redirect_uris(&self, request: &CreateClientRequest) -> Result<(), ClientError> {
+        for uri in &request.redirect_uris {
+            self.redirect_uri_policy
+                .validate_registration(uri, request.is_confidential)
+                .map_err(|reason| ClientError::RedirectUriRejected(uri.clone(), reason))?;
+        }
+        Ok(())
+    }
+
     // Валидация scope
     pub fn validate_scope(&self, client: &OAuthClient, scope: &str) -> Result<(), ClientError> {
         let requested_scopes: Vec<&str> = scope.split_whitespace().collect();
@@ -166,6 +376,27 @@ impl ClientService {
         Ok(())
     }
 
+    // Изменение признака first-party клиента
+    pub async fn set_first_party(&self, client_id: &str, is_first_party: bool) -> Result<OAuthClient, ClientError> {
+        let client = sqlx::query_as::<_, OAuthClient>(&format!(
+            r#"
+            UPDATE oauth_clients
+            SET is_first_party = $2, updated_at = NOW()
+            WHERE client_id = $1
+            RETURNING {}
+            "#,
+            CLIENT_COLUMNS
+        ))
+        .bind(client_id)
+        .bind(is_first_party)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(ClientError::DatabaseError)?;
+        self.auth_cache.invalidate(client_id);
+
+        client.ok_or(ClientError::ClientNotFound)
+    }
+
     // Удаление клиента
     pub async fn delete_client(&self, client_id: &str) -> Result<bool, ClientError> {
         let result = sqlx::query("DELETE FROM oauth_clients WHERE client_id = $1")
@@ -173,6 +404,7 @@ impl ClientService {
             .execute(&self.pool)
             .await
             .map_err(ClientError::DatabaseError)?;
+        self.auth_cache.invalidate(client_id);
 
         Ok(result.rows_affected() > 0)
     }
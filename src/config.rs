@@ -0,0 +1,558 @@
+use std::env;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use chrono::{DateTime, Duration, Utc};
+use crate::models::OAuthClient;
+use crate::pkce::{PkcePolicy, PkceRequirement};
+use crate::redirect_uri::RedirectUriPolicy;
+use crate::mail::{MailConfig, MailTransport, SmtpSettings};
+
+// Конфигурация сервера авторизации, общая для всех сервисов и handlers
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    // Идентификатор сервера авторизации (issuer), без завершающего '/'
+    pub issuer: String,
+    // Значение claim `aud` в access token (идентификатор защищаемого API)
+    pub access_token_audience: String,
+    // Политика PKCE по умолчанию для всех клиентов
+    pub pkce_policy: PkcePolicy,
+    // Время жизни токенов и кодов по умолчанию (клиент может переопределить)
+    pub token_lifetimes: TokenLifetimes,
+    // Правила проверки redirect URI клиентов
+    pub redirect_uri_policy: RedirectUriPolicy,
+    // Подтверждение email пользователей
+    pub email_verification: EmailVerificationPolicy,
+    // Срок действия ссылки сброса пароля (в секундах)
+    pub password_reset_ttl: i64,
+    // Смена email
+    pub email_change: EmailChangePolicy,
+    // Удаление аккаунта по запросу пользователя
+    pub account_deletion: AccountDeletionPolicy,
+    // Название сервиса в приложении-аутентификаторе (otpauth:// issuer)
+    pub totp_issuer: String,
+    // Relying party для WebAuthn (ключи доступа)
+    pub webauthn: WebAuthnConfig,
+    // Защита входа по паролю от перебора
+    pub login_throttle: LoginThrottlePolicy,
+    // Адреса обратных прокси, которым разрешено передавать IP клиента в X-Forwarded-For
+    pub trusted_proxies: Vec<IpAddr>,
+    // Требования к новым паролям
+    pub password_policy: PasswordPolicyConfig,
+    // Параметры хеширования паролей
+    pub password_hashing: PasswordHashingConfig,
+    // Пул для хеширования паролей и секретов клиентов
+    pub hashing_pool: HashingPoolConfig,
+    // Кеш аутентификации OAuth клиентов
+    pub client_auth_cache: ClientAuthCacheConfig,
+    // Отправка писем
+    pub mail: MailConfig,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        let issuer = "http://127.0.0.1:8080".to_string();
+        Self {
+            access_token_audience: issuer.clone(),
+            webauthn: WebAuthnConfig::for_issuer(&issuer, "AuthService"),
+            issuer,
+            pkce_policy: PkcePolicy::default(),
+            token_lifetimes: TokenLifetimes::default(),
+            redirect_uri_policy: RedirectUriPolicy::default(),
+            email_verification: EmailVerificationPolicy::default(),
+            password_reset_ttl: 3600, // 1 hour
+            email_change: EmailChangePolicy::default(),
+            account_deletion: AccountDeletionPolicy::default(),
+            totp_issuer: "AuthService".to_string(),
+            login_throttle: LoginThrottlePolicy::default(),
+            trusted_proxies: Vec::new(),
+            password_policy: PasswordPolicyConfig::default(),
+            password_hashing: PasswordHashingConfig::default(),
+            hashing_pool: HashingPoolConfig::default(),
+            client_auth_cache: ClientAuthCacheConfig::default(),
+            mail: MailConfig::default(),
+        }
+    }
+}
+
+impl AppConfig {
+    // Загрузка конфигурации из переменных окружения
+    pub fn from_env(host: &str, port: &str) -> Self {
+        let issuer = env::var("ISSUER_URL").unwrap_or_else(|_| {
+            println!("ISSUER_URL не задан, используется адрес сервера");
+            format!("http://{}:{}", host, port)
+        });
+        let issuer = issuer.trim_end_matches('/').to_string();
+
+        let access_token_audience = env::var("ACCESS_TOKEN_AUDIENCE")
+            .unwrap_or_else(|_| issuer.clone());
+
+        let defaults = Self::default();
+
+        let pkce_policy = PkcePolicy {
+            requirement: env::var("PKCE_REQUIRED")
+                .ok()
+                .and_then(|value| PkceRequirement::parse(&value))
+                .unwrap_or(defaults.pkce_policy.requirement),
+            allow_plain: env_bool("PKCE_ALLOW_PLAIN", defaults.pkce_policy.allow_plain),
+        };
+
+        let token_lifetimes = TokenLifetimes {
+            access_token_ttl: env_positive("ACCESS_TOKEN_TTL")
+                .unwrap_or(defaults.token_lifetimes.access_token_ttl),
+            refresh_token_ttl: env_positive("REFRESH_TOKEN_TTL")
+                .unwrap_or(defaults.token_lifetimes.refresh_token_ttl),
+            refresh_token_absolute_ttl: env_positive("REFRESH_TOKEN_ABSOLUTE_TTL")
+                .or(defaults.token_lifetimes.refresh_token_absolute_ttl),
+            authorization_code_ttl: env_positive("AUTHORIZATION_CODE_TTL")
+                .unwrap_or(defaults.token_lifetimes.authorization_code_ttl),
+        };
+
+        let redirect_uri_policy = RedirectUriPolicy {
+            allow_wildcards: env_bool(
+                "ALLOW_REDIRECT_URI_WILDCARDS",
+                defaults.redirect_uri_policy.allow_wildcards,
+            ),
+        };
+
+        let email_verification = EmailVerificationPolicy {
+            required: env_bool("REQUIRE_VERIFIED_EMAIL", defaults.email_verification.required),
+            link_ttl: env_positive("EMAIL_VERIFICATION_TTL")
+                .unwrap_or(defaults.email_verification.link_ttl),
+            resend_interval: env_positive("EMAIL_VERIFICATION_RESEND_INTERVAL")
+                .unwrap_or(defaults.email_verification.resend_interval),
+        };
+
+        let password_reset_ttl = env_positive("PASSWORD_RESET_TTL").unwrap_or(defaults.password_reset_ttl);
+
+        let email_change = EmailChangePolicy {
+            link_ttl: env_positive("EMAIL_CHANGE_TTL").unwrap_or(defaults.email_change.link_ttl),
+            cancel_ttl: env_positive("EMAIL_CHANGE_CANCEL_TTL").unwrap_or(defaults.email_change.cancel_ttl),
+        };
+
+        let account_deletion = AccountDeletionPolicy {
+            grace_period: env_positive("ACCOUNT_DELETION_GRACE_PERIOD")
+                .unwrap_or(defaults.account_deletion.grace_period),
+            purge_interval: env_positive("ACCOUNT_DELETION_PURGE_INTERVAL")
+                .unwrap_or(defaults.account_deletion.purge_interval),
+        };
+
+        let totp_issuer = env::var("TOTP_ISSUER").unwrap_or(defaults.totp_issuer);
+
+        let webauthn = {
+            let derived = WebAuthnConfig::for_issuer(&issuer, &totp_issuer);
+            WebAuthnConfig {
+                rp_id: env::var("WEBAUTHN_RP_ID").unwrap_or(derived.rp_id),
+                rp_name: env::var("WEBAUTHN_RP_NAME").unwrap_or(derived.rp_name),
+                origin: env::var("WEBAUTHN_ORIGIN")
+                    .map(|origin| origin.trim_end_matches('/').to_string())
+                    .unwrap_or(derived.origin),
+            }
+        };
+
+        let login_throttle = LoginThrottlePolicy {
+            max_account_failures: env_positive("LOGIN_MAX_FAILURES")
+                .unwrap_or(defaults.login_throttle.max_account_failures),
+            max_ip_failures: env_positive("LOGIN_MAX_FAILURES_PER_IP")
+                .unwrap_or(defaults.login_throttle.max_ip_failures),
+            failure_window: env_positive("LOGIN_FAILURE_WINDOW")
+                .unwrap_or(defaults.login_throttle.failure_window),
+            lockout_duration: env_positive("LOGIN_LOCKOUT_DURATION")
+                .unwrap_or(defaults.login_throttle.lockout_duration),
+            base_delay_ms: env_i64("LOGIN_DELAY_BASE_MS")
+                .and_then(|ms| u64::try_from(ms).ok())
+                .unwrap_or(defaults.login_throttle.base_delay_ms),
+            max_delay_ms: env_i64("LOGIN_DELAY_MAX_MS")
+                .and_then(|ms| u64::try_from(ms).ok())
+                .unwrap_or(defaults.login_throttle.max_delay_ms),
+        };
+
+        let trusted_proxies = env::var("TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|proxy| !proxy.is_empty())
+            .map(|proxy| {
+                proxy
+                    .parse::<IpAddr>()
+                    .unwrap_or_else(|_| panic!("Недопустимый адрес в TRUSTED_PROXIES: {}", proxy))
+            })
+            .collect();
+
+        let password_policy = PasswordPolicyConfig {
+            min_length: env_i64("PASSWORD_MIN_LENGTH")
+                .and_then(|len| usize::try_from(len).ok())
+                .unwrap_or(defaults.password_policy.min_length),
+            max_bytes: env_i64("PASSWORD_MAX_BYTES")
+                .and_then(|len| usize::try_from(len).ok())
+                .unwrap_or(defaults.password_policy.max_bytes),
+            reject_similar_to_identity: env_bool(
+                "PASSWORD_REJECT_SIMILAR_TO_IDENTITY",
+                defaults.password_policy.reject_similar_to_identity,
+            ),
+            breached_corpus_dir: env::var("PASSWORD_BREACHED_CORPUS_DIR")
+                .ok()
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+        };
+
+        let password_hashing = PasswordHashingConfig {
+            memory_kib: env_i64("ARGON2_MEMORY_KIB")
+                .and_then(|kib| u32::try_from(kib).ok())
+                .unwrap_or(defaults.password_hashing.memory_kib),
+            iterations: env_i64("ARGON2_ITERATIONS")
+                .and_then(|n| u32::try_from(n).ok())
+                .unwrap_or(defaults.password_hashing.iterations),
+            parallelism: env_i64("ARGON2_PARALLELISM")
+                .and_then(|n| u32::try_from(n).ok())
+                .unwrap_or(defaults.password_hashing.parallelism),
+        };
+
+        let hashing_pool = HashingPoolConfig {
+            max_concurrency: env_i64("HASHING_MAX_CONCURRENCY")
+                .and_then(|n| usize::try_from(n).ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(defaults.hashing_pool.max_concurrency),
+        };
+
+        let client_auth_cache = ClientAuthCacheConfig {
+            ttl: env_i64("CLIENT_AUTH_CACHE_TTL")
+                .and_then(|ttl| u64::try_from(ttl).ok())
+                .unwrap_or(defaults.client_auth_cache.ttl),
+            max_entries: env_i64("CLIENT_AUTH_CACHE_MAX_ENTRIES")
+                .and_then(|n| usize::try_from(n).ok())
+                .unwrap_or(defaults.client_auth_cache.max_entries),
+        };
+
+        let mail = mail_config_from_env(defaults.mail);
+
+        Self {
+            issuer,
+            access_token_audience,
+            pkce_policy,
+            token_lifetimes,
+            redirect_uri_policy,
+            email_verification,
+            password_reset_ttl,
+            email_change,
+            account_deletion,
+            totp_issuer,
+            webauthn,
+            login_throttle,
+            trusted_proxies,
+            password_policy,
+            password_hashing,
+            hashing_pool,
+            client_auth_cache,
+            mail,
+        }
+    }
+
+    // Полный URL эндпоинта относительно issuer
+    pub fn endpoint(&self, path: &str) -> String {
+        format!("{}{}", self.issuer, path)
+    }
+}
+
+// Время жизни токенов и authorization code (в секундах)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenLifetimes {
+    pub access_token_ttl: i64,
+    // Скользящий срок жизни refresh token: продлевается при каждой ротации
+    pub refresh_token_ttl: i64,
+    // Абсолютный срок жизни цепочки refresh token от первой выдачи (None — без ограничения)
+    pub refresh_token_absolute_ttl: Option<i64>,
+    pub authorization_code_ttl: i64,
+}
+
+impl Default for TokenLifetimes {
+    fn default() -> Self {
+        Self {
+            access_token_ttl: 3600,          // 1 hour
+            refresh_token_ttl: 2592000,      // 30 days
+            refresh_token_absolute_ttl: None,
+            authorization_code_ttl: 600,     // 10 minutes
+        }
+    }
+}
+
+impl TokenLifetimes {
+    // Значения для клиента: заданные в oauth_clients переопределяют глобальные
+    pub fn for_client(&self, client: &OAuthClient) -> Self {
+        Self {
+            access_token_ttl: client.access_token_ttl.unwrap_or(self.access_token_ttl),
+            refresh_token_ttl: client.refresh_token_ttl.unwrap_or(self.refresh_token_ttl),
+            refresh_token_absolute_ttl: client.refresh_token_absolute_ttl.or(self.refresh_token_absolute_ttl),
+            authorization_code_ttl: client.authorization_code_ttl.unwrap_or(self.authorization_code_ttl),
+        }
+    }
+
+    // Сроки действия нового refresh token: (истечение, абсолютный предел цепочки).
+    // При ротации передается предел исходного гранта, скользящий срок его не превышает
+    pub fn refresh_expiry(
+        &self,
+        now: DateTime<Utc>,
+        absolute_expires_at: Option<DateTime<Utc>>,
+    ) -> (DateTime<Utc>, Option<DateTime<Utc>>) {
+        let absolute_expires_at = absolute_expires_at
+            .or_else(|| self.refresh_token_absolute_ttl.map(|ttl| now + Duration::seconds(ttl)));
+        let sliding = now + Duration::seconds(self.refresh_token_ttl);
+
+        let expires_at = match absolute_expires_at {
+            Some(limit) => sliding.min(limit),
+            None => sliding,
+        };
+
+        (expires_at, absolute_expires_at)
+    }
+}
+
+// Подтверждение email (сроки в секундах)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmailVerificationPolicy {
+    // Запрещать вход и выдачу токенов до подтверждения email
+    pub required: bool,
+    // Срок действия ссылки подтверждения
+    pub link_ttl: i64,
+    // Минимальный интервал между повторными отправками письма
+    pub resend_interval: i64,
+}
+
+impl Default for EmailVerificationPolicy {
+    fn default() -> Self {
+        Self {
+            required: false,
+            link_ttl: 86400,       // 24 hours
+            resend_interval: 60,   // 1 minute
+        }
+    }
+}
+
+// Смена email (сроки в секундах)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmailChangePolicy {
+    // Срок действия ссылки подтверждения, отправленной на новый адрес
+    pub link_ttl: i64,
+    // Срок, в течение которого ссылка из уведомления на старый адрес отменяет смену
+    pub cancel_ttl: i64,
+}
+
+impl Default for EmailChangePolicy {
+    fn default() -> Self {
+        Self {
+            link_ttl: 86400,      // 24 hours
+            cancel_ttl: 604800,   // 7 days
+        }
+    }
+}
+
+// Удаление аккаунта (сроки в секундах)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountDeletionPolicy {
+    // Срок, в течение которого удаление можно отменить
+    pub grace_period: i64,
+    // Периодичность окончательного удаления аккаунтов с истекшим сроком
+    pub purge_interval: i64,
+}
+
+impl Default for AccountDeletionPolicy {
+    fn default() -> Self {
+        Self {
+            grace_period: 2592000, // 30 days
+            purge_interval: 3600,  // 1 hour
+        }
+    }
+}
+
+// Защита входа от перебора паролей (сроки в секундах). Неудачи считаются отдельно
+// для аккаунта (email) и для IP-адреса
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoginThrottlePolicy {
+    // Неудачных попыток для аккаунта до временной блокировки
+    pub max_account_failures: i64,
+    // Неудачных попыток с одного IP-адреса до временной блокировки
+    pub max_ip_failures: i64,
+    // Неудачи старше окна не учитываются
+    pub failure_window: i64,
+    // Длительность блокировки, после нее вход разблокируется автоматически
+    pub lockout_duration: i64,
+    // Задержка ответа после неудачи: base * 2^(n-1), не больше max (в миллисекундах)
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for LoginThrottlePolicy {
+    fn default() -> Self {
+        Self {
+            max_account_failures: 5,
+            max_ip_failures: 50,
+            failure_window: 900,      // 15 minutes
+            lockout_duration: 900,    // 15 minutes
+            base_delay_ms: 250,
+            max_delay_ms: 8000,
+        }
+    }
+}
+
+impl LoginThrottlePolicy {
+    // Задержка ответа после failures неудачных попыток подряд
+    pub fn failure_delay(&self, failures: i64) -> std::time::Duration {
+        if failures <= 0 {
+            return std::time::Duration::ZERO;
+        }
+        let factor = 1u64 << (failures - 1).min(32);
+        std::time::Duration::from_millis(self.base_delay_ms.saturating_mul(factor).min(self.max_delay_ms))
+    }
+}
+
+// Требования к паролям при регистрации, сбросе и смене пароля
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordPolicyConfig {
+    // Минимальная длина в символах
+    pub min_length: usize,
+    // Максимальная длина в байтах UTF-8 (ограничивает работу хеширования)
+    pub max_bytes: usize,
+    // Запрещать пароли, похожие на имя пользователя или email
+    pub reject_similar_to_identity: bool,
+    // Каталог с утекшими паролями в формате hash-prefix (файлы XXXXX.txt со строками SUFFIX:COUNT)
+    pub breached_corpus_dir: Option<PathBuf>,
+}
+
+impl Default for PasswordPolicyConfig {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_bytes: 256,
+            reject_similar_to_identity: true,
+            breached_corpus_dir: None,
+        }
+    }
+}
+
+// Параметры Argon2id для новых хешей паролей (по умолчанию — рекомендация OWASP)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordHashingConfig {
+    // Объем памяти в КиБ
+    pub memory_kib: u32,
+    // Число проходов
+    pub iterations: u32,
+    // Число потоков
+    pub parallelism: u32,
+}
+
+impl Default for PasswordHashingConfig {
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+// Ограничение одновременных задач хеширования (по умолчанию — число ядер CPU)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashingPoolConfig {
+    pub max_concurrency: usize,
+}
+
+impl Default for HashingPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
+        }
+    }
+}
+
+// Кеш успешной аутентификации клиентов в памяти процесса (ttl в секундах, 0 — отключен)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientAuthCacheConfig {
+    pub ttl: u64,
+    pub max_entries: usize,
+}
+
+impl Default for ClientAuthCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: 30,
+            max_entries: 10_000,
+        }
+    }
+}
+
+// Relying party для WebAuthn: домен, к которому привязаны ключи доступа, и origin страниц входа
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebAuthnConfig {
+    pub rp_id: String,
+    pub rp_name: String,
+    pub origin: String,
+}
+
+impl WebAuthnConfig {
+    // Значения по умолчанию из issuer: домен и origin сервера авторизации
+    pub fn for_issuer(issuer: &str, rp_name: &str) -> Self {
+        let parsed = url::Url::parse(issuer).ok();
+        Self {
+            rp_id: parsed
+                .as_ref()
+                .and_then(|url| url.host_str().map(str::to_string))
+                .unwrap_or_else(|| "localhost".to_string()),
+            rp_name: rp_name.to_string(),
+            origin: parsed
+                .map(|url| url.origin().ascii_serialization())
+                .unwrap_or_else(|| issuer.to_string()),
+        }
+    }
+}
+
+// Настройки почты: MAIL_TRANSPORT = smtp | file | memory
+fn mail_config_from_env(defaults: MailConfig) -> MailConfig {
+    let from = env::var("MAIL_FROM").unwrap_or(defaults.from);
+
+    let transport = env::var("MAIL_TRANSPORT")
+        .ok()
+        .map(|v| v.trim().to_ascii_lowercase())
+        .filter(|v| !v.is_empty());
+    let transport = match transport.as_deref() {
+        Some("smtp") => MailTransport::Smtp(SmtpSettings {
+            host: env::var("SMTP_HOST").unwrap_or_else(|_| "localhost".to_string()),
+            port: env_i64("SMTP_PORT")
+                .and_then(|port| u16::try_from(port).ok())
+                .unwrap_or(587),
+            username: env::var("SMTP_USERNAME").ok().filter(|v| !v.is_empty()),
+            password: env::var("SMTP_PASSWORD").ok().filter(|v| !v.is_empty()),
+            starttls: env_bool("SMTP_STARTTLS", true),
+        }),
+        Some("memory") => MailTransport::Memory,
+        Some("file") => MailTransport::File(
+            env::var("MAIL_OUTBOX_DIR").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("mail_outbox")),
+        ),
+        // Опечатка в MAIL_TRANSPORT не должна незаметно отключать доставку писем
+        Some(other) => panic!("Неизвестный MAIL_TRANSPORT: {} (допустимые значения: smtp, file, memory)", other),
+        None => defaults.transport,
+    };
+
+    MailConfig { from, transport }
+}
+
+// Чтение числовой переменной окружения
+fn env_i64(name: &str) -> Option<i64> {
+    env::var(name).ok().and_then(|value| value.trim().parse().ok())
+}
+
+// Чтение сроков, интервалов и порогов: нулевые и отрицательные значения отклоняются
+fn env_positive(name: &str) -> Option<i64> {
+    let value = env_i64(name)?;
+    if value <= 0 {
+        eprintln!("{} должен быть положительным, используется значение по умолчанию", name);
+        return None;
+    }
+    Some(value)
+}
+
+// Чтение булевой переменной окружения ("true"/"1"/"yes")
+fn env_bool(name: &str, default: bool) -> bool {
+    match env::var(name) {
+        Ok(value) => matches!(value.to_ascii_lowercase().as_str(), "true" | "1" | "yes"),
+        Err(_) => default,
+    }
+}
@@ -0,0 +1,118 @@
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+use chrono::Utc;
+use crate::models::{Consent, ConnectedApp};
+
+pub struct ConsentService {
+    pool: Pool<Postgres>,
+}
+
+impl ConsentService {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    // Получение согласия пользователя для клиента
+    pub async fn get_consent(&self, user_id: Uuid, client_id: &str) -> Result<Option<Consent>, sqlx::Error> {
+        let consent = sqlx::query_as::<_, Consent>(
+            r#"
+            SELECT id, user_id, client_id, scopes, declined_scopes, created_at, updated_at
+            FROM oauth_consents
+            WHERE user_id = $1 AND client_id = $2
+            "#
+        )
+        .bind(user_id)
+        .bind(client_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(consent)
+    }
+
+    // Сохранение согласия: новые scopes добавляются к ранее выданным,
+    // отказ запоминается, пока пользователь не одобрит scope
+    pub async fn grant_consent(
+        &self,
+        user_id: Uuid,
+        client_id: &str,
+        scopes: &[&str],
+        declined_scopes: &[&str],
+    ) -> Result<Consent, sqlx::Error> {
+        let now = Utc::now();
+        let scopes: Vec<String> = scopes.iter().map(|s| s.to_string()).collect();
+        let declined_scopes: Vec<String> = declined_scopes.iter().map(|s| s.to_string()).collect();
+
+        let consent = sqlx::query_as::<_, Consent>(
+            r#"
+            INSERT INTO oauth_consents (id, user_id, client_id, scopes, declined_scopes, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $6)
+            ON CONFLICT (user_id, client_id) DO UPDATE
+            SET scopes = ARRAY(
+                    SELECT DISTINCT unnest(oauth_consents.scopes || EXCLUDED.scopes)
+                ),
+                declined_scopes = ARRAY(
+                    SELECT DISTINCT s
+                    FROM unnest(oauth_consents.declined_scopes || EXCLUDED.declined_scopes) AS s
+                    WHERE s <> ALL(EXCLUDED.scopes)
+                ),
+                updated_at = EXCLUDED.updated_at
+            RETURNING id, user_id, client_id, scopes, declined_scopes, created_at, updated_at
+            "#
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(client_id)
+        .bind(&scopes)
+        .bind(&declined_scopes)
+        .bind(now)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(consent)
+    }
+
+    // Приложения пользователя: выданные согласия и клиенты с действующими токенами
+    pub async fn list_connected_apps(&self, user_id: Uuid) -> Result<Vec<ConnectedApp>, sqlx::Error> {
+        let apps = sqlx::query_as::<_, ConnectedApp>(
+            r#"
+            SELECT c.client_id,
+                   c.client_name,
+                   COALESCE(cs.scopes, ARRAY[]::TEXT[]) AS scopes,
+                   cs.created_at AS authorized_at,
+                   t.last_used_at,
+                   COALESCE(t.active_tokens, 0) AS active_tokens
+            FROM oauth_clients c
+            LEFT JOIN oauth_consents cs ON cs.client_id = c.client_id AND cs.user_id = $1
+            LEFT JOIN (
+                SELECT client_id,
+                       MAX(COALESCE(last_used_at, created_at)) AS last_used_at,
+                       COUNT(*) FILTER (
+                           WHERE revoked = false
+                             AND (expires_at > NOW() OR refresh_expires_at > NOW())
+                       ) AS active_tokens
+                FROM oauth_tokens
+                WHERE user_id = $1
+                GROUP BY client_id
+            ) t ON t.client_id = c.client_id
+            WHERE cs.id IS NOT NULL OR t.active_tokens > 0
+            ORDER BY t.last_used_at DESC NULLS LAST, c.client_name
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(apps)
+    }
+
+    // Удаление согласия: при следующей авторизации consent screen покажется снова
+    pub async fn revoke_consent(&self, user_id: Uuid, client_id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM oauth_consents WHERE user_id = $1 AND client_id = $2")
+            .bind(user_id)
+            .bind(client_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
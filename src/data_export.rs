@@ -0,0 +1,127 @@
+// Выгрузка персональных данных пользователя в JSON
+use chrono::Utc;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+use crate::consent_service::ConsentService;
+use crate::models::{AccountExport, AuditEvent, Consent, SessionRecord, UserProfile};
+use crate::services::PROFILE_COLUMNS;
+
+pub struct DataExportService {
+    pool: Pool<Postgres>,
+    consent_service: ConsentService,
+}
+
+impl DataExportService {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self {
+            consent_service: ConsentService::new(pool.clone()),
+            pool,
+        }
+    }
+
+    pub async fn export(&self, user_id: Uuid) -> Result<Option<AccountExport>, sqlx::Error> {
+        let profile = sqlx::query_as::<_, UserProfile>(&format!(
+            "SELECT {} FROM users WHERE id = $1",
+            PROFILE_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(profile) = profile else {
+            return Ok(None);
+        };
+
+        let consents = sqlx::query_as::<_, Consent>(
+            r#"
+            SELECT id, user_id, client_id, scopes, declined_scopes, created_at, updated_at
+            FROM oauth_consents
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let sessions = sqlx::query_as::<_, SessionRecord>(
+            r#"
+            SELECT id, user_agent, ip_address, created_at, last_seen_at, revoked_at
+            FROM user_sessions
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let connected_apps = self.consent_service.list_connected_apps(user_id).await?;
+        let audit_history = self.audit_history(user_id).await?;
+
+        Ok(Some(AccountExport {
+            exported_at: Utc::now(),
+            profile,
+            consents,
+            sessions,
+            connected_apps,
+            audit_history,
+        }))
+    }
+
+    // Отдельного журнала аудита нет: история собирается из записей о действиях с аккаунтом
+    async fn audit_history(&self, user_id: Uuid) -> Result<Vec<AuditEvent>, sqlx::Error> {
+        sqlx::query_as::<_, AuditEvent>(
+            r#"
+            SELECT event, occurred_at, details FROM (
+                SELECT 'account_created' AS event, created_at AS occurred_at, NULL::TEXT AS details
+                FROM users WHERE id = $1
+                UNION ALL
+                SELECT 'email_verified', email_verified_at, email
+                FROM users WHERE id = $1 AND email_verified_at IS NOT NULL
+                UNION ALL
+                SELECT 'account_deletion_requested', deletion_requested_at, NULL
+                FROM users WHERE id = $1 AND deletion_requested_at IS NOT NULL
+                UNION ALL
+                SELECT 'email_change_requested', created_at, new_email
+                FROM email_change_requests WHERE user_id = $1
+                UNION ALL
+                SELECT 'email_change_confirmed', confirmed_at, new_email
+                FROM email_change_requests WHERE user_id = $1 AND confirmed_at IS NOT NULL
+                UNION ALL
+                SELECT 'email_change_cancelled', cancelled_at, new_email
+                FROM email_change_requests WHERE user_id = $1 AND cancelled_at IS NOT NULL
+                UNION ALL
+                SELECT 'password_reset_requested', created_at, NULL
+                FROM password_reset_tokens WHERE user_id = $1
+                UNION ALL
+                SELECT 'password_reset_completed', used_at, NULL
+                FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NOT NULL
+                UNION ALL
+                SELECT 'totp_enabled', enabled_at, NULL
+                FROM user_totp WHERE user_id = $1 AND enabled_at IS NOT NULL
+                UNION ALL
+                SELECT 'recovery_code_used', used_at, NULL
+                FROM mfa_recovery_codes WHERE user_id = $1 AND used_at IS NOT NULL
+                UNION ALL
+                SELECT 'passkey_registered', created_at, name
+                FROM webauthn_credentials WHERE user_id = $1
+                UNION ALL
+                SELECT 'session_started', created_at, ip_address
+                FROM user_sessions WHERE user_id = $1
+                UNION ALL
+                SELECT 'session_ended', revoked_at, ip_address
+                FROM user_sessions WHERE user_id = $1 AND revoked_at IS NOT NULL
+                UNION ALL
+                SELECT 'login_failures', f.last_failure_at, f.failure_count::TEXT
+                FROM login_failures f JOIN users u ON f.scope = 'account' AND f.key = LOWER(TRIM(u.email))
+                WHERE u.id = $1
+            ) events
+            ORDER BY occurred_at DESC
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
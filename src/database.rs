@@ -58,6 +58,27 @@ pub async fn run_migrations(pool: &Pool<Postgres>) -> Result<(), sqlx::Error> {
         .execute(pool)
         .await?;
 
+    // Формат access token для клиента: 'jwt' или 'opaque'
+    sqlx::query(
+        r#"
+        ALTER TABLE oauth_clients
+            ADD COLUMN IF NOT EXISTS access_token_format VARCHAR(10) NOT NULL DEFAULT 'jwt'
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Переопределения политики PKCE для клиента
+    sqlx::query(
+        r#"
+        ALTER TABLE oauth_clients
+            ADD COLUMN IF NOT EXISTS require_pkce BOOLEAN,
+            ADD COLUMN IF NOT EXISTS allow_plain_pkce BOOLEAN
+        "#
+    )
+    .execute(pool)
+    .await?;
+
     // Создание таблицы oauth_authorization_codes
     sqlx::query(
         r#"
@@ -88,13 +109,13 @@ pub async fn run_migrations(pool: &Pool<Postgres>) -> Result<(), sqlx::Error> {
         .execute(pool)
         .await?;
 
-    // Создание таблицы oauth_tokens
+    // Создание таблицы oauth_tokens. Токены хранятся только в виде SHA-256 (base64url)
     sqlx::query(
         r#"
         CREATE TABLE IF NOT EXISTS oauth_tokens (
             id UUID PRIMARY KEY,
-            access_token VARCHAR(512) UNIQUE NOT NULL,
-            refresh_token VARCHAR(512) UNIQUE,
+            access_token_hash VARCHAR(64) NOT NULL,
+            refresh_token_hash VARCHAR(64),
             client_id VARCHAR(255) NOT NULL,
             user_id UUID,
             scope TEXT NOT NULL,
@@ -110,11 +131,41 @@ pub async fn run_migrations(pool: &Pool<Postgres>) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_oauth_tokens_access_token ON oauth_tokens(access_token)")
+    // Таблица прежнего формата с токенами в открытом виде: вычисление хешей
+    // (как secrets::hash_secret) и удаление исходных колонок
+    sqlx::query(
+        r#"
+        DO $$
+        BEGIN
+            IF EXISTS (
+                SELECT 1 FROM information_schema.columns
+                WHERE table_schema = current_schema() AND table_name = 'oauth_tokens' AND column_name = 'access_token'
+            ) THEN
+                ALTER TABLE oauth_tokens
+                    ADD COLUMN IF NOT EXISTS access_token_hash VARCHAR(64),
+                    ADD COLUMN IF NOT EXISTS refresh_token_hash VARCHAR(64);
+
+                UPDATE oauth_tokens SET
+                    access_token_hash = rtrim(translate(encode(sha256(convert_to(access_token, 'UTF8')), 'base64'), '+/', '-_'), '='),
+                    refresh_token_hash = rtrim(translate(encode(sha256(convert_to(refresh_token, 'UTF8')), 'base64'), '+/', '-_'), '=');
+
+                ALTER TABLE oauth_tokens
+                    DROP COLUMN access_token,
+                    DROP COLUMN refresh_token,
+                    ALTER COLUMN access_token_hash SET NOT NULL;
+            END IF;
+        END
+        $$
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_oauth_tokens_access_token_hash ON oauth_tokens(access_token_hash)")
         .execute(pool)
         .await?;
 
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_oauth_tokens_refresh_token ON oauth_tokens(refresh_token)")
+    sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_oauth_tokens_refresh_token_hash ON oauth_tokens(refresh_token_hash)")
         .execute(pool)
         .await?;
 
@@ -122,6 +173,78 @@ pub async fn run_migrations(pool: &Pool<Postgres>) -> Result<(), sqlx::Error> {
         .execute(pool)
         .await?;
 
+    // Контекст аутентификации и jti для access token (RFC 9068)
+    sqlx::query(
+        r#"
+        ALTER TABLE oauth_authorization_codes
+            ADD COLUMN IF NOT EXISTS auth_time TIMESTAMPTZ,
+            ADD COLUMN IF NOT EXISTS acr VARCHAR(50)
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        ALTER TABLE oauth_tokens
+            ADD COLUMN IF NOT EXISTS jti VARCHAR(64),
+            ADD COLUMN IF NOT EXISTS auth_time TIMESTAMPTZ,
+            ADD COLUMN IF NOT EXISTS acr VARCHAR(50)
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Исходный scope гранта: refresh token сохраняет его при сужении scope access token
+    sqlx::query("ALTER TABLE oauth_tokens ADD COLUMN IF NOT EXISTS grant_scope TEXT")
+        .execute(pool)
+        .await?;
+
+    // Время жизни токенов для клиента (NULL — глобальные значения из конфигурации)
+    sqlx::query(
+        r#"
+        ALTER TABLE oauth_clients
+            ADD COLUMN IF NOT EXISTS access_token_ttl BIGINT,
+            ADD COLUMN IF NOT EXISTS refresh_token_ttl BIGINT,
+            ADD COLUMN IF NOT EXISTS refresh_token_absolute_ttl BIGINT,
+            ADD COLUMN IF NOT EXISTS authorization_code_ttl BIGINT
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("ALTER TABLE oauth_tokens ADD COLUMN IF NOT EXISTS refresh_absolute_expires_at TIMESTAMPTZ")
+        .execute(pool)
+        .await?;
+
+    // Время последнего использования токена (для списка подключенных приложений)
+    sqlx::query("ALTER TABLE oauth_tokens ADD COLUMN IF NOT EXISTS last_used_at TIMESTAMPTZ")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_oauth_tokens_jti ON oauth_tokens(jti)")
+        .execute(pool)
+        .await?;
+
+    // Создание таблицы oauth_consents
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS oauth_consents (
+            id UUID PRIMARY KEY,
+            user_id UUID NOT NULL,
+            client_id VARCHAR(255) NOT NULL,
+            scopes TEXT[] NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL,
+            updated_at TIMESTAMPTZ NOT NULL,
+            UNIQUE (user_id, client_id),
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (client_id) REFERENCES oauth_clients(client_id) ON DELETE CASCADE
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
     // Создание таблицы oauth_scopes
     sqlx::query(
         r#"
@@ -148,14 +271,383 @@ pub async fn run_migrations(pool: &Pool<Postgres>) -> Result<(), sqlx::Error> {
             (gen_random_uuid(), 'read:profile', 'Чтение профиля пользователя', NOW()),
             (gen_random_uuid(), 'write:profile', 'Изменение профиля пользователя', NOW()),
             (gen_random_uuid(), 'read:email', 'Чтение email адреса', NOW()),
-            (gen_random_uuid(), 'admin', 'Административный доступ', NOW())
+            (gen_random_uuid(), 'admin', 'Административный доступ', NOW()),
+            (gen_random_uuid(), 'offline_access', 'Доступ без участия пользователя (refresh token)', NOW())
         ON CONFLICT (scope_name) DO NOTHING
         "#
     )
     .execute(pool)
     .await?;
 
+    // Флаги scopes и признак first-party клиента
+    sqlx::query(
+        r#"
+        ALTER TABLE oauth_scopes
+            ADD COLUMN IF NOT EXISTS requires_consent BOOLEAN NOT NULL DEFAULT true,
+            ADD COLUMN IF NOT EXISTS is_default BOOLEAN NOT NULL DEFAULT false,
+            ADD COLUMN IF NOT EXISTS restricted_to_first_party BOOLEAN NOT NULL DEFAULT false,
+            ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // admin доступен только first-party клиентам (пока scope не менялся через API)
+    sqlx::query(
+        r#"
+        UPDATE oauth_scopes
+        SET restricted_to_first_party = true, updated_at = NOW()
+        WHERE scope_name = 'admin' AND updated_at IS NULL
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Необязательные scopes: пользователь может отказаться от них на consent screen
+    sqlx::query("ALTER TABLE oauth_scopes ADD COLUMN IF NOT EXISTS is_optional BOOLEAN NOT NULL DEFAULT false")
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE oauth_scopes
+        SET is_optional = true, updated_at = NOW()
+        WHERE scope_name = 'read:email' AND updated_at IS NULL
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("ALTER TABLE oauth_consents ADD COLUMN IF NOT EXISTS declined_scopes TEXT[] NOT NULL DEFAULT '{}'")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE oauth_clients ADD COLUMN IF NOT EXISTS is_first_party BOOLEAN NOT NULL DEFAULT false")
+        .execute(pool)
+        .await?;
+
+    // Переводы описаний scopes для consent screen
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS oauth_scope_translations (
+            scope_name VARCHAR(100) NOT NULL REFERENCES oauth_scopes(scope_name) ON DELETE CASCADE,
+            locale VARCHAR(20) NOT NULL,
+            description TEXT NOT NULL,
+            PRIMARY KEY (scope_name, locale)
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO oauth_scope_translations (scope_name, locale, description)
+        VALUES
+            ('read:profile', 'en', 'Read your profile'),
+            ('write:profile', 'en', 'Update your profile'),
+            ('read:email', 'en', 'Read your email address'),
+            ('admin', 'en', 'Administrative access'),
+            ('offline_access', 'en', 'Access your data while you are offline (refresh token)')
+        ON CONFLICT (scope_name, locale) DO NOTHING
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Подтверждение email
+    sqlx::query(
+        r#"
+        ALTER TABLE users
+            ADD COLUMN IF NOT EXISTS email_verified BOOLEAN NOT NULL DEFAULT false,
+            ADD COLUMN IF NOT EXISTS email_verified_at TIMESTAMPTZ
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Одноразовые ссылки подтверждения email (хранится только SHA-256 от nonce)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS email_verification_tokens (
+            id UUID PRIMARY KEY,
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            token_hash VARCHAR(64) UNIQUE NOT NULL,
+            email VARCHAR(255) NOT NULL,
+            expires_at TIMESTAMPTZ NOT NULL,
+            used_at TIMESTAMPTZ,
+            created_at TIMESTAMPTZ NOT NULL
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_email_verification_tokens_user_id ON email_verification_tokens(user_id)")
+        .execute(pool)
+        .await?;
+
+    // Серверный учет браузерных сессий
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS user_sessions (
+            id UUID PRIMARY KEY,
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            user_agent TEXT,
+            ip_address VARCHAR(64),
+            created_at TIMESTAMPTZ NOT NULL,
+            last_seen_at TIMESTAMPTZ NOT NULL,
+            revoked_at TIMESTAMPTZ
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_user_sessions_user_id ON user_sessions(user_id)")
+        .execute(pool)
+        .await?;
+
+    // Одноразовые токены сброса пароля (хранится только SHA-256)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS password_reset_tokens (
+            id UUID PRIMARY KEY,
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            token_hash VARCHAR(64) UNIQUE NOT NULL,
+            expires_at TIMESTAMPTZ NOT NULL,
+            used_at TIMESTAMPTZ,
+            created_at TIMESTAMPTZ NOT NULL
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_password_reset_tokens_user_id ON password_reset_tokens(user_id)")
+        .execute(pool)
+        .await?;
+
+    // Методы аутентификации (amr) в кодах и токенах
+    sqlx::query("ALTER TABLE oauth_authorization_codes ADD COLUMN IF NOT EXISTS amr TEXT[]")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE oauth_tokens ADD COLUMN IF NOT EXISTS amr TEXT[]")
+        .execute(pool)
+        .await?;
+
+    // TOTP второй фактор: enabled_at пуст до подтверждения первым кодом
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS user_totp (
+            user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+            secret VARCHAR(128) NOT NULL,
+            enabled_at TIMESTAMPTZ,
+            last_used_step BIGINT,
+            created_at TIMESTAMPTZ NOT NULL
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Одноразовые коды восстановления (хранится только SHA-256)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS mfa_recovery_codes (
+            id UUID PRIMARY KEY,
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            code_hash VARCHAR(64) NOT NULL,
+            used_at TIMESTAMPTZ,
+            created_at TIMESTAMPTZ NOT NULL
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_mfa_recovery_codes_user_id ON mfa_recovery_codes(user_id)")
+        .execute(pool)
+        .await?;
+
+    // Ключи доступа (WebAuthn)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS webauthn_credentials (
+            id UUID PRIMARY KEY,
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            credential_id TEXT UNIQUE NOT NULL,
+            public_key BYTEA NOT NULL,
+            sign_count BIGINT NOT NULL DEFAULT 0,
+            name VARCHAR(100) NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL,
+            last_used_at TIMESTAMPTZ
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_webauthn_credentials_user_id ON webauthn_credentials(user_id)")
+        .execute(pool)
+        .await?;
+
+    // Одноразовые challenges церемоний WebAuthn (хранится только SHA-256).
+    // user_id пуст при входе без пароля
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS webauthn_challenges (
+            id UUID PRIMARY KEY,
+            challenge_hash VARCHAR(64) UNIQUE NOT NULL,
+            ceremony VARCHAR(20) NOT NULL,
+            user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+            expires_at TIMESTAMPTZ NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Неудачные попытки входа по паролю: scope 'account' (email) или 'ip'
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS login_failures (
+            scope VARCHAR(10) NOT NULL,
+            key VARCHAR(255) NOT NULL,
+            failure_count INTEGER NOT NULL,
+            first_failure_at TIMESTAMPTZ NOT NULL,
+            last_failure_at TIMESTAMPTZ NOT NULL,
+            locked_until TIMESTAMPTZ,
+            PRIMARY KEY (scope, key)
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Поля профиля пользователя
+    sqlx::query(
+        r#"
+        ALTER TABLE users
+            ADD COLUMN IF NOT EXISTS display_name VARCHAR(100),
+            ADD COLUMN IF NOT EXISTS locale VARCHAR(35),
+            ADD COLUMN IF NOT EXISTS timezone VARCHAR(64),
+            ADD COLUMN IF NOT EXISTS avatar_url TEXT
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Запросы смены email (хранится только SHA-256 от ссылок подтверждения и отмены)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS email_change_requests (
+            id UUID PRIMARY KEY,
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            old_email VARCHAR(255) NOT NULL,
+            old_email_verified BOOLEAN NOT NULL,
+            new_email VARCHAR(255) NOT NULL,
+            confirm_token_hash VARCHAR(64) UNIQUE NOT NULL,
+            cancel_token_hash VARCHAR(64) UNIQUE NOT NULL,
+            expires_at TIMESTAMPTZ NOT NULL,
+            cancel_expires_at TIMESTAMPTZ NOT NULL,
+            confirmed_at TIMESTAMPTZ,
+            cancelled_at TIMESTAMPTZ,
+            created_at TIMESTAMPTZ NOT NULL
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_email_change_requests_user_id ON email_change_requests(user_id)")
+        .execute(pool)
+        .await?;
+
+    // Запланированное удаление аккаунта: до deletion_scheduled_for его можно отменить
+    sqlx::query(
+        r#"
+        ALTER TABLE users
+            ADD COLUMN IF NOT EXISTS deletion_requested_at TIMESTAMPTZ,
+            ADD COLUMN IF NOT EXISTS deletion_scheduled_for TIMESTAMPTZ
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_users_deletion_scheduled_for ON users(deletion_scheduled_for) WHERE deletion_scheduled_for IS NOT NULL"
+    )
+    .execute(pool)
+    .await?;
+
+    // Статус пользователя: 'active' или 'suspended' (вход и выдача токенов запрещены);
+    // password_reset_required — вход по паролю запрещен до сброса пароля
+    sqlx::query(
+        r#"
+        ALTER TABLE users
+            ADD COLUMN IF NOT EXISTS status VARCHAR(20) NOT NULL DEFAULT 'active',
+            ADD COLUMN IF NOT EXISTS suspended_at TIMESTAMPTZ,
+            ADD COLUMN IF NOT EXISTS suspension_reason TEXT,
+            ADD COLUMN IF NOT EXISTS password_reset_required BOOLEAN NOT NULL DEFAULT false
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_users_status ON users(status)")
+        .execute(pool)
+        .await?;
+
+    // Неизвестный статус не должен давать доступ: такие записи блокируются,
+    // после чего допустимые значения закрепляются ограничением
+    sqlx::query("UPDATE users SET status = 'suspended' WHERE status NOT IN ('active', 'suspended')")
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        r#"
+        DO $$
+        BEGIN
+            IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'users_status_check') THEN
+                ALTER TABLE users ADD CONSTRAINT users_status_check CHECK (status IN ('active', 'suspended'));
+            END IF;
+        END
+        $$
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Роль администратора: пользовательские токены со scope admin принимаются
+    // в /api/admin только для таких пользователей. Назначается вручную в БД
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS is_admin BOOLEAN NOT NULL DEFAULT false")
+        .execute(pool)
+        .await?;
+
+    // Незавершенные входы со вторым фактором (хранится только SHA-256 идентификатора из cookie)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS mfa_pending_logins (
+            token_hash VARCHAR(64) PRIMARY KEY,
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            expires_at TIMESTAMPTZ NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_mfa_pending_logins_user_id ON mfa_pending_logins(user_id)")
+        .execute(pool)
+        .await?;
+
     println!("Миграции успешно применены");
+
     Ok(())
 }
 
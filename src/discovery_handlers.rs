@@ -0,0 +1,39 @@
+use actix_web::{web, HttpResponse, Responder};
+use crate::config::AppConfig;
+use crate::models::AuthorizationServerMetadata;
+
+// Формирование метаданных сервера авторизации (RFC 8414)
+pub fn build_authorization_server_metadata(config: &AppConfig) -> AuthorizationServerMetadata {
+    AuthorizationServerMetadata {
+        issuer: config.issuer.clone(),
+        authorization_endpoint: config.endpoint("/oauth/authorize"),
+        token_endpoint: config.endpoint("/oauth/token"),
+        revocation_endpoint: config.endpoint("/oauth/revoke"),
+        registration_endpoint: config.endpoint("/oauth/clients"),
+        response_types_supported: vec!["code".to_string()],
+        grant_types_supported: vec![
+            "authorization_code".to_string(),
+            "client_credentials".to_string(),
+            "refresh_token".to_string(),
+        ],
+        token_endpoint_auth_methods_supported: vec!["client_secret_post".to_string()],
+        revocation_endpoint_auth_methods_supported: vec!["none".to_string()],
+        introspection_endpoint: config.endpoint("/oauth/introspect"),
+        introspection_endpoint_auth_methods_supported: vec!["client_secret_post".to_string()],
+        code_challenge_methods_supported: config.pkce_policy.supported_methods(),
+        authorization_response_iss_parameter_supported: true,
+    }
+}
+
+// GET /.well-known/oauth-authorization-server
+pub async fn authorization_server_metadata(config: web::Data<AppConfig>) -> impl Responder {
+    HttpResponse::Ok().json(build_authorization_server_metadata(&config))
+}
+
+// Конфигурация маршрутов для discovery
+pub fn configure_discovery_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route(
+        "/.well-known/oauth-authorization-server",
+        web::get().to(authorization_server_metadata),
+    );
+}
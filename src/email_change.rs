@@ -0,0 +1,262 @@
+// Смена email: подтверждение по ссылке на новый адрес и отмена по ссылке из уведомления
+// на старый. users.email меняется только после подтверждения нового адреса
+use std::sync::Arc;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+use crate::config::{AppConfig, EmailChangePolicy};
+use crate::mail::{EmailMessage, MailError, MailSender};
+use crate::models::User;
+use crate::secrets::{hash_secret, random_secret};
+
+#[derive(Debug)]
+pub enum EmailChangeError {
+    DatabaseError(sqlx::Error),
+    // Адрес занят другим пользователем
+    EmailExists,
+    SameEmail,
+    // Ссылка не найдена, истекла или уже использована
+    InvalidToken,
+    MailError(MailError),
+}
+
+impl std::fmt::Display for EmailChangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmailChangeError::DatabaseError(e) => write!(f, "Database error: {}", e),
+            EmailChangeError::EmailExists => write!(f, "Email already exists"),
+            EmailChangeError::SameEmail => write!(f, "New email matches the current one"),
+            EmailChangeError::InvalidToken => write!(f, "Invalid or expired link"),
+            EmailChangeError::MailError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for EmailChangeError {}
+
+impl From<sqlx::Error> for EmailChangeError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::Database(ref db) if db.is_unique_violation() => EmailChangeError::EmailExists,
+            e => EmailChangeError::DatabaseError(e),
+        }
+    }
+}
+
+// Результат отмены: reverted — смена уже была подтверждена и прежний адрес восстановлен
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmailChangeCancellation {
+    pub user_id: Uuid,
+    pub reverted: bool,
+}
+
+#[derive(sqlx::FromRow)]
+struct EmailChangeRecord {
+    id: Uuid,
+    user_id: Uuid,
+    old_email: String,
+    old_email_verified: bool,
+    new_email: String,
+    confirmed_at: Option<DateTime<Utc>>,
+}
+
+pub struct EmailChangeService {
+    pool: Pool<Postgres>,
+    mailer: Arc<dyn MailSender>,
+    issuer: String,
+    policy: EmailChangePolicy,
+}
+
+impl EmailChangeService {
+    pub fn new(pool: Pool<Postgres>, mailer: Arc<dyn MailSender>, config: &AppConfig) -> Self {
+        Self {
+            pool,
+            mailer,
+            issuer: config.issuer.clone(),
+            policy: config.email_change,
+        }
+    }
+
+    // Запрос смены: ссылка подтверждения на новый адрес и уведомление со ссылкой отмены на старый.
+    // Предыдущий неподтвержденный запрос перестает действовать
+    pub async fn request_change(&self, user: &User, new_email: &str) -> Result<(), EmailChangeError> {
+        if new_email.eq_ignore_ascii_case(&user.email) {
+            return Err(EmailChangeError::SameEmail);
+        }
+
+        let confirm_token = random_secret(48);
+        let cancel_token = random_secret(48);
+        let now = Utc::now();
+
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            "DELETE FROM email_change_requests WHERE user_id = $1 AND confirmed_at IS NULL AND cancelled_at IS NULL"
+        )
+        .bind(user.id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO email_change_requests (
+                id, user_id, old_email, old_email_verified, new_email, confirm_token_hash, cancel_token_hash,
+                expires_at, cancel_expires_at, created_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#
+        )
+        .bind(Uuid::new_v4())
+        .bind(user.id)
+        .bind(&user.email)
+        .bind(user.email_verified)
+        .bind(new_email)
+        .bind(hash_secret(&confirm_token))
+        .bind(hash_secret(&cancel_token))
+        .bind(now + Duration::seconds(self.policy.link_ttl))
+        .bind(now + Duration::seconds(self.policy.cancel_ttl))
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        let confirmation = EmailMessage {
+            to: new_email.to_string(),
+            subject: "Подтверждение нового email".to_string(),
+            body: format!(
+                "Здравствуйте, {}!\n\nДля смены адреса аккаунта на этот перейдите по ссылке:\n{}/auth/email/change/confirm?token={}\n\nСсылка действительна {} ч. Если вы не запрашивали смену адреса, проигнорируйте это письмо.\n",
+                user.username,
+                self.issuer,
+                urlencoding::encode(&confirm_token),
+                self.policy.link_ttl / 3600
+            ),
+        };
+
+        let notice = EmailMessage {
+            to: user.email.clone(),
+            subject: "Смена email".to_string(),
+            body: format!(
+                "Здравствуйте, {}!\n\nЗапрошена смена адреса вашего аккаунта на {}.\n\nЕсли это были не вы, отмените смену по ссылке:\n{}/auth/email/change/cancel?token={}\n\nСсылка действительна {} дн. и вернет этот адрес, даже если смена уже подтверждена. После отмены смените пароль.\n",
+                user.username,
+                mask_email(new_email),
+                self.issuer,
+                urlencoding::encode(&cancel_token),
+                self.policy.cancel_ttl / 86400
+            ),
+        };
+
+        let confirmation_result = self.mailer.send(confirmation).await;
+        let notice_result = self.mailer.send(notice).await;
+        confirmation_result.and(notice_result).map_err(EmailChangeError::MailError)
+    }
+
+    // Подтверждение нового адреса: погашение ссылки и замена users.email одной транзакцией.
+    // Адрес, занятый за время ожидания, не заменяется (уникальность обеспечивает таблица users)
+    pub async fn confirm_change(&self, token: &str) -> Result<Uuid, EmailChangeError> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+
+        let record = sqlx::query_as::<_, EmailChangeRecord>(
+            r#"
+            UPDATE email_change_requests SET confirmed_at = $1
+            WHERE confirm_token_hash = $2 AND confirmed_at IS NULL AND cancelled_at IS NULL AND expires_at > $1
+            RETURNING id, user_id, old_email, old_email_verified, new_email, confirmed_at
+            "#
+        )
+        .bind(now)
+        .bind(hash_secret(token))
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(EmailChangeError::InvalidToken)?;
+
+        // Адрес сменился другим способом после запроса — ссылка устарела
+        let updated = sqlx::query(
+            r#"
+            UPDATE users SET email = $1, email_verified = true, email_verified_at = $2, updated_at = $2
+            WHERE id = $3 AND email = $4
+            "#
+        )
+        .bind(&record.new_email)
+        .bind(now)
+        .bind(record.user_id)
+        .bind(&record.old_email)
+        .execute(&mut *tx)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Err(EmailChangeError::InvalidToken);
+        }
+
+        // Ссылки сброса пароля, отправленные на прежний адрес, перестают действовать
+        sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NULL")
+            .bind(record.user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(record.user_id)
+    }
+
+    // Отмена по ссылке из уведомления. Неподтвержденный запрос просто гасится,
+    // подтвержденный — возвращает прежний адрес и его статус подтверждения
+    pub async fn cancel_change(&self, token: &str) -> Result<EmailChangeCancellation, EmailChangeError> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+
+        let record = sqlx::query_as::<_, EmailChangeRecord>(
+            r#"
+            SELECT id, user_id, old_email, old_email_verified, new_email, confirmed_at
+            FROM email_change_requests
+            WHERE cancel_token_hash = $1 AND cancelled_at IS NULL AND cancel_expires_at > $2
+            FOR UPDATE
+            "#
+        )
+        .bind(hash_secret(token))
+        .bind(now)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(EmailChangeError::InvalidToken)?;
+
+        let reverted = record.confirmed_at.is_some();
+        if reverted {
+            let updated = sqlx::query(
+                r#"
+                UPDATE users SET email = $1, email_verified = $2, updated_at = $3
+                WHERE id = $4 AND email = $5
+                "#
+            )
+            .bind(&record.old_email)
+            .bind(record.old_email_verified)
+            .bind(now)
+            .bind(record.user_id)
+            .bind(&record.new_email)
+            .execute(&mut *tx)
+            .await?;
+
+            if updated.rows_affected() == 0 {
+                return Err(EmailChangeError::InvalidToken);
+            }
+        }
+
+        sqlx::query("UPDATE email_change_requests SET cancelled_at = $1 WHERE id = $2")
+            .bind(now)
+            .bind(record.id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(EmailChangeCancellation { user_id: record.user_id, reverted })
+    }
+}
+
+// Адрес в уведомлении на старый email: первый символ и домен (j***@example.com)
+pub fn mask_email(email: &str) -> String {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            let first = local.chars().next().map(String::from).unwrap_or_default();
+            format!("{}***@{}", first, domain)
+        }
+        None => "***".to_string(),
+    }
+}
@@ -0,0 +1,215 @@
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, Responder};
+use actix_session::Session;
+use validator::Validate;
+use crate::account_handlers::{session_user, verify_current_password};
+use crate::email_change::{EmailChangeError, EmailChangeService};
+use crate::login_throttle::LoginThrottle;
+use crate::models::{ChangeEmailRequest, ErrorResponse, VerifyEmailQuery};
+use crate::oauth_handlers::html_escape;
+use crate::services::UserService;
+use crate::session_service::SessionService;
+use crate::token_service::TokenService;
+
+// Страница с результатом перехода по ссылке из письма
+fn message_page(status: StatusCode, message: &str) -> HttpResponse {
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="UTF-8">
+    <title>Смена email</title>
+    <style>
+        body {{ font-family: Arial, sans-serif; max-width: 400px; margin: 50px auto; padding: 20px; text-align: center; }}
+    </style>
+</head>
+<body>
+    <h1>Смена email</h1>
+    <p>{}</p>
+    <p><a href="/auth/login">Вход</a></p>
+</body>
+</html>"#,
+        message
+    );
+
+    HttpResponse::build(status)
+        .content_type("text/html; charset=utf-8")
+        .body(html)
+}
+
+// Страница ссылки из письма: сама ссылка ничего не меняет (ее открывают сканеры почты
+// и предпросмотр), изменение выполняется POST-запросом с токеном по кнопке
+fn action_page(action: &str, message: &str, button: &str, token: &str) -> HttpResponse {
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="UTF-8">
+    <title>Смена email</title>
+    <style>
+        body {{ font-family: Arial, sans-serif; max-width: 400px; margin: 50px auto; padding: 20px; text-align: center; }}
+        button {{ padding: 10px 20px; background: #007bff; color: white; border: none; cursor: pointer; }}
+    </style>
+</head>
+<body>
+    <h1>Смена email</h1>
+    <p>{}</p>
+    <form method="POST" action="{}">
+        <input type="hidden" name="token" value="{}">
+        <button type="submit">{}</button>
+    </form>
+</body>
+</html>"#,
+        message,
+        action,
+        html_escape(token),
+        button
+    );
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .insert_header(("Referrer-Policy", "no-referrer"))
+        .body(html)
+}
+
+// POST /auth/email/change - запрос смены email (требует текущий пароль)
+pub async fn request_email_change(
+    req: HttpRequest,
+    user_service: web::Data<UserService>,
+    login_throttle: web::Data<LoginThrottle>,
+    email_change_service: web::Data<EmailChangeService>,
+    session: Session,
+    request: web::Json<ChangeEmailRequest>,
+) -> impl Responder {
+    let user = match session_user(&user_service, &session).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    if let Err(errors) = request.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Validation error: {}", errors),
+        });
+    }
+    if let Err(response) = verify_current_password(
+        &req, &user_service, &login_throttle, &user, &request.current_password,
+    ).await {
+        return response;
+    }
+
+    match user_service.email_exists(&request.new_email).await {
+        Ok(false) => {}
+        Ok(true) => {
+            return HttpResponse::Conflict().json(ErrorResponse {
+                error: EmailChangeError::EmailExists.to_string(),
+            });
+        }
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            });
+        }
+    }
+
+    match email_change_service.request_change(&user, &request.new_email).await {
+        Ok(()) => HttpResponse::Accepted().json(serde_json::json!({
+            "message": "Confirmation link has been sent to the new email address"
+        })),
+        Err(EmailChangeError::SameEmail) => HttpResponse::BadRequest().json(ErrorResponse {
+            error: EmailChangeError::SameEmail.to_string(),
+        }),
+        Err(e) => {
+            eprintln!("Email change error: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            })
+        }
+    }
+}
+
+// GET /auth/email/change/confirm?token=... - страница подтверждения по ссылке из письма
+pub async fn confirm_email_change_page(query: web::Query<VerifyEmailQuery>) -> impl Responder {
+    action_page(
+        "/auth/email/change/confirm",
+        "Подтвердите, что этот адрес должен стать адресом вашего аккаунта.",
+        "Подтвердить адрес",
+        &query.token,
+    )
+}
+
+// POST /auth/email/change/confirm - подтверждение нового адреса
+pub async fn confirm_email_change(
+    email_change_service: web::Data<EmailChangeService>,
+    form: web::Form<VerifyEmailQuery>,
+) -> impl Responder {
+    match email_change_service.confirm_change(&form.token).await {
+        Ok(_) => message_page(StatusCode::OK, "Адрес email изменен. Используйте его для входа."),
+        Err(EmailChangeError::InvalidToken) => message_page(
+            StatusCode::BAD_REQUEST,
+            "Ссылка недействительна, истекла или смена адреса отменена.",
+        ),
+        Err(EmailChangeError::EmailExists) => message_page(
+            StatusCode::CONFLICT,
+            "Этот адрес уже используется другим аккаунтом.",
+        ),
+        Err(e) => {
+            eprintln!("Email change error: {}", e);
+            message_page(StatusCode::INTERNAL_SERVER_ERROR, "Внутренняя ошибка сервера.")
+        }
+    }
+}
+
+// GET /auth/email/change/cancel?token=... - страница отмены по ссылке из уведомления на старый адрес
+pub async fn cancel_email_change_page(query: web::Query<VerifyEmailQuery>) -> impl Responder {
+    action_page(
+        "/auth/email/change/cancel",
+        "Если вы не меняли адрес email, отмените смену: прежний адрес будет восстановлен, а все сессии завершены.",
+        "Отменить смену адреса",
+        &query.token,
+    )
+}
+
+// POST /auth/email/change/cancel - отмена смены адреса.
+// Если смена уже подтверждена, адрес возвращается, а сессии и токены пользователя отзываются
+pub async fn cancel_email_change(
+    email_change_service: web::Data<EmailChangeService>,
+    session_service: web::Data<SessionService>,
+    token_service: web::Data<TokenService>,
+    form: web::Form<VerifyEmailQuery>,
+) -> impl Responder {
+    match email_change_service.cancel_change(&form.token).await {
+        Ok(cancellation) if cancellation.reverted => {
+            if let Err(e) = session_service.revoke_user_sessions(cancellation.user_id).await {
+                eprintln!("Database error: {}", e);
+            }
+            if let Err(e) = token_service.revoke_user_tokens(cancellation.user_id).await {
+                eprintln!("Database error: {}", e);
+            }
+            message_page(
+                StatusCode::OK,
+                "Прежний адрес email восстановлен, все сессии завершены. Смените пароль.",
+            )
+        }
+        Ok(_) => message_page(StatusCode::OK, "Смена адреса email отменена."),
+        Err(EmailChangeError::InvalidToken) => message_page(
+            StatusCode::BAD_REQUEST,
+            "Ссылка недействительна, истекла или уже использована.",
+        ),
+        Err(EmailChangeError::EmailExists) => message_page(
+            StatusCode::CONFLICT,
+            "Прежний адрес уже используется другим аккаунтом. Обратитесь в поддержку.",
+        ),
+        Err(e) => {
+            eprintln!("Email change error: {}", e);
+            message_page(StatusCode::INTERNAL_SERVER_ERROR, "Внутренняя ошибка сервера.")
+        }
+    }
+}
+
+// Маршруты смены email (подключаются внутри scope /auth)
+pub fn configure_email_change_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/email/change", web::post().to(request_email_change))
+        .route("/email/change/confirm", web::get().to(confirm_email_change_page))
+        .route("/email/change/confirm", web::post().to(confirm_email_change))
+        .route("/email/change/cancel", web::get().to(cancel_email_change_page))
+        .route("/email/change/cancel", web::post().to(cancel_email_change));
+}
@@ -0,0 +1,208 @@
+// Подтверждение email: подписанные одноразовые ссылки
+use std::sync::Arc;
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+use crate::config::{AppConfig, EmailVerificationPolicy};
+use crate::mail::{EmailMessage, MailError, MailSender};
+use crate::models::{EmailVerificationToken, User};
+use crate::secrets::{hash_secret, random_secret};
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug)]
+pub enum VerificationError {
+    DatabaseError(sqlx::Error),
+    InvalidToken,
+    TokenExpired,
+    TokenAlreadyUsed,
+    MailError(MailError),
+}
+
+impl std::fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerificationError::DatabaseError(e) => write!(f, "Database error: {}", e),
+            VerificationError::InvalidToken => write!(f, "Invalid verification link"),
+            VerificationError::TokenExpired => write!(f, "Verification link expired"),
+            VerificationError::TokenAlreadyUsed => write!(f, "Verification link already used"),
+            VerificationError::MailError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+impl From<sqlx::Error> for VerificationError {
+    fn from(e: sqlx::Error) -> Self {
+        VerificationError::DatabaseError(e)
+    }
+}
+
+// Колонки email_verification_tokens, возвращаемые во всех запросах
+const VERIFICATION_COLUMNS: &str = "id, user_id, token_hash, email, expires_at, used_at, created_at";
+
+pub struct EmailVerificationService {
+    pool: Pool<Postgres>,
+    mailer: Arc<dyn MailSender>,
+    secret: Vec<u8>,
+    issuer: String,
+    policy: EmailVerificationPolicy,
+}
+
+impl EmailVerificationService {
+    pub fn new(pool: Pool<Postgres>, mailer: Arc<dyn MailSender>, secret: &str, config: &AppConfig) -> Self {
+        Self {
+            pool,
+            mailer,
+            secret: secret.as_bytes().to_vec(),
+            issuer: config.issuer.clone(),
+            policy: config.email_verification,
+        }
+    }
+
+    fn verification_link(&self, token: &str) -> String {
+        format!("{}/auth/verify-email?token={}", self.issuer, urlencoding::encode(token))
+    }
+
+    // Создание ссылки подтверждения и отправка письма
+    pub async fn send_verification(&self, user: &User) -> Result<(), VerificationError> {
+        if user.email_verified {
+            return Ok(());
+        }
+
+        let nonce = random_secret(32);
+        let token = sign_verification_token(&self.secret, &nonce);
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO email_verification_tokens (id, user_id, token_hash, email, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#
+        )
+        .bind(Uuid::new_v4())
+        .bind(user.id)
+        .bind(hash_secret(&nonce))
+        .bind(&user.email)
+        .bind(now + Duration::seconds(self.policy.link_ttl))
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        let message = EmailMessage {
+            to: user.email.clone(),
+            subject: "Подтверждение email".to_string(),
+            body: format!(
+                "Здравствуйте, {}!\n\nДля подтверждения адреса перейдите по ссылке:\n{}\n\nСсылка действительна {} ч. Если вы не регистрировались, проигнорируйте это письмо.\n",
+                user.username,
+                self.verification_link(&token),
+                self.policy.link_ttl / 3600
+            ),
+        };
+
+        self.mailer.send(message).await.map_err(VerificationError::MailError)
+    }
+
+    // Повторная отправка: предыдущие ссылки перестают действовать.
+    // false — email уже подтвержден или письмо отправлялось недавно
+    pub async fn resend_verification(&self, user: &User) -> Result<bool, VerificationError> {
+        if user.email_verified {
+            return Ok(false);
+        }
+
+        let last_sent = sqlx::query_scalar::<_, Option<chrono::DateTime<Utc>>>(
+            "SELECT MAX(created_at) FROM email_verification_tokens WHERE user_id = $1"
+        )
+        .bind(user.id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if last_sent.is_some_and(|sent| Utc::now() - sent < Duration::seconds(self.policy.resend_interval)) {
+            return Ok(false);
+        }
+
+        sqlx::query("DELETE FROM email_verification_tokens WHERE user_id = $1 AND used_at IS NULL")
+            .bind(user.id)
+            .execute(&self.pool)
+            .await?;
+
+        self.send_verification(user).await?;
+        Ok(true)
+    }
+
+    // Подтверждение email по ссылке, возвращает ID пользователя
+    pub async fn verify_email(&self, token: &str) -> Result<Uuid, VerificationError> {
+        let nonce = verify_verification_token(&self.secret, token)
+            .ok_or(VerificationError::InvalidToken)?;
+
+        let mut tx = self.pool.begin().await?;
+
+        let record = sqlx::query_as::<_, EmailVerificationToken>(&format!(
+            "SELECT {} FROM email_verification_tokens WHERE token_hash = $1 FOR UPDATE",
+            VERIFICATION_COLUMNS
+        ))
+        .bind(hash_secret(nonce))
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(VerificationError::InvalidToken)?;
+
+        if record.used_at.is_some() {
+            return Err(VerificationError::TokenAlreadyUsed);
+        }
+
+        let now = Utc::now();
+        if now > record.expires_at {
+            return Err(VerificationError::TokenExpired);
+        }
+
+        sqlx::query("UPDATE email_verification_tokens SET used_at = $1 WHERE id = $2")
+            .bind(now)
+            .bind(record.id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Ссылка действительна только для адреса, на который была отправлена
+        let updated = sqlx::query(
+            r#"
+            UPDATE users
+            SET email_verified = true, email_verified_at = $1, updated_at = $1
+            WHERE id = $2 AND email = $3
+            "#
+        )
+        .bind(now)
+        .bind(record.user_id)
+        .bind(&record.email)
+        .execute(&mut *tx)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Err(VerificationError::InvalidToken);
+        }
+
+        tx.commit().await?;
+        Ok(record.user_id)
+    }
+}
+
+// Токен ссылки: nonce и его HMAC-SHA256 подпись
+pub fn sign_verification_token(secret: &[u8], nonce: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(nonce.as_bytes());
+    format!("{}.{}", nonce, general_purpose::URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+}
+
+// Проверка подписи токена, возвращает nonce
+pub fn verify_verification_token<'a>(secret: &[u8], token: &'a str) -> Option<&'a str> {
+    let (nonce, signature) = token.split_once('.')?;
+    let signature = general_purpose::URL_SAFE_NO_PAD.decode(signature).ok()?;
+
+    let mut mac = HmacSha256::new_from_slice(secret).ok()?;
+    mac.update(nonce.as_bytes());
+    mac.verify_slice(&signature).ok()?;
+
+    Some(nonce)
+}
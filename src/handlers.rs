@@ -1,11 +1,23 @@
 use actix_web::{web, HttpResponse, Responder};
 use validator::Validate;
-use crate::models::{RegisterUserRequest, RegisterUserResponse, ErrorResponse};
+use crate::models::{RegisterUserRequest, RegisterUserResponse, ErrorResponse, PasswordPolicyErrorResponse};
 use crate::services::{UserService, RegistrationError};
+use crate::email_verification::EmailVerificationService;
+use crate::password_policy::{PasswordPolicy, PasswordViolation};
+
+// Ответ 400 со списком нарушенных правил политики паролей
+pub(crate) fn password_policy_error_response(violations: Vec<PasswordViolation>) -> HttpResponse {
+    HttpResponse::BadRequest().json(PasswordPolicyErrorResponse {
+        error: "Пароль не соответствует требованиям".to_string(),
+        violations,
+    })
+}
 
 // Endpoint для регистрации пользователя
 pub async fn register(
     user_service: web::Data<UserService>,
+    verification_service: web::Data<EmailVerificationService>,
+    password_policy: web::Data<PasswordPolicy>,
     request: web::Json<RegisterUserRequest>,
 ) -> impl Responder {
     // Валидация входных данных
@@ -15,9 +27,19 @@ pub async fn register(
         });
     }
 
+    let violations = password_policy.check(&request.password, &request.username, &request.email).await;
+    if !violations.is_empty() {
+        return password_policy_error_response(violations);
+    }
+
     // Регистрация пользователя
     match user_service.register_user(request.into_inner()).await {
         Ok(user) => {
+            // Ошибка отправки письма не отменяет регистрацию: письмо можно запросить повторно
+            if let Err(e) = verification_service.send_verification(&user).await {
+                eprintln!("Ошибка отправки письма подтверждения: {}", e);
+            }
+
             let response: RegisterUserResponse = user.into();
             HttpResponse::Created().json(response)
         }
@@ -0,0 +1,137 @@
+// Пул для хеширования паролей и секретов клиентов вне потоков actix.
+// Число одновременных задач ограничено, время ожидания в очереди учитывается в метриках
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use serde::Serialize;
+use tokio::sync::Semaphore;
+use crate::config::HashingPoolConfig;
+
+#[derive(Debug)]
+pub struct HashingPoolError(String);
+
+impl std::fmt::Display for HashingPoolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Hashing task failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for HashingPoolError {}
+
+// Снимок метрик пула (время в микросекундах)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HashingPoolMetrics {
+    pub max_concurrency: usize,
+    pub in_flight: usize,
+    pub queued: usize,
+    pub completed: u64,
+    pub failed: u64,
+    pub total_queue_time_us: u64,
+    pub max_queue_time_us: u64,
+    pub avg_queue_time_us: u64,
+    pub total_run_time_us: u64,
+}
+
+pub struct HashingPool {
+    semaphore: Arc<Semaphore>,
+    max_concurrency: usize,
+    in_flight: Arc<AtomicUsize>,
+    queued: AtomicUsize,
+    started: AtomicU64,
+    completed: AtomicU64,
+    failed: AtomicU64,
+    total_queue_time_us: AtomicU64,
+    max_queue_time_us: AtomicU64,
+    total_run_time_us: Arc<AtomicU64>,
+}
+
+// Уменьшает счетчик при выходе из области видимости, в том числе при отмене запроса
+struct CounterGuard<'a>(&'a AtomicUsize);
+
+impl Drop for CounterGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl HashingPool {
+    pub fn new(config: &HashingPoolConfig) -> Self {
+        let max_concurrency = config.max_concurrency.max(1);
+        Self {
+            semaphore: Arc::new(Semaphore::new(max_concurrency)),
+            max_concurrency,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            queued: AtomicUsize::new(0),
+            started: AtomicU64::new(0),
+            completed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            total_queue_time_us: AtomicU64::new(0),
+            max_queue_time_us: AtomicU64::new(0),
+            total_run_time_us: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    // Выполнение задачи в пуле блокирующих потоков tokio после получения слота.
+    // Слот освобождается по завершении задачи, даже если ожидавший ее запрос отменен
+    pub async fn run<T, F>(&self, task: F) -> Result<T, HashingPoolError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let enqueued_at = Instant::now();
+        let permit = {
+            self.queued.fetch_add(1, Ordering::Relaxed);
+            let _queued = CounterGuard(&self.queued);
+            Arc::clone(&self.semaphore)
+                .acquire_owned()
+                .await
+                .map_err(|e| HashingPoolError(e.to_string()))?
+        };
+
+        let queue_time_us = enqueued_at.elapsed().as_micros() as u64;
+        self.started.fetch_add(1, Ordering::Relaxed);
+        self.total_queue_time_us.fetch_add(queue_time_us, Ordering::Relaxed);
+        self.max_queue_time_us.fetch_max(queue_time_us, Ordering::Relaxed);
+
+        let in_flight = Arc::clone(&self.in_flight);
+        let total_run_time_us = Arc::clone(&self.total_run_time_us);
+        in_flight.fetch_add(1, Ordering::Relaxed);
+
+        let result = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let _in_flight = CounterGuard(&in_flight);
+            let started_at = Instant::now();
+            let output = task();
+            total_run_time_us.fetch_add(started_at.elapsed().as_micros() as u64, Ordering::Relaxed);
+            output
+        })
+        .await;
+
+        match result {
+            Ok(output) => {
+                self.completed.fetch_add(1, Ordering::Relaxed);
+                Ok(output)
+            }
+            Err(e) => {
+                self.failed.fetch_add(1, Ordering::Relaxed);
+                Err(HashingPoolError(e.to_string()))
+            }
+        }
+    }
+
+    pub fn metrics(&self) -> HashingPoolMetrics {
+        let total_queue_time_us = self.total_queue_time_us.load(Ordering::Relaxed);
+
+        HashingPoolMetrics {
+            max_concurrency: self.max_concurrency,
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            queued: self.queued.load(Ordering::Relaxed),
+            completed: self.completed.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            total_queue_time_us,
+            max_queue_time_us: self.max_queue_time_us.load(Ordering::Relaxed),
+            avg_queue_time_us: total_queue_time_us.checked_div(self.started.load(Ordering::Relaxed)).unwrap_or(0),
+            total_run_time_us: self.total_run_time_us.load(Ordering::Relaxed),
+        }
+    }
+}
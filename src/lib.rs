@@ -9,4 +9,34 @@ pub mod auth_handlers;
 pub mod oauth_handlers;
 pub mod middleware;
 pub mod protected_handlers;
+pub mod config;
+pub mod discovery_handlers;
+pub mod pkce;
+pub mod scope_utils;
+pub mod consent_service;
+pub mod account_handlers;
+pub mod scope_service;
+pub mod admin_handlers;
+pub mod redirect_uri;
+pub mod secrets;
+pub mod mail;
+pub mod email_verification;
+pub mod session_service;
+pub mod password_reset;
+pub mod mfa_service;
+pub mod pending_login;
+pub mod mfa_handlers;
+pub mod webauthn;
+pub mod passkey_service;
+pub mod passkey_handlers;
+pub mod login_throttle;
+pub mod password_policy;
+pub mod password_hasher;
+pub mod hashing_pool;
+pub mod profile_handlers;
+pub mod email_change;
+pub mod email_change_handlers;
+pub mod account_deletion;
+pub mod data_export;
+pub mod privacy_handlers;
 
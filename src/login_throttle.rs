@@ -0,0 +1,175 @@
+// Учет неудачных попыток входа по паролю и временная блокировка.
+// Состояние хранится в Postgres и общее для всех экземпляров сервера
+use std::net::IpAddr;
+use actix_web::{web, HttpRequest};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{Pool, Postgres};
+use crate::config::{AppConfig, LoginThrottlePolicy};
+
+pub(crate) const SCOPE_ACCOUNT: &str = "account";
+const SCOPE_IP: &str = "ip";
+
+// Ключ аккаунта: email без учета регистра (в том числе для несуществующих аккаунтов)
+pub fn account_key(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+// IP-адрес клиента для учета неудач и списка сессий. X-Forwarded-For задает сам клиент,
+// поэтому заголовок учитывается, только если соединение пришло от доверенного прокси
+// (TRUSTED_PROXIES): цепочка читается справа налево до первого недоверенного адреса
+pub fn client_ip(req: &HttpRequest) -> Option<String> {
+    let mut ip = req.peer_addr()?.ip();
+    let trusted_proxies = req
+        .app_data::<web::Data<AppConfig>>()
+        .map(|config| config.trusted_proxies.as_slice())
+        .unwrap_or_default();
+
+    if trusted_proxies.contains(&ip) {
+        let forwarded: Vec<&str> = req
+            .headers()
+            .get_all("X-Forwarded-For")
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+
+        for hop in forwarded.iter().rev() {
+            let Ok(hop) = hop.parse::<IpAddr>() else { break };
+            ip = hop;
+            if !trusted_proxies.contains(&ip) {
+                break;
+            }
+        }
+    }
+
+    Some(ip.to_string())
+}
+
+pub struct LoginThrottle {
+    pool: Pool<Postgres>,
+    policy: LoginThrottlePolicy,
+}
+
+impl LoginThrottle {
+    pub fn new(pool: Pool<Postgres>, policy: LoginThrottlePolicy) -> Self {
+        Self { pool, policy }
+    }
+
+    pub fn policy(&self) -> &LoginThrottlePolicy {
+        &self.policy
+    }
+
+    // Заблокирован ли вход для аккаунта или IP-адреса
+    pub async fn is_locked(&self, email: &str, ip: Option<&str>) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM login_failures
+                WHERE ((scope = $1 AND key = $2) OR (scope = $3 AND key = $4)) AND locked_until > $5
+            )
+            "#
+        )
+        .bind(SCOPE_ACCOUNT)
+        .bind(account_key(email))
+        .bind(SCOPE_IP)
+        .bind(ip.unwrap_or_default())
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    // Учет неудачной попытки. Возвращает задержку ответа по числу неудач подряд
+    pub async fn record_failure(&self, email: &str, ip: Option<&str>) -> Result<std::time::Duration, sqlx::Error> {
+        let account_failures = self
+            .increment(SCOPE_ACCOUNT, &account_key(email), self.policy.max_account_failures)
+            .await?;
+        if let Some(ip) = ip {
+            self.increment(SCOPE_IP, ip, self.policy.max_ip_failures).await?;
+        }
+
+        Ok(self.policy.failure_delay(account_failures))
+    }
+
+    // Успешный вход сбрасывает счетчик аккаунта; счетчик IP истекает сам,
+    // иначе вход в собственный аккаунт обнулял бы перебор чужих
+    pub async fn record_success(&self, email: &str) -> Result<(), sqlx::Error> {
+        self.clear(SCOPE_ACCOUNT, &account_key(email)).await.map(|_| ())
+    }
+
+    // Снятие блокировки аккаунта администратором; false, если блокировки и неудач не было
+    pub async fn unlock_account(&self, email: &str) -> Result<bool, sqlx::Error> {
+        self.clear(SCOPE_ACCOUNT, &account_key(email)).await
+    }
+
+    // Время окончания блокировки аккаунта, если она действует
+    pub async fn account_locked_until(&self, email: &str) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        sqlx::query_scalar::<_, DateTime<Utc>>(
+            "SELECT locked_until FROM login_failures WHERE scope = $1 AND key = $2 AND locked_until > $3"
+        )
+        .bind(SCOPE_ACCOUNT)
+        .bind(account_key(email))
+        .bind(Utc::now())
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn clear(&self, scope: &str, key: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM login_failures WHERE scope = $1 AND key = $2")
+            .bind(scope)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Атомарное увеличение счетчика; неудачи вне окна и после истекшей блокировки
+    // начинают отсчет заново. При достижении порога ставится блокировка
+    async fn increment(&self, scope: &str, key: &str, max_failures: i64) -> Result<i64, sqlx::Error> {
+        let now = Utc::now();
+
+        let failures = sqlx::query_scalar::<_, i32>(
+            r#"
+            INSERT INTO login_failures (scope, key, failure_count, first_failure_at, last_failure_at)
+            VALUES ($1, $2, 1, $3, $3)
+            ON CONFLICT (scope, key) DO UPDATE SET
+                failure_count = CASE
+                    WHEN login_failures.last_failure_at < $4 OR login_failures.locked_until <= $3 THEN 1
+                    ELSE login_failures.failure_count + 1
+                END,
+                first_failure_at = CASE
+                    WHEN login_failures.last_failure_at < $4 OR login_failures.locked_until <= $3 THEN $3
+                    ELSE login_failures.first_failure_at
+                END,
+                locked_until = CASE
+                    WHEN login_failures.locked_until <= $3 THEN NULL
+                    ELSE login_failures.locked_until
+                END,
+                last_failure_at = $3
+            RETURNING failure_count
+            "#
+        )
+        .bind(scope)
+        .bind(key)
+        .bind(now)
+        .bind(now - Duration::seconds(self.policy.failure_window))
+        .fetch_one(&self.pool)
+        .await? as i64;
+
+        if failures >= max_failures {
+            sqlx::query(
+                r#"
+                UPDATE login_failures SET locked_until = $1
+                WHERE scope = $2 AND key = $3 AND locked_until IS NULL
+                "#
+            )
+            .bind(now + Duration::seconds(self.policy.lockout_duration))
+            .bind(scope)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        }
+
+        Ok(failures)
+    }
+}
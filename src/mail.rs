@@ -0,0 +1,212 @@
+// Отправка писем: SMTP для production, файловый и in-memory outbox для разработки и тестов
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use chrono::Utc;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use uuid::Uuid;
+
+// Письмо (text/plain)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub enum MailError {
+    InvalidAddress(String),
+    Build(String),
+    Transport(String),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for MailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MailError::InvalidAddress(address) => write!(f, "Invalid email address: {}", address),
+            MailError::Build(e) => write!(f, "Failed to build message: {}", e),
+            MailError::Transport(e) => write!(f, "Mail transport error: {}", e),
+            MailError::Io(e) => write!(f, "Mail outbox error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MailError {}
+
+// Способ доставки писем
+#[async_trait]
+pub trait MailSender: Send + Sync {
+    async fn send(&self, message: EmailMessage) -> Result<(), MailError>;
+}
+
+// Настройки отправки писем
+#[derive(Debug, Clone)]
+pub struct MailConfig {
+    // Адрес отправителя (From)
+    pub from: String,
+    pub transport: MailTransport,
+}
+
+#[derive(Debug, Clone)]
+pub enum MailTransport {
+    Smtp(SmtpSettings),
+    // Каталог, в который письма сохраняются файлами .eml
+    File(PathBuf),
+    Memory,
+}
+
+#[derive(Clone)]
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    // STARTTLS (false — соединение без шифрования, только для локального relay)
+    pub starttls: bool,
+}
+
+// Пароль SMTP не выводится в логи
+impl std::fmt::Debug for SmtpSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SmtpSettings")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("username", &self.username)
+            .field("starttls", &self.starttls)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        Self {
+            from: "no-reply@localhost".to_string(),
+            transport: MailTransport::File(PathBuf::from("mail_outbox")),
+        }
+    }
+}
+
+// Создание отправителя по конфигурации
+pub fn build_mail_sender(config: &MailConfig) -> Result<Arc<dyn MailSender>, MailError> {
+    let sender: Arc<dyn MailSender> = match &config.transport {
+        MailTransport::Smtp(settings) => Arc::new(SmtpMailSender::new(settings, &config.from)?),
+        MailTransport::File(dir) => Arc::new(FileMailSender::new(dir.clone(), &config.from)?),
+        MailTransport::Memory => Arc::new(InMemoryMailSender::new()),
+    };
+
+    Ok(sender)
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox, MailError> {
+    address.parse().map_err(|_| MailError::InvalidAddress(address.to_string()))
+}
+
+// Письмо в формате RFC 5322
+fn build_message(from: &Mailbox, message: EmailMessage) -> Result<Message, MailError> {
+    Message::builder()
+        .from(from.clone())
+        .to(parse_mailbox(&message.to)?)
+        .subject(message.subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(message.body)
+        .map_err(|e| MailError::Build(e.to_string()))
+}
+
+// Отправка через SMTP сервер
+pub struct SmtpMailSender {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailSender {
+    pub fn new(settings: &SmtpSettings, from: &str) -> Result<Self, MailError> {
+        let builder = if settings.starttls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host)
+                .map_err(|e| MailError::Transport(e.to_string()))?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host)
+        };
+
+        let builder = match (&settings.username, &settings.password) {
+            (Some(username), Some(password)) => {
+                builder.credentials(Credentials::new(username.clone(), password.clone()))
+            }
+            _ => builder,
+        };
+
+        Ok(Self {
+            transport: builder.port(settings.port).build(),
+            from: parse_mailbox(from)?,
+        })
+    }
+}
+
+#[async_trait]
+impl MailSender for SmtpMailSender {
+    async fn send(&self, message: EmailMessage) -> Result<(), MailError> {
+        let message = build_message(&self.from, message)?;
+        self.transport.send(message)
+            .await
+            .map_err(|e| MailError::Transport(e.to_string()))?;
+        Ok(())
+    }
+}
+
+// Сохранение писем в каталог (локальная разработка)
+pub struct FileMailSender {
+    dir: PathBuf,
+    from: Mailbox,
+}
+
+impl FileMailSender {
+    pub fn new(dir: PathBuf, from: &str) -> Result<Self, MailError> {
+        Ok(Self { dir, from: parse_mailbox(from)? })
+    }
+}
+
+#[async_trait]
+impl MailSender for FileMailSender {
+    async fn send(&self, message: EmailMessage) -> Result<(), MailError> {
+        let message = build_message(&self.from, message)?;
+        let path = self.dir.join(format!("{}-{}.eml", Utc::now().format("%Y%m%d%H%M%S"), Uuid::new_v4()));
+
+        tokio::fs::create_dir_all(&self.dir).await.map_err(MailError::Io)?;
+        tokio::fs::write(&path, message.formatted()).await.map_err(MailError::Io)?;
+        Ok(())
+    }
+}
+
+// Хранение писем в памяти (тесты)
+#[derive(Default)]
+pub struct InMemoryMailSender {
+    outbox: Mutex<Vec<EmailMessage>>,
+}
+
+impl InMemoryMailSender {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Отправленные письма в порядке отправки
+    pub fn messages(&self) -> Vec<EmailMessage> {
+        self.outbox.lock().unwrap().clone()
+    }
+
+    // Последнее письмо на адрес
+    pub fn last_message_to(&self, to: &str) -> Option<EmailMessage> {
+        self.outbox.lock().unwrap().iter().rev().find(|m| m.to == to).cloned()
+    }
+}
+
+#[async_trait]
+impl MailSender for InMemoryMailSender {
+    async fn send(&self, message: EmailMessage) -> Result<(), MailError> {
+        parse_mailbox(&message.to)?;
+        self.outbox.lock().unwrap().push(message);
+        Ok(())
+    }
+}
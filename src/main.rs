@@ -9,6 +9,36 @@ pub mod auth_handlers;
 pub mod oauth_handlers;
 pub mod middleware;
 pub mod protected_handlers;
+pub mod config;
+pub mod discovery_handlers;
+pub mod pkce;
+pub mod scope_utils;
+pub mod consent_service;
+pub mod account_handlers;
+pub mod scope_service;
+pub mod admin_handlers;
+pub mod redirect_uri;
+pub mod secrets;
+pub mod mail;
+pub mod email_verification;
+pub mod session_service;
+pub mod password_reset;
+pub mod mfa_service;
+pub mod pending_login;
+pub mod mfa_handlers;
+pub mod webauthn;
+pub mod passkey_service;
+pub mod passkey_handlers;
+pub mod login_throttle;
+pub mod password_policy;
+pub mod password_hasher;
+pub mod hashing_pool;
+pub mod profile_handlers;
+pub mod email_change;
+pub mod email_change_handlers;
+pub mod account_deletion;
+pub mod data_export;
+pub mod privacy_handlers;
 
 use actix_web::{App, HttpServer, web, middleware as actix_middleware};
 use actix_session::{SessionMiddleware, storage::CookieSessionStore};
@@ -17,9 +47,27 @@ use dotenv::dotenv;
 use std::env;
 use services::UserService;
 use token_service::TokenService;
-use client_service::ClientService;
+use client_service::{ClientService, ClientSecretKeys};
 use oauth_service::OAuthService;
-use middleware::AuthMiddleware;
+use consent_service::ConsentService;
+use scope_service::ScopeService;
+use email_verification::EmailVerificationService;
+use password_reset::PasswordResetService;
+use email_change::EmailChangeService;
+use account_deletion::AccountDeletionService;
+use data_export::DataExportService;
+use session_service::SessionService;
+use mfa_service::MfaService;
+use secrets::SecretCipher;
+use pending_login::PendingLoginService;
+use passkey_service::PasskeyService;
+use login_throttle::LoginThrottle;
+use password_policy::PasswordPolicy;
+use password_hasher::PasswordHasher;
+use hashing_pool::HashingPool;
+use std::sync::Arc;
+use middleware::{AdminGuard, AuthMiddleware, ScopeValidator, SessionGuard};
+use config::AppConfig;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -35,14 +83,38 @@ async fn main() -> std::io::Result<()> {
 
     let host = env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
     let port = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
+    let app_config = AppConfig::from_env(&host, &port);
     let jwt_secret = env::var("JWT_SECRET").unwrap_or_else(|_| {
         println!("WARNING: Using default JWT_SECRET. Set JWT_SECRET in .env for production!");
         "your-secret-key-change-this-in-production".to_string()
     });
+    // Отдельный ключ HMAC секретов клиентов; прежние ключи (через запятую) принимаются
+    // при проверке, и хеш переводится на текущий ключ при успешной аутентификации
+    let client_secret_key = env::var("CLIENT_SECRET_KEY")
+        .ok()
+        .filter(|key| !key.trim().is_empty())
+        .ok_or_else(|| std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "CLIENT_SECRET_KEY не задан (прежний ключ, например JWT_SECRET, укажите в CLIENT_SECRET_PREVIOUS_KEYS)",
+        ))?;
+    let previous_client_secret_keys = env::var("CLIENT_SECRET_PREVIOUS_KEYS").unwrap_or_default();
+    let client_secret_keys = ClientSecretKeys::new(&client_secret_key).with_previous(
+        previous_client_secret_keys.split(',').map(str::trim).filter(|key| !key.is_empty()),
+    );
     let session_key = env::var("SESSION_KEY").unwrap_or_else(|_| {
         println!("WARNING: Using default SESSION_KEY. Set SESSION_KEY in .env for production!");
         "your-session-key-must-be-at-least-64-bytes-long-change-this-in-prod".to_string()
     });
+    // Отдельный ключ шифрования секретов TOTP; без него сервер не запускается
+    let totp_cipher = env::var("TOTP_ENCRYPTION_KEY")
+        .map_err(|_| "TOTP_ENCRYPTION_KEY не задан (32 байта в base64: openssl rand -base64 32)".to_string())
+        .and_then(|key| {
+            SecretCipher::from_base64(&key).map_err(|e| format!("Недопустимый TOTP_ENCRYPTION_KEY: {}", e))
+        })
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let mail_sender = mail::build_mail_sender(&app_config.mail).map_err(|e| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Не удалось настроить отправку писем: {}", e))
+    })?;
 
     println!("Подключение к базе данных...");
 
@@ -60,22 +132,68 @@ async fn main() -> std::io::Result<()> {
         .expect("Не удалось применить миграции");
 
     // Создание сервисов
-    let user_service = web::Data::new(UserService::new(pool.clone()));
-    let token_service = TokenService::new(pool.clone(), jwt_secret);
+    let password_hasher = PasswordHasher::new(&app_config.password_hashing)
+        .expect("Недопустимые параметры хеширования паролей");
+    let hashing_pool = Arc::new(HashingPool::new(&app_config.hashing_pool));
+    let user_service = web::Data::new(UserService::new(pool.clone(), password_hasher, hashing_pool.clone()));
+    let token_service = TokenService::new(pool.clone(), jwt_secret.clone(), &app_config);
     let token_service_data = web::Data::new(token_service);
-    let client_service = web::Data::new(ClientService::new(pool.clone()));
+    let client_service = web::Data::new(ClientService::new(
+        pool.clone(),
+        app_config.redirect_uri_policy,
+        hashing_pool.clone(),
+        client_secret_keys,
+        app_config.client_auth_cache,
+    ));
+    let consent_service = web::Data::new(ConsentService::new(pool.clone()));
+    let scope_service = web::Data::new(ScopeService::new(pool.clone()));
     let oauth_service = web::Data::new(OAuthService::new(
         pool.clone(),
-        TokenService::new(pool.clone(), env::var("JWT_SECRET").unwrap_or_else(|_| {
-            "your-secret-key-change-this-in-production".to_string()
-        })),
+        TokenService::new(pool.clone(), jwt_secret.clone(), &app_config),
+        app_config.pkce_policy,
+        app_config.email_verification.required,
+    ));
+    // Ключ подписи ссылок подтверждения email: отдельный или производный от JWT_SECRET
+    let email_verification_key = env::var("EMAIL_VERIFICATION_KEY")
+        .ok()
+        .filter(|key| !key.trim().is_empty())
+        .unwrap_or_else(|| secrets::derive_key(&jwt_secret, "email-verification"));
+    let verification_service = web::Data::new(EmailVerificationService::new(
+        pool.clone(),
+        mail_sender.clone(),
+        &email_verification_key,
+        &app_config,
     ));
+    let reset_service = web::Data::new(PasswordResetService::new(pool.clone(), mail_sender.clone(), &app_config));
+    let email_change_service = web::Data::new(EmailChangeService::new(pool.clone(), mail_sender.clone(), &app_config));
+    let deletion_service = web::Data::new(AccountDeletionService::new(pool.clone(), mail_sender, &app_config));
+    let data_export_service = web::Data::new(DataExportService::new(pool.clone()));
+    let session_service = web::Data::new(SessionService::new(pool.clone()));
+    let mfa_service = web::Data::new(MfaService::new(pool.clone(), &app_config.totp_issuer, totp_cipher));
+    let encrypted = mfa_service
+        .encrypt_legacy_secrets()
+        .await
+        .expect("Не удалось зашифровать секреты TOTP");
+    if encrypted > 0 {
+        println!("Зашифровано секретов TOTP: {}", encrypted);
+    }
+    let pending_login_service = web::Data::new(PendingLoginService::new(pool.clone()));
+    let passkey_service = web::Data::new(PasskeyService::new(pool.clone(), &app_config.webauthn));
+    let login_throttle = web::Data::new(LoginThrottle::new(pool.clone(), app_config.login_throttle));
+    let password_policy = web::Data::new(PasswordPolicy::new(&app_config.password_policy));
+    let hashing_pool = web::Data::from(hashing_pool);
+
+    let config_data = web::Data::new(app_config);
+
+    // Окончательное удаление аккаунтов по истечении срока отмены
+    account_deletion::spawn_purge_task(deletion_service.clone().into_inner());
 
     // Создание session key
     let secret_key = Key::from(session_key.as_bytes());
 
     let bind_address = format!("{}:{}", host, port);
     println!("OAuth 2.0 сервер запущен на http://{}", bind_address);
+    println!("Issuer: {}", config_data.issuer);
     println!("\n=== API Endpoints ===");
     println!("Health Check:");
     println!("  GET  http://{}/api/health", bind_address);
@@ -84,16 +202,65 @@ async fn main() -> std::io::Result<()> {
     println!("\nAuthentication:");
     println!("  GET  http://{}/auth/login", bind_address);
     println!("  POST http://{}/auth/login", bind_address);
+    println!("  POST http://{}/auth/login/mfa", bind_address);
+    println!("  POST http://{}/auth/login/passkey/options", bind_address);
+    println!("  POST http://{}/auth/login/passkey", bind_address);
     println!("  POST http://{}/auth/logout", bind_address);
     println!("  GET  http://{}/auth/me", bind_address);
+    println!("  GET  http://{}/auth/verify-email", bind_address);
+    println!("  POST http://{}/auth/verify-email/resend", bind_address);
+    println!("  GET|POST http://{}/auth/password/forgot", bind_address);
+    println!("  GET|POST http://{}/auth/password/reset", bind_address);
+    println!("  POST http://{}/auth/password/change", bind_address);
+    println!("  POST http://{}/auth/email/change", bind_address);
+    println!("  GET|POST http://{}/auth/email/change/confirm", bind_address);
+    println!("  GET|POST http://{}/auth/email/change/cancel", bind_address);
+    println!("\nTwo-Factor Authentication:");
+    println!("  GET  http://{}/auth/mfa/setup", bind_address);
+    println!("  GET  http://{}/auth/mfa/status", bind_address);
+    println!("  POST http://{}/auth/mfa/totp/enroll", bind_address);
+    println!("  POST http://{}/auth/mfa/totp/activate", bind_address);
+    println!("  DELETE http://{}/auth/mfa/totp", bind_address);
+    println!("  POST http://{}/auth/mfa/recovery-codes", bind_address);
+    println!("\nPasskeys:");
+    println!("  GET  http://{}/auth/passkeys/setup", bind_address);
+    println!("  GET  http://{}/auth/passkeys", bind_address);
+    println!("  POST http://{}/auth/passkeys/register/options", bind_address);
+    println!("  POST http://{}/auth/passkeys/register", bind_address);
+    println!("  DELETE http://{}/auth/passkeys/{{id}}", bind_address);
     println!("\nOAuth 2.0:");
     println!("  GET  http://{}/oauth/authorize", bind_address);
     println!("  POST http://{}/oauth/authorize", bind_address);
     println!("  POST http://{}/oauth/token", bind_address);
     println!("  POST http://{}/oauth/revoke", bind_address);
+    println!("  POST http://{}/oauth/introspect", bind_address);
     println!("  POST http://{}/oauth/clients", bind_address);
+    println!("  GET  http://{}/.well-known/oauth-authorization-server", bind_address);
+    println!("\nAccount:");
+    println!("  GET  http://{}/account/apps", bind_address);
+    println!("  GET  http://{}/account/api/apps", bind_address);
+    println!("  DELETE http://{}/account/api/apps/{{client_id}}", bind_address);
+    println!("  GET  http://{}/account/profile", bind_address);
+    println!("  GET|PATCH http://{}/account/api/profile", bind_address);
+    println!("  GET  http://{}/account/privacy", bind_address);
+    println!("  GET  http://{}/account/api/export", bind_address);
+    println!("  GET|POST|DELETE http://{}/account/api/deletion", bind_address);
+    println!("\nAdmin (scope admin, пользователь с ролью администратора):");
+    println!("  GET|POST http://{}/api/admin/scopes", bind_address);
+    println!("  GET|PATCH|DELETE http://{}/api/admin/scopes/{{scope_name}}", bind_address);
+    println!("  PUT|DELETE http://{}/api/admin/scopes/{{scope_name}}/translations/{{locale}}", bind_address);
+    println!("  PATCH http://{}/api/admin/clients/{{client_id}}", bind_address);
+    println!("  GET  http://{}/api/admin/users", bind_address);
+    println!("  GET  http://{}/api/admin/users/{{user_id}}", bind_address);
+    println!("  POST http://{}/api/admin/users/{{user_id}}/suspend", bind_address);
+    println!("  POST http://{}/api/admin/users/{{user_id}}/reactivate", bind_address);
+    println!("  POST http://{}/api/admin/users/{{user_id}}/force-password-reset", bind_address);
+    println!("  POST http://{}/api/admin/users/{{user_id}}/force-logout", bind_address);
+    println!("  POST http://{}/api/admin/users/{{user_id}}/unlock", bind_address);
+    println!("  POST http://{}/api/admin/tokens/{{jti}}/revoke", bind_address);
+    println!("  GET  http://{}/api/admin/metrics/hashing", bind_address);
     println!("\nProtected Resources:");
-    println!("  GET  http://{}/api/protected/profile", bind_address);
+    println!("  GET|PATCH http://{}/api/protected/profile", bind_address);
     println!("  GET  http://{}/api/protected/data", bind_address);
     println!("\n===================\n");
 
@@ -101,9 +268,8 @@ async fn main() -> std::io::Result<()> {
     HttpServer::new(move || {
         let token_service_for_middleware = TokenService::new(
             pool.clone(),
-            env::var("JWT_SECRET").unwrap_or_else(|_| {
-                "your-secret-key-change-this-in-production".to_string()
-            })
+            jwt_secret.clone(),
+            &config_data,
         );
 
         App::new()
@@ -111,7 +277,24 @@ async fn main() -> std::io::Result<()> {
             .app_data(token_service_data.clone())
             .app_data(client_service.clone())
             .app_data(oauth_service.clone())
+            .app_data(consent_service.clone())
+            .app_data(scope_service.clone())
+            .app_data(verification_service.clone())
+            .app_data(reset_service.clone())
+            .app_data(email_change_service.clone())
+            .app_data(deletion_service.clone())
+            .app_data(data_export_service.clone())
+            .app_data(session_service.clone())
+            .app_data(mfa_service.clone())
+            .app_data(pending_login_service.clone())
+            .app_data(passkey_service.clone())
+            .app_data(login_throttle.clone())
+            .app_data(password_policy.clone())
+            .app_data(hashing_pool.clone())
+            .app_data(config_data.clone())
             .wrap(actix_middleware::Logger::default())
+            // Проверка отзыва сессии выполняется внутри SessionMiddleware
+            .wrap(SessionGuard::new(session_service.get_ref().clone()))
             .wrap(
                 SessionMiddleware::builder(
                     CookieSessionStore::default(),
@@ -120,14 +303,28 @@ async fn main() -> std::io::Result<()> {
                 .cookie_secure(false) // Set to true in production with HTTPS
                 .build()
             )
-            .configure(handlers::configure_routes)
-            .configure(auth_handlers::configure_auth_routes)
-            .configure(oauth_handlers::configure_oauth_routes)
+            // Вложенные scopes /api/* регистрируются до /api, иначе он перехватывает их запросы
             .service(
                 web::scope("/api/protected")
                     .wrap(AuthMiddleware::new(token_service_for_middleware))
                     .configure(protected_handlers::configure_protected_routes)
             )
+            .service(
+                web::scope("/api/admin")
+                    .wrap(AdminGuard)
+                    .wrap(ScopeValidator::new(vec!["admin".to_string()]))
+                    .wrap(AuthMiddleware::new(TokenService::new(
+                        pool.clone(),
+                        jwt_secret.clone(),
+                        &config_data,
+                    )))
+                    .configure(admin_handlers::configure_admin_routes)
+            )
+            .configure(handlers::configure_routes)
+            .configure(auth_handlers::configure_auth_routes)
+            .configure(oauth_handlers::configure_oauth_routes)
+            .configure(discovery_handlers::configure_discovery_routes)
+            .configure(account_handlers::configure_account_routes)
     })
     .bind(&bind_address)?
     .run()
@@ -0,0 +1,290 @@
+use actix_web::{web, HttpResponse, Responder};
+use actix_session::Session;
+use validator::Validate;
+use crate::account_handlers::session_user_id;
+use crate::mfa_service::{MfaError, MfaService};
+use crate::models::{ErrorResponse, MfaCodeRequest};
+use crate::services::UserService;
+
+// Преобразование ошибки MfaService в HTTP ответ
+fn mfa_error_response(error: MfaError) -> HttpResponse {
+    match error {
+        MfaError::AlreadyEnabled => HttpResponse::Conflict().json(ErrorResponse {
+            error: error.to_string(),
+        }),
+        MfaError::NotEnrolled => HttpResponse::NotFound().json(ErrorResponse {
+            error: error.to_string(),
+        }),
+        MfaError::InvalidCode => HttpResponse::BadRequest().json(ErrorResponse {
+            error: error.to_string(),
+        }),
+        MfaError::DatabaseError(_) | MfaError::Totp(_) => {
+            eprintln!("MFA error: {}", error);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            })
+        }
+    }
+}
+
+fn validate_code(request: &MfaCodeRequest) -> Result<(), HttpResponse> {
+    request.validate().map_err(|errors| {
+        HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Validation error: {}", errors),
+        })
+    })
+}
+
+// GET /auth/mfa/setup - страница подключения двухфакторной аутентификации
+pub async fn mfa_setup_page(session: Session) -> impl Responder {
+    if session_user_id(&session).is_err() {
+        return HttpResponse::Found()
+            .append_header(("Location", "/auth/login?return_to=%2Fauth%2Fmfa%2Fsetup"))
+            .finish();
+    }
+
+    let html = r#"
+<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="UTF-8">
+    <title>Двухфакторная аутентификация</title>
+    <style>
+        body { font-family: Arial, sans-serif; max-width: 500px; margin: 50px auto; padding: 20px; }
+        h1 { text-align: center; }
+        form { display: flex; flex-direction: column; gap: 15px; }
+        input { padding: 10px; border: 1px solid #ddd; border-radius: 4px; }
+        button { padding: 10px; background-color: #007bff; color: white; border: none; border-radius: 4px; cursor: pointer; }
+        .qr { text-align: center; margin: 20px 0; }
+        .secret { font-family: monospace; word-break: break-all; background: #f5f5f5; padding: 10px; border-radius: 4px; }
+        .codes { font-family: monospace; columns: 2; background: #f5f5f5; padding: 10px; border-radius: 4px; }
+        .hidden { display: none; }
+        .error { color: red; text-align: center; }
+    </style>
+</head>
+<body>
+    <h1>Двухфакторная аутентификация</h1>
+    <div id="status"></div>
+
+    <div id="enroll" class="hidden">
+        <button id="startButton">Подключить приложение-аутентификатор</button>
+    </div>
+
+    <div id="activate" class="hidden">
+        <p>Отсканируйте QR-код в приложении-аутентификаторе или введите ключ вручную:</p>
+        <div class="qr" id="qr"></div>
+        <p class="secret" id="secret"></p>
+        <form id="activateForm">
+            <input type="text" name="code" placeholder="Код из приложения" autocomplete="one-time-code" required>
+            <button type="submit">Подтвердить</button>
+        </form>
+    </div>
+
+    <div id="recovery" class="hidden">
+        <p>Сохраните коды восстановления. Каждый код можно использовать один раз, если приложение недоступно. Повторно они показаны не будут.</p>
+        <div class="codes" id="codes"></div>
+    </div>
+
+    <div class="error" id="error"></div>
+
+    <script>
+        function show(id) {
+            for (const section of ['enroll', 'activate', 'recovery']) {
+                document.getElementById(section).classList.toggle('hidden', section !== id);
+            }
+        }
+
+        async function loadStatus() {
+            const response = await fetch('/auth/mfa/status');
+            if (!response.ok) {
+                document.getElementById('error').textContent = 'Не удалось загрузить состояние';
+                return;
+            }
+            const status = await response.json();
+            if (status.totp_enabled) {
+                document.getElementById('status').textContent =
+                    'Двухфакторная аутентификация включена. Осталось кодов восстановления: ' + status.recovery_codes_remaining;
+                show(null);
+            } else {
+                document.getElementById('status').textContent = 'Двухфакторная аутентификация выключена.';
+                show('enroll');
+            }
+        }
+
+        document.getElementById('startButton').addEventListener('click', async () => {
+            const response = await fetch('/auth/mfa/totp/enroll', { method: 'POST' });
+            const result = await response.json();
+            if (!response.ok) {
+                document.getElementById('error').textContent = result.error || 'Ошибка';
+                return;
+            }
+            document.getElementById('qr').innerHTML = result.qr_svg;
+            document.getElementById('secret').textContent = result.secret;
+            show('activate');
+        });
+
+        document.getElementById('activateForm').addEventListener('submit', async (e) => {
+            e.preventDefault();
+            const formData = new FormData(e.target);
+            const response = await fetch('/auth/mfa/totp/activate', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ code: formData.get('code') })
+            });
+            const result = await response.json();
+            if (!response.ok) {
+                document.getElementById('error').textContent = result.error || 'Неверный код';
+                return;
+            }
+            document.getElementById('error').textContent = '';
+            document.getElementById('status').textContent = 'Двухфакторная аутентификация включена.';
+            const codes = document.getElementById('codes');
+            codes.innerHTML = '';
+            for (const code of result.recovery_codes) {
+                const item = document.createElement('div');
+                item.textContent = code;
+                codes.appendChild(item);
+            }
+            show('recovery');
+        });
+
+        loadStatus();
+    </script>
+</body>
+</html>
+    "#;
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html)
+}
+
+// GET /auth/mfa/status - состояние второго фактора текущего пользователя
+pub async fn mfa_status(
+    mfa_service: web::Data<MfaService>,
+    session: Session,
+) -> impl Responder {
+    let user_id = match session_user_id(&session) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    match mfa_service.status(user_id).await {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            })
+        }
+    }
+}
+
+// POST /auth/mfa/totp/enroll - новый секрет TOTP (otpauth:// URI и QR-код)
+pub async fn enroll_totp(
+    user_service: web::Data<UserService>,
+    mfa_service: web::Data<MfaService>,
+    session: Session,
+) -> impl Responder {
+    let user_id = match session_user_id(&session) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let user = match user_service.get_user_by_id(user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "User not found".to_string(),
+            })
+        }
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            });
+        }
+    };
+
+    match mfa_service.start_enrollment(&user).await {
+        Ok(enrollment) => HttpResponse::Ok().json(enrollment),
+        Err(e) => mfa_error_response(e),
+    }
+}
+
+// POST /auth/mfa/totp/activate - подтверждение подключения первым кодом
+pub async fn activate_totp(
+    mfa_service: web::Data<MfaService>,
+    session: Session,
+    request: web::Json<MfaCodeRequest>,
+) -> impl Responder {
+    let user_id = match session_user_id(&session) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+    if let Err(response) = validate_code(&request) {
+        return response;
+    }
+
+    match mfa_service.activate(user_id, &request.code).await {
+        Ok(recovery_codes) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "Two-factor authentication enabled",
+            "recovery_codes": recovery_codes
+        })),
+        Err(e) => mfa_error_response(e),
+    }
+}
+
+// DELETE /auth/mfa/totp - отключение второго фактора
+pub async fn disable_totp(
+    mfa_service: web::Data<MfaService>,
+    session: Session,
+    request: web::Json<MfaCodeRequest>,
+) -> impl Responder {
+    let user_id = match session_user_id(&session) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+    if let Err(response) = validate_code(&request) {
+        return response;
+    }
+
+    match mfa_service.disable(user_id, &request.code).await {
+        Ok(()) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "Two-factor authentication disabled"
+        })),
+        Err(e) => mfa_error_response(e),
+    }
+}
+
+// POST /auth/mfa/recovery-codes - новый набор кодов восстановления
+pub async fn regenerate_recovery_codes(
+    mfa_service: web::Data<MfaService>,
+    session: Session,
+    request: web::Json<MfaCodeRequest>,
+) -> impl Responder {
+    let user_id = match session_user_id(&session) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+    if let Err(response) = validate_code(&request) {
+        return response;
+    }
+
+    match mfa_service.regenerate_recovery_codes(user_id, &request.code).await {
+        Ok(recovery_codes) => HttpResponse::Ok().json(serde_json::json!({
+            "recovery_codes": recovery_codes
+        })),
+        Err(e) => mfa_error_response(e),
+    }
+}
+
+// Маршруты двухфакторной аутентификации (подключаются внутри scope /auth)
+pub fn configure_mfa_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/mfa/setup", web::get().to(mfa_setup_page))
+        .route("/mfa/status", web::get().to(mfa_status))
+        .route("/mfa/totp/enroll", web::post().to(enroll_totp))
+        .route("/mfa/totp/activate", web::post().to(activate_totp))
+        .route("/mfa/totp", web::delete().to(disable_totp))
+        .route("/mfa/recovery-codes", web::post().to(regenerate_recovery_codes));
+}
@@ -0,0 +1,343 @@
+// Второй фактор: TOTP (RFC 6238) и одноразовые коды восстановления
+use chrono::{DateTime, Utc};
+use qrcode::render::svg;
+use qrcode::QrCode;
+use serde::Serialize;
+use sqlx::{FromRow, Pool, Postgres};
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+use crate::models::User;
+use crate::secrets::{constant_time_eq, hash_secret, random_secret, SecretCipher};
+
+// Параметры TOTP, совместимые с распространенными приложениями-аутентификаторами
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+// Допустимое расхождение часов: один шаг до и после текущего
+const TOTP_SKEW: u64 = 1;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+#[derive(Debug)]
+pub enum MfaError {
+    DatabaseError(sqlx::Error),
+    AlreadyEnabled,
+    NotEnrolled,
+    InvalidCode,
+    Totp(String),
+}
+
+impl std::fmt::Display for MfaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MfaError::DatabaseError(e) => write!(f, "Database error: {}", e),
+            MfaError::AlreadyEnabled => write!(f, "Two-factor authentication is already enabled"),
+            MfaError::NotEnrolled => write!(f, "Two-factor authentication is not set up"),
+            MfaError::InvalidCode => write!(f, "Invalid verification code"),
+            MfaError::Totp(e) => write!(f, "TOTP error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MfaError {}
+
+impl From<sqlx::Error> for MfaError {
+    fn from(e: sqlx::Error) -> Self {
+        MfaError::DatabaseError(e)
+    }
+}
+
+// Данные для добавления аккаунта в приложение-аутентификатор
+#[derive(Debug, Serialize)]
+pub struct TotpEnrollment {
+    // Секрет в base32 для ручного ввода
+    pub secret: String,
+    pub otpauth_uri: String,
+    pub qr_svg: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MfaStatus {
+    pub totp_enabled: bool,
+    pub recovery_codes_remaining: i64,
+}
+
+#[derive(Debug, FromRow)]
+struct UserTotp {
+    secret: String,
+    enabled_at: Option<DateTime<Utc>>,
+    last_used_step: Option<i64>,
+}
+
+pub struct MfaService {
+    pool: Pool<Postgres>,
+    issuer: String,
+    // Секреты TOTP хранятся зашифрованными отдельным ключом (TOTP_ENCRYPTION_KEY)
+    cipher: SecretCipher,
+}
+
+impl MfaService {
+    pub fn new(pool: Pool<Postgres>, issuer: &str, cipher: SecretCipher) -> Self {
+        Self { pool, issuer: issuer.to_string(), cipher }
+    }
+
+    // Запись TOTP с расшифрованным секретом
+    async fn get_totp(&self, user_id: Uuid) -> Result<Option<UserTotp>, MfaError> {
+        let totp = sqlx::query_as::<_, UserTotp>("SELECT secret, enabled_at, last_used_step FROM user_totp WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        let Some(mut totp) = totp else {
+            return Ok(None);
+        };
+        if !SecretCipher::is_encrypted(&totp.secret) {
+            // Секрет сохранен до включения шифрования
+            self.encrypt_stored_secret(user_id, &totp.secret).await?;
+            return Ok(Some(totp));
+        }
+        totp.secret = self.cipher
+            .decrypt(&totp.secret, user_id.as_bytes())
+            .ok_or_else(|| MfaError::Totp("Cannot decrypt TOTP secret".to_string()))?;
+
+        Ok(Some(totp))
+    }
+
+    async fn encrypt_stored_secret(&self, user_id: Uuid, plaintext: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE user_totp SET secret = $1 WHERE user_id = $2 AND secret = $3")
+            .bind(self.cipher.encrypt(plaintext, user_id.as_bytes()))
+            .bind(user_id)
+            .bind(plaintext)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Шифрование секретов, сохраненных открытым текстом до включения шифрования.
+    // Выполняется при запуске; возвращает число зашифрованных записей
+    pub async fn encrypt_legacy_secrets(&self) -> Result<u64, sqlx::Error> {
+        let legacy = sqlx::query_as::<_, (Uuid, String)>(
+            "SELECT user_id, secret FROM user_totp WHERE secret NOT LIKE 'v1:%'"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        for (user_id, secret) in &legacy {
+            self.encrypt_stored_secret(*user_id, secret).await?;
+        }
+
+        Ok(legacy.len() as u64)
+    }
+
+    // Включен ли второй фактор у пользователя
+    pub async fn is_enabled(&self, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM user_totp WHERE user_id = $1 AND enabled_at IS NOT NULL)"
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn status(&self, user_id: Uuid) -> Result<MfaStatus, sqlx::Error> {
+        let totp_enabled = self.is_enabled(user_id).await?;
+        let recovery_codes_remaining = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM mfa_recovery_codes WHERE user_id = $1 AND used_at IS NULL"
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(MfaStatus { totp_enabled, recovery_codes_remaining })
+    }
+
+    // Начало подключения TOTP: новый секрет заменяет незавершенную попытку
+    pub async fn start_enrollment(&self, user: &User) -> Result<TotpEnrollment, MfaError> {
+        let secret = Secret::generate_secret()
+            .to_bytes()
+            .map_err(|e| MfaError::Totp(e.to_string()))?;
+        let encoded = Secret::Raw(secret.clone()).to_encoded().to_string();
+
+        let totp = TOTP::new(
+            Algorithm::SHA1,
+            TOTP_DIGITS,
+            TOTP_SKEW as u8,
+            TOTP_STEP,
+            secret,
+            Some(self.issuer.clone()),
+            user.email.clone(),
+        )
+        .map_err(|e| MfaError::Totp(e.to_string()))?;
+        let otpauth_uri = totp.get_url();
+
+        let stored = sqlx::query(
+            r#"
+            INSERT INTO user_totp (user_id, secret, created_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id) DO UPDATE
+            SET secret = EXCLUDED.secret, last_used_step = NULL, created_at = EXCLUDED.created_at
+            WHERE user_totp.enabled_at IS NULL
+            "#
+        )
+        .bind(user.id)
+        .bind(self.cipher.encrypt(&encoded, user.id.as_bytes()))
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+
+        if stored.rows_affected() == 0 {
+            return Err(MfaError::AlreadyEnabled);
+        }
+
+        let qr_svg = QrCode::new(otpauth_uri.as_bytes())
+            .map_err(|e| MfaError::Totp(e.to_string()))?
+            .render::<svg::Color>()
+            .min_dimensions(200, 200)
+            .build();
+
+        Ok(TotpEnrollment { secret: encoded, otpauth_uri, qr_svg })
+    }
+
+    // Подтверждение подключения первым кодом из приложения; возвращает коды восстановления
+    pub async fn activate(&self, user_id: Uuid, code: &str) -> Result<Vec<String>, MfaError> {
+        let totp = self.get_totp(user_id).await?.ok_or(MfaError::NotEnrolled)?;
+        if totp.enabled_at.is_some() {
+            return Err(MfaError::AlreadyEnabled);
+        }
+
+        let step = verify_totp_code(&totp.secret, code, Utc::now().timestamp() as u64, None)
+            .ok_or(MfaError::InvalidCode)?;
+
+        sqlx::query("UPDATE user_totp SET enabled_at = $1, last_used_step = $2 WHERE user_id = $3")
+            .bind(Utc::now())
+            .bind(step)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        self.replace_recovery_codes(user_id).await
+    }
+
+    // Проверка второго фактора: код TOTP или неиспользованный код восстановления
+    pub async fn verify(&self, user_id: Uuid, code: &str) -> Result<(), MfaError> {
+        let totp = self.get_totp(user_id).await?
+            .filter(|t| t.enabled_at.is_some())
+            .ok_or(MfaError::NotEnrolled)?;
+
+        let now = Utc::now().timestamp() as u64;
+        if let Some(step) = verify_totp_code(&totp.secret, code, now, totp.last_used_step) {
+            // Условие на шаг не дает использовать один код дважды при параллельных запросах
+            let updated = sqlx::query(
+                r#"
+                UPDATE user_totp SET last_used_step = $1
+                WHERE user_id = $2 AND (last_used_step IS NULL OR last_used_step < $1)
+                "#
+            )
+            .bind(step)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+            return if updated.rows_affected() > 0 { Ok(()) } else { Err(MfaError::InvalidCode) };
+        }
+
+        let used = sqlx::query(
+            r#"
+            UPDATE mfa_recovery_codes SET used_at = $1
+            WHERE user_id = $2 AND code_hash = $3 AND used_at IS NULL
+            "#
+        )
+        .bind(Utc::now())
+        .bind(user_id)
+        .bind(hash_secret(&normalize_recovery_code(code)))
+        .execute(&self.pool)
+        .await?;
+
+        if used.rows_affected() > 0 {
+            Ok(())
+        } else {
+            Err(MfaError::InvalidCode)
+        }
+    }
+
+    // Новый набор кодов восстановления, прежние перестают действовать
+    pub async fn regenerate_recovery_codes(&self, user_id: Uuid, code: &str) -> Result<Vec<String>, MfaError> {
+        self.verify(user_id, code).await?;
+        self.replace_recovery_codes(user_id).await
+    }
+
+    // Отключение второго фактора (требуется действующий код)
+    pub async fn disable(&self, user_id: Uuid, code: &str) -> Result<(), MfaError> {
+        self.verify(user_id, code).await?;
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM mfa_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM user_totp WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(&self, user_id: Uuid) -> Result<Vec<String>, MfaError> {
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| generate_recovery_code()).collect();
+        let now = Utc::now();
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM mfa_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        for code in &codes {
+            sqlx::query(
+                "INSERT INTO mfa_recovery_codes (id, user_id, code_hash, created_at) VALUES ($1, $2, $3, $4)"
+            )
+            .bind(Uuid::new_v4())
+            .bind(user_id)
+            .bind(hash_secret(&normalize_recovery_code(code)))
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(codes)
+    }
+}
+
+// Проверка кода TOTP в окне ±TOTP_SKEW шагов. Возвращает номер шага совпавшего кода;
+// шаги не новее last_used_step отклоняются (защита от повторного использования)
+pub fn verify_totp_code(secret: &str, code: &str, now: u64, last_used_step: Option<i64>) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let secret = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+    let totp = TOTP::new_unchecked(Algorithm::SHA1, TOTP_DIGITS, 0, TOTP_STEP, secret, None, String::new());
+    let current = now / TOTP_STEP;
+
+    (current.saturating_sub(TOTP_SKEW)..=current + TOTP_SKEW)
+        .filter(|step| last_used_step.is_none_or(|last| *step as i64 > last))
+        .find(|step| constant_time_eq(totp.generate(step * TOTP_STEP).as_bytes(), code.as_bytes()))
+        .map(|step| step as i64)
+}
+
+// Код восстановления вида xxxxx-xxxxx
+fn generate_recovery_code() -> String {
+    let code = random_secret(10).to_ascii_lowercase();
+    format!("{}-{}", &code[..5], &code[5..])
+}
+
+// Регистр и разделители при вводе кода восстановления не важны
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
@@ -2,11 +2,16 @@ use actix_web::{
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
     Error, HttpMessage, HttpResponse,
     body::MessageBody,
+    web,
 };
 use futures::future::LocalBoxFuture;
 use std::future::{ready, Ready};
 use std::rc::Rc;
+use actix_session::SessionExt;
+use uuid::Uuid;
 use crate::token_service::TokenService;
+use crate::services::UserService;
+use crate::session_service::SessionService;
 use crate::models::TokenClaims;
 
 // Middleware для проверки Bearer токенов
@@ -77,34 +82,20 @@ where
                 }
             };
 
-            // Верификация JWT
-            let claims = match token_service.verify_jwt(&token) {
-                Ok(claims) => claims,
-                Err(e) => {
-                    eprintln!("Token verification failed: {}", e);
-                    let (http_req, _) = req.into_parts();
-                    let response = HttpResponse::Unauthorized()
-                        .json(serde_json::json!({
-                            "error": "Invalid or expired token"
-                        }));
-                    return Ok(ServiceResponse::new(http_req, response).map_into_boxed_body());
-                }
-            };
-
-            // Проверка токена в БД (не отозван ли)
-            match token_service.validate_token(&token).await {
-                Ok(Some(_)) => {
+            // Верификация токена (JWT или opaque) и проверка в БД
+            match token_service.resolve_access_token(&token).await {
+                Ok(Some(claims)) => {
                     // Токен валиден, добавляем claims в extensions
                     req.extensions_mut().insert(claims);
                     let res = service.call(req).await?;
                     Ok(res.map_into_boxed_body())
                 }
                 Ok(None) => {
-                    // Токен не найден или отозван
+                    // Токен невалиден, истек или отозван
                     let (http_req, _) = req.into_parts();
                     let response = HttpResponse::Unauthorized()
                         .json(serde_json::json!({
-                            "error": "Token has been revoked"
+                            "error": "Invalid, expired or revoked token"
                         }));
                     Ok(ServiceResponse::new(http_req, response).map_into_boxed_body())
                 }
@@ -230,3 +221,181 @@ where
         })
     }
 }
+
+// Middleware административного API: токен, выданный пользователю, принимается только
+// при роли администратора (users.is_admin); токены client_credentials выдаются лишь
+// конфиденциальным клиентам и проверяются по scope. Подключается после ScopeValidator
+pub struct AdminGuard;
+
+impl<S, B> Transform<S, ServiceRequest> for AdminGuard
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AdminGuardService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AdminGuardService {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct AdminGuardService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AdminGuardService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            let claims = req.extensions().get::<TokenClaims>().cloned();
+            let Some(claims) = claims else {
+                let (http_req, _) = req.into_parts();
+                let response = HttpResponse::Unauthorized()
+                    .json(serde_json::json!({
+                        "error": "Authentication required"
+                    }));
+                return Ok(ServiceResponse::new(http_req, response).map_into_boxed_body());
+            };
+
+            // sub токена client_credentials — client_id, а не ID пользователя
+            if let Ok(user_id) = claims.sub.parse::<Uuid>() {
+                let is_admin = match req.app_data::<web::Data<UserService>>() {
+                    Some(user_service) => user_service.is_admin(user_id).await,
+                    None => Ok(false),
+                };
+
+                match is_admin {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        let (http_req, _) = req.into_parts();
+                        let response = HttpResponse::Forbidden()
+                            .json(serde_json::json!({
+                                "error": "Administrator role required"
+                            }));
+                        return Ok(ServiceResponse::new(http_req, response).map_into_boxed_body());
+                    }
+                    Err(e) => {
+                        eprintln!("Database error during admin check: {}", e);
+                        let (http_req, _) = req.into_parts();
+                        let response = HttpResponse::InternalServerError()
+                            .json(serde_json::json!({
+                                "error": "Internal server error"
+                            }));
+                        return Ok(ServiceResponse::new(http_req, response).map_into_boxed_body());
+                    }
+                }
+            }
+
+            let res = service.call(req).await?;
+            Ok(res.map_into_boxed_body())
+        })
+    }
+}
+
+// Middleware проверки браузерной сессии: отозванная в user_sessions сессия очищается
+// до обработки запроса, и handlers видят пользователя неаутентифицированным
+pub struct SessionGuard {
+    session_service: Rc<SessionService>,
+}
+
+impl SessionGuard {
+    pub fn new(session_service: SessionService) -> Self {
+        Self {
+            session_service: Rc::new(session_service),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SessionGuard
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse;
+    type Error = Error;
+    type InitError = ();
+    type Transform = SessionGuardService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SessionGuardService {
+            service: Rc::new(service),
+            session_service: self.session_service.clone(),
+        }))
+    }
+}
+
+pub struct SessionGuardService<S> {
+    service: Rc<S>,
+    session_service: Rc<SessionService>,
+}
+
+impl<S, B> Service<ServiceRequest> for SessionGuardService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let session_service = self.session_service.clone();
+
+        Box::pin(async move {
+            let session = req.get_session();
+
+            if let Ok(Some(user_id)) = session.get::<String>("user_id") {
+                let session_id = session.get::<String>("session_id").ok().flatten();
+
+                // Сессии без session_id (созданные до учета сессий) считаются недействительными
+                let active = match (user_id.parse::<Uuid>(), session_id.and_then(|id| id.parse::<Uuid>().ok())) {
+                    (Ok(user_id), Some(session_id)) => match session_service.touch_session(session_id, user_id).await {
+                        Ok(active) => active,
+                        Err(e) => {
+                            eprintln!("Database error during session validation: {}", e);
+                            let (http_req, _) = req.into_parts();
+                            let response = HttpResponse::InternalServerError()
+                                .json(serde_json::json!({
+                                    "error": "Internal server error"
+                                }));
+                            return Ok(ServiceResponse::new(http_req, response).map_into_boxed_body());
+                        }
+                    },
+                    _ => false,
+                };
+
+                if !active {
+                    session.purge();
+                }
+            }
+
+            let res = service.call(req).await?;
+            Ok(res.map_into_boxed_body())
+        })
+    }
+}
@@ -1,8 +1,9 @@
 use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
 // ============= USER MODELS =============
 
@@ -15,6 +16,47 @@ pub struct User {
     pub password_hash: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub email_verified: bool,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub status: String,
+    // Администратор потребовал сменить пароль: вход по паролю запрещен до сброса
+    pub password_reset_required: bool,
+}
+
+impl User {
+    pub fn status(&self) -> UserStatus {
+        UserStatus::from_db(&self.status)
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.status() == UserStatus::Suspended
+    }
+}
+
+// Статус аккаунта (колонка users.status)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UserStatus {
+    Active,
+    // Вход и выдача токенов запрещены
+    Suspended,
+}
+
+impl UserStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserStatus::Active => "active",
+            UserStatus::Suspended => "suspended",
+        }
+    }
+
+    // Неизвестное значение считается блокировкой: доступ дает только явный 'active'
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "active" => UserStatus::Active,
+            _ => UserStatus::Suspended,
+        }
+    }
 }
 
 // DTO для регистрации пользователя
@@ -26,7 +68,7 @@ pub struct RegisterUserRequest {
     #[validate(email)]
     pub email: String,
 
-    #[validate(length(min = 8))]
+    // Длина и состав проверяются PasswordPolicy, чтобы клиент получил структурированные нарушения
     pub password: String,
 }
 
@@ -36,6 +78,7 @@ pub struct RegisterUserResponse {
     pub id: Uuid,
     pub username: String,
     pub email: String,
+    pub email_verified: bool,
     pub created_at: DateTime<Utc>,
 }
 
@@ -45,11 +88,132 @@ impl From<User> for RegisterUserResponse {
             id: user.id,
             username: user.username,
             email: user.email,
+            email_verified: user.email_verified,
             created_at: user.created_at,
         }
     }
 }
 
+// Одноразовая ссылка подтверждения email
+#[derive(Debug, Clone, FromRow)]
+pub struct EmailVerificationToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub email: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Параметры ссылки подтверждения email
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailQuery {
+    pub token: String,
+}
+
+// DTO повторной отправки письма подтверждения
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResendVerificationRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
+// DTO запроса ссылки сброса пароля
+#[derive(Debug, Deserialize, Validate)]
+pub struct ForgotPasswordRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
+// DTO установки нового пароля по ссылке из письма
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPasswordRequest {
+    #[validate(length(min = 1))]
+    pub token: String,
+
+    // Длина и состав проверяются PasswordPolicy, чтобы клиент получил структурированные нарушения
+    pub password: String,
+}
+
+// DTO смены пароля пользователем сессии
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangePasswordRequest {
+    #[validate(length(min = 1))]
+    pub current_password: String,
+
+    // Длина и состав проверяются PasswordPolicy, чтобы клиент получил структурированные нарушения
+    pub new_password: String,
+
+    // Завершить остальные сессии и отозвать токены, выданные приложениям
+    #[serde(default)]
+    pub sign_out_other_sessions: bool,
+}
+
+// DTO запроса удаления аккаунта
+#[derive(Debug, Deserialize, Validate)]
+pub struct DeleteAccountRequest {
+    #[validate(length(min = 1))]
+    pub current_password: String,
+}
+
+// Контекст аутентификации пользователя, сохраняется в сессии при входе
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthContext {
+    pub auth_time: DateTime<Utc>,
+    pub acr: String,
+    // Методы аутентификации (RFC 8176), в сессиях до появления поля — только пароль
+    #[serde(default = "password_amr")]
+    pub amr: Vec<String>,
+}
+
+// Уровень аутентификации (acr) при входе только по паролю
+pub const ACR_PASSWORD: &str = "1";
+// Уровень аутентификации (acr) при входе с паролем и вторым фактором
+pub const ACR_MULTI_FACTOR: &str = "2";
+
+// amr входа только по паролю
+pub fn password_amr() -> Vec<String> {
+    vec!["pwd".to_string()]
+}
+
+impl AuthContext {
+    pub fn password(auth_time: DateTime<Utc>) -> Self {
+        Self {
+            auth_time,
+            acr: ACR_PASSWORD.to_string(),
+            amr: password_amr(),
+        }
+    }
+
+    // Вход по паролю и одноразовому коду (TOTP или код восстановления)
+    pub fn multi_factor(auth_time: DateTime<Utc>) -> Self {
+        Self {
+            auth_time,
+            acr: ACR_MULTI_FACTOR.to_string(),
+            amr: vec!["pwd".to_string(), "otp".to_string()],
+        }
+    }
+
+    // Вход по паролю и ключу доступа (passkey) в качестве второго фактора
+    pub fn password_and_passkey(auth_time: DateTime<Utc>) -> Self {
+        Self {
+            auth_time,
+            acr: ACR_MULTI_FACTOR.to_string(),
+            amr: vec!["pwd".to_string(), "hwk".to_string()],
+        }
+    }
+
+    // Вход по ключу доступа без пароля: ключ и проверка пользователя на устройстве
+    pub fn passkey(auth_time: DateTime<Utc>) -> Self {
+        Self {
+            auth_time,
+            acr: ACR_MULTI_FACTOR.to_string(),
+            amr: vec!["hwk".to_string(), "user".to_string()],
+        }
+    }
+}
+
 // DTO для логина
 #[derive(Debug, Deserialize, Validate)]
 pub struct LoginRequest {
@@ -59,9 +223,200 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+// DTO смены email: новый адрес и текущий пароль
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangeEmailRequest {
+    #[validate(email)]
+    pub new_email: String,
+    #[validate(length(min = 1))]
+    pub current_password: String,
+}
+
+// DTO с кодом второго фактора: TOTP или код восстановления
+#[derive(Debug, Deserialize, Validate)]
+pub struct MfaCodeRequest {
+    #[validate(length(min = 1, max = 32))]
+    pub code: String,
+}
+
+// ============= PROFILE MODELS =============
+
+// Профиль пользователя (без хеша пароля)
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct UserProfile {
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub email_verified: bool,
+    pub display_name: Option<String>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    pub avatar_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// Частичное изменение профиля: отсутствующее поле не меняется,
+// пустая строка очищает необязательное поле
+#[derive(Debug, Default, Deserialize, Validate)]
+pub struct UpdateProfileRequest {
+    #[validate(length(min = 3, max = 50))]
+    pub username: Option<String>,
+    #[validate(length(max = 100))]
+    pub display_name: Option<String>,
+    #[validate(custom = "validate_locale")]
+    pub locale: Option<String>,
+    #[validate(custom = "validate_timezone")]
+    pub timezone: Option<String>,
+    #[validate(length(max = 2048), custom = "validate_avatar_url")]
+    pub avatar_url: Option<String>,
+}
+
+impl UpdateProfileRequest {
+    pub fn is_empty(&self) -> bool {
+        self.username.is_none()
+            && self.display_name.is_none()
+            && self.locale.is_none()
+            && self.timezone.is_none()
+            && self.avatar_url.is_none()
+    }
+}
+
+// Тег языка BCP 47: подтеги из букв и цифр через '-' (en, ru-RU, zh-Hant-TW)
+pub fn validate_locale(locale: &str) -> Result<(), ValidationError> {
+    if locale.is_empty() {
+        return Ok(());
+    }
+    let mut subtags = locale.split('-');
+    let language_ok = subtags
+        .next()
+        .is_some_and(|tag| (2..=8).contains(&tag.len()) && tag.chars().all(|c| c.is_ascii_alphabetic()));
+    let rest_ok = subtags.all(|tag| (1..=8).contains(&tag.len()) && tag.chars().all(|c| c.is_ascii_alphanumeric()));
+
+    if language_ok && rest_ok && locale.len() <= 35 {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid_locale"))
+    }
+}
+
+// Имя часового пояса IANA (Europe/Moscow, America/Argentina/Buenos_Aires) или UTC
+pub fn validate_timezone(timezone: &str) -> Result<(), ValidationError> {
+    if timezone.is_empty() || timezone == "UTC" {
+        return Ok(());
+    }
+    let valid = timezone.len() <= 64
+        && timezone.contains('/')
+        && timezone.split('/').all(|part| {
+            part.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+'))
+        });
+
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid_timezone"))
+    }
+}
+
+// Аватар — абсолютный https URL (отображается на страницах других сервисов)
+pub fn validate_avatar_url(avatar_url: &str) -> Result<(), ValidationError> {
+    if avatar_url.is_empty() {
+        return Ok(());
+    }
+    match url::Url::parse(avatar_url) {
+        Ok(url) if url.scheme() == "https" && url.host_str().is_some() => Ok(()),
+        _ => Err(ValidationError::new("invalid_avatar_url")),
+    }
+}
+
+// ============= PASSKEY MODELS =============
+
+// Ключ доступа (WebAuthn) пользователя
+#[derive(Debug, Clone, FromRow)]
+pub struct WebAuthnCredential {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    // Идентификатор учетных данных аутентификатора (base64url)
+    pub credential_id: String,
+    // Открытый ключ P-256 в несжатом SEC1 формате
+    pub public_key: Vec<u8>,
+    pub sign_count: i64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+// Ключ доступа в списке пользователя (без открытого ключа)
+#[derive(Debug, Serialize)]
+pub struct PasskeyResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<WebAuthnCredential> for PasskeyResponse {
+    fn from(credential: WebAuthnCredential) -> Self {
+        Self {
+            id: credential.id,
+            name: credential.name,
+            created_at: credential.created_at,
+            last_used_at: credential.last_used_at,
+        }
+    }
+}
+
+// DTO ответа navigator.credentials.create (бинарные поля в base64url)
+#[derive(Debug, Deserialize, Validate)]
+pub struct PasskeyRegistrationRequest {
+    #[validate(length(max = 100))]
+    pub name: Option<String>,
+    #[validate(length(min = 1, max = 1400))]
+    pub id: String,
+    #[validate(length(min = 1))]
+    pub client_data_json: String,
+    #[validate(length(min = 1))]
+    pub attestation_object: String,
+}
+
+// DTO ответа navigator.credentials.get (бинарные поля в base64url)
+#[derive(Debug, Deserialize, Validate)]
+pub struct PasskeyAssertionRequest {
+    #[validate(length(min = 1, max = 1400))]
+    pub id: String,
+    #[validate(length(min = 1))]
+    pub client_data_json: String,
+    #[validate(length(min = 1))]
+    pub authenticator_data: String,
+    #[validate(length(min = 1))]
+    pub signature: String,
+    pub user_handle: Option<String>,
+}
+
 // ============= OAUTH CLIENT MODELS =============
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+// Формат access token, выдаваемого клиенту
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessTokenFormat {
+    // Самодостаточный JWT (RFC 9068), проверяется без обращения к серверу
+    #[default]
+    Jwt,
+    // Случайный reference token, проверяется через БД или introspection
+    Opaque,
+}
+
+impl AccessTokenFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccessTokenFormat::Jwt => "jwt",
+            AccessTokenFormat::Opaque => "opaque",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct OAuthClient {
     pub id: Uuid,
     pub client_id: String,
@@ -73,6 +428,26 @@ pub struct OAuthClient {
     pub is_confidential: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub access_token_format: String,
+    // Переопределения глобальной политики PKCE (NULL — использовать глобальную)
+    pub require_pkce: Option<bool>,
+    pub allow_plain_pkce: Option<bool>,
+    // Переопределения времени жизни токенов в секундах (NULL — глобальные значения)
+    pub access_token_ttl: Option<i64>,
+    pub refresh_token_ttl: Option<i64>,
+    pub refresh_token_absolute_ttl: Option<i64>,
+    pub authorization_code_ttl: Option<i64>,
+    // Собственное приложение сервиса: допускаются scopes restricted_to_first_party
+    pub is_first_party: bool,
+}
+
+impl OAuthClient {
+    pub fn token_format(&self) -> AccessTokenFormat {
+        match self.access_token_format.as_str() {
+            "opaque" => AccessTokenFormat::Opaque,
+            _ => AccessTokenFormat::Jwt,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -84,6 +459,24 @@ pub struct CreateClientRequest {
     pub allowed_scopes: Vec<String>,
     pub grant_types: Vec<String>,
     pub is_confidential: bool,
+    #[serde(default)]
+    pub access_token_format: AccessTokenFormat,
+    pub require_pkce: Option<bool>,
+    pub allow_plain_pkce: Option<bool>,
+    #[validate(range(min = 60, max = 86400))]
+    pub access_token_ttl: Option<i64>,
+    #[validate(range(min = 300))]
+    pub refresh_token_ttl: Option<i64>,
+    #[validate(range(min = 300))]
+    pub refresh_token_absolute_ttl: Option<i64>,
+    #[validate(range(min = 30, max = 600))]
+    pub authorization_code_ttl: Option<i64>,
+}
+
+// Административное изменение клиента
+#[derive(Debug, Deserialize)]
+pub struct UpdateClientRequest {
+    pub is_first_party: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
@@ -94,6 +487,11 @@ pub struct CreateClientResponse {
     pub redirect_uris: Vec<String>,
     pub allowed_scopes: Vec<String>,
     pub grant_types: Vec<String>,
+    pub access_token_format: AccessTokenFormat,
+    pub access_token_ttl: Option<i64>,
+    pub refresh_token_ttl: Option<i64>,
+    pub refresh_token_absolute_ttl: Option<i64>,
+    pub authorization_code_ttl: Option<i64>,
 }
 
 // ============= OAUTH AUTHORIZATION CODE MODELS =============
@@ -111,6 +509,20 @@ pub struct AuthorizationCode {
     pub expires_at: DateTime<Utc>,
     pub used: bool,
     pub created_at: DateTime<Utc>,
+    pub auth_time: Option<DateTime<Utc>>,
+    pub acr: Option<String>,
+    pub amr: Option<Vec<String>>,
+}
+
+impl AuthorizationCode {
+    // Контекст аутентификации пользователя на момент выдачи кода
+    pub fn auth_context(&self) -> Option<AuthContext> {
+        self.auth_time.map(|auth_time| AuthContext {
+            auth_time,
+            acr: self.acr.clone().unwrap_or_else(|| ACR_PASSWORD.to_string()),
+            amr: self.amr.clone().unwrap_or_else(password_amr),
+        })
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -133,6 +545,50 @@ pub struct ConsentRequest {
     pub code_challenge: Option<String>,
     pub code_challenge_method: Option<String>,
     pub approved: bool,
+    // Одобренные пользователем scopes (подмножество scope); без поля одобрены все
+    #[serde(default)]
+    pub approved_scope: Option<String>,
+}
+
+// ============= OAUTH CONSENT MODELS =============
+
+// Согласие пользователя на доступ клиента к scopes
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Consent {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub client_id: String,
+    pub scopes: Vec<String>,
+    // Необязательные scopes, от которых пользователь отказался
+    pub declined_scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Consent {
+    // Scopes из запроса, на которые пользователь еще не давал согласие
+    pub fn missing_scopes<'a>(&self, requested: &[&'a str]) -> Vec<&'a str> {
+        requested.iter()
+            .filter(|s| !self.scopes.iter().any(|granted| granted == *s))
+            .copied()
+            .collect()
+    }
+
+    // Отказывался ли пользователь от scope
+    pub fn is_declined(&self, scope: &str) -> bool {
+        self.declined_scopes.iter().any(|declined| declined == scope)
+    }
+}
+
+// Приложение, которому пользователь выдал доступ
+#[derive(Debug, Serialize, FromRow)]
+pub struct ConnectedApp {
+    pub client_id: String,
+    pub client_name: String,
+    pub scopes: Vec<String>,
+    pub authorized_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub active_tokens: i64,
 }
 
 // ============= OAUTH TOKEN MODELS =============
@@ -140,8 +596,9 @@ pub struct ConsentRequest {
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct OAuthToken {
     pub id: Uuid,
-    pub access_token: String,
-    pub refresh_token: Option<String>,
+    // SHA-256 токенов (secrets::hash_secret), сами токены не хранятся
+    pub access_token_hash: String,
+    pub refresh_token_hash: Option<String>,
     pub client_id: String,
     pub user_id: Option<Uuid>,
     pub scope: String,
@@ -150,6 +607,29 @@ pub struct OAuthToken {
     pub refresh_expires_at: Option<DateTime<Utc>>,
     pub revoked: bool,
     pub created_at: DateTime<Utc>,
+    pub jti: Option<String>,
+    pub auth_time: Option<DateTime<Utc>>,
+    pub acr: Option<String>,
+    pub grant_scope: Option<String>,
+    // Абсолютный предел жизни цепочки refresh token, переносится при ротации
+    pub refresh_absolute_expires_at: Option<DateTime<Utc>>,
+    pub amr: Option<Vec<String>>,
+}
+
+impl OAuthToken {
+    // Scope исходного гранта (для записей без grant_scope совпадает со scope)
+    pub fn granted_scope(&self) -> &str {
+        self.grant_scope.as_deref().unwrap_or(&self.scope)
+    }
+
+    // Восстановление контекста аутентификации, с которым был выдан токен
+    pub fn auth_context(&self) -> Option<AuthContext> {
+        self.auth_time.map(|auth_time| AuthContext {
+            auth_time,
+            acr: self.acr.clone().unwrap_or_else(|| ACR_PASSWORD.to_string()),
+            amr: self.amr.clone().unwrap_or_else(password_amr),
+        })
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -173,23 +653,279 @@ pub struct TokenResponse {
     pub scope: String,
 }
 
+// Запрос introspection (RFC 7662)
+#[derive(Debug, Deserialize)]
+pub struct IntrospectionRequest {
+    pub token: String,
+    pub token_type_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+// Ответ introspection (RFC 7662)
+#[derive(Debug, Serialize, Default)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acr: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amr: Option<Vec<String>>,
+}
+
+impl From<TokenClaims> for IntrospectionResponse {
+    fn from(claims: TokenClaims) -> Self {
+        Self {
+            active: true,
+            scope: Some(claims.scope),
+            client_id: Some(claims.client_id),
+            token_type: Some("Bearer".to_string()),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            sub: Some(claims.sub),
+            aud: Some(claims.aud),
+            iss: Some(claims.iss),
+            jti: Some(claims.jti),
+            auth_time: claims.auth_time,
+            acr: claims.acr,
+            amr: claims.amr,
+        }
+    }
+}
+
+// Claims access token по профилю RFC 9068
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TokenClaims {
+    pub iss: String,
     pub sub: String, // user_id or client_id
+    pub aud: String,
     pub client_id: String,
     pub scope: String,
+    pub jti: String,
     pub exp: i64,
     pub iat: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acr: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amr: Option<Vec<String>>,
 }
 
 // ============= SCOPE MODELS =============
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Scope {
     pub id: Uuid,
     pub scope_name: String,
+    // Описание по умолчанию (используется, если нет перевода)
+    pub description: String,
+    // Требуется ли явное согласие пользователя на consent screen
+    pub requires_consent: bool,
+    // Выдается, если в запросе авторизации scope не указан
+    pub is_default: bool,
+    // Доступен только first-party клиентам
+    pub restricted_to_first_party: bool,
+    // Пользователь может отказаться от scope на consent screen
+    pub is_optional: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// Перевод описания scope
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ScopeTranslation {
+    pub scope_name: String,
+    pub locale: String,
     pub description: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScopeResponse {
+    #[serde(flatten)]
+    pub scope: Scope,
+    // Переводы описания: locale -> описание
+    pub translations: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateScopeRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub scope_name: String,
+    #[validate(length(min = 1))]
+    pub description: String,
+    #[serde(default = "default_true")]
+    pub requires_consent: bool,
+    #[serde(default)]
+    pub is_default: bool,
+    #[serde(default)]
+    pub restricted_to_first_party: bool,
+    #[serde(default)]
+    pub is_optional: bool,
+    #[serde(default)]
+    pub translations: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateScopeRequest {
+    #[validate(length(min = 1))]
+    pub description: Option<String>,
+    pub requires_consent: Option<bool>,
+    pub is_default: Option<bool>,
+    pub restricted_to_first_party: Option<bool>,
+    pub is_optional: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ScopeTranslationRequest {
+    #[validate(length(min = 1))]
+    pub description: String,
+}
+
+// Scope с описанием на языке пользователя (для consent screen)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopeDescription {
+    pub scope_name: String,
+    pub description: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// ============= DISCOVERY MODELS =============
+
+// Метаданные сервера авторизации (RFC 8414)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthorizationServerMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub revocation_endpoint: String,
+    pub registration_endpoint: String,
+    pub response_types_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub revocation_endpoint_auth_methods_supported: Vec<String>,
+    pub introspection_endpoint: String,
+    pub introspection_endpoint_auth_methods_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
+    pub authorization_response_iss_parameter_supported: bool,
+}
+
+// ============= DATA EXPORT MODELS =============
+
+// Выгрузка персональных данных пользователя (без хешей паролей, секретов и токенов)
+#[derive(Debug, Serialize)]
+pub struct AccountExport {
+    pub exported_at: DateTime<Utc>,
+    pub profile: UserProfile,
+    pub consents: Vec<Consent>,
+    pub sessions: Vec<SessionRecord>,
+    pub connected_apps: Vec<ConnectedApp>,
+    pub audit_history: Vec<AuditEvent>,
+}
+
+// Браузерная сессия пользователя
+#[derive(Debug, Serialize, FromRow)]
+pub struct SessionRecord {
+    pub id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+// Событие безопасности аккаунта (регистрация, смена email, сброс пароля, второй фактор и т.п.)
+#[derive(Debug, Serialize, FromRow)]
+pub struct AuditEvent {
+    pub event: String,
+    pub occurred_at: DateTime<Utc>,
+    pub details: Option<String>,
+}
+
+// ============= ADMIN USER MODELS =============
+
+// Пользователь в административном API (без хеша пароля)
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AdminUser {
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub email_verified: bool,
+    pub status: String,
+    pub suspended_at: Option<DateTime<Utc>>,
+    pub suspension_reason: Option<String>,
+    pub password_reset_required: bool,
+    pub is_admin: bool,
+    pub deletion_scheduled_for: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// Поиск пользователей: q — подстрока username или email, фильтры и страница
+#[derive(Debug, Default, Deserialize, Validate)]
+pub struct AdminUserQuery {
+    #[validate(length(max = 255))]
+    pub q: Option<String>,
+    pub status: Option<UserStatus>,
+    pub email_verified: Option<bool>,
+    #[validate(range(min = 1))]
+    pub page: Option<i64>,
+    #[validate(range(min = 1, max = 100))]
+    pub per_page: Option<i64>,
+}
+
+impl AdminUserQuery {
+    pub const DEFAULT_PER_PAGE: i64 = 20;
+
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1)
+    }
+
+    pub fn per_page(&self) -> i64 {
+        self.per_page.unwrap_or(Self::DEFAULT_PER_PAGE)
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page() - 1).saturating_mul(self.per_page())
+    }
+}
+
+// Страница результатов поиска пользователей
+#[derive(Debug, Serialize)]
+pub struct AdminUserList {
+    pub users: Vec<AdminUser>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+}
+
+// Приостановка пользователя администратором
+#[derive(Debug, Default, Deserialize, Validate)]
+pub struct SuspendUserRequest {
+    #[validate(length(max = 500))]
+    pub reason: Option<String>,
 }
 
 // ============= ERROR RESPONSES =============
@@ -200,6 +936,13 @@ pub struct ErrorResponse {
     pub error: String,
 }
 
+// Пароль не соответствует политике: список нарушенных правил
+#[derive(Debug, Serialize)]
+pub struct PasswordPolicyErrorResponse {
+    pub error: String,
+    pub violations: Vec<crate::password_policy::PasswordViolation>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct OAuthErrorResponse {
     pub error: String,
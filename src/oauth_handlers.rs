@@ -1,29 +1,44 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, http::header, HttpRequest, HttpResponse, Responder};
 use actix_session::Session;
-use crate::models::{AuthorizeRequest, ConsentRequest, TokenRequest, OAuthErrorResponse, CreateClientRequest, CreateClientResponse};
-use crate::client_service::ClientService;
-use crate::oauth_service::OAuthService;
+use crate::models::{AuthorizeRequest, ConsentRequest, TokenRequest, OAuthErrorResponse, CreateClientRequest, CreateClientResponse, AuthContext, IntrospectionRequest, ScopeDescription};
+use crate::client_service::{ClientService, ClientError};
+use crate::oauth_service::{OAuthService, NewAuthorizationCode};
 use crate::token_service::TokenService;
+use crate::config::AppConfig;
+use crate::consent_service::ConsentService;
+use crate::scope_utils::parse_scope;
+use crate::scope_service::{
+    ScopeService, ScopeError, check_scopes, scopes_requiring_consent, describe_scopes, parse_accept_language,
+    optional_scopes, resolve_approved_scopes,
+};
 use validator::Validate;
 
 // GET /oauth/authorize - показывает consent screen
+#[allow(clippy::too_many_arguments)]
 pub async fn authorize_get(
+    req: HttpRequest,
     query: web::Query<AuthorizeRequest>,
     client_service: web::Data<ClientService>,
+    oauth_service: web::Data<OAuthService>,
+    consent_service: web::Data<ConsentService>,
+    scope_service: web::Data<ScopeService>,
+    config: web::Data<AppConfig>,
     session: Session,
 ) -> impl Responder {
     // Проверка аутентификации пользователя
-    let _user_id_str = match session.get::<String>("user_id") {
+    let user_id_str = match session.get::<String>("user_id") {
         Ok(Some(id)) => id,
         Ok(None) => {
             // Redirect to login with return URL
             let return_url = format!(
-                "/oauth/authorize?response_type={}&client_id={}&redirect_uri={}&scope={}&state={}",
-                query.response_type,
-                query.client_id,
+                "/oauth/authorize?response_type={}&client_id={}&redirect_uri={}&scope={}&state={}&code_challenge={}&code_challenge_method={}",
+                urlencoding::encode(&query.response_type),
+                urlencoding::encode(&query.client_id),
                 urlencoding::encode(&query.redirect_uri),
-                urlencoding::encode(&query.scope.as_deref().unwrap_or("")),
-                urlencoding::encode(&query.state.as_deref().unwrap_or(""))
+                urlencoding::encode(query.scope.as_deref().unwrap_or("")),
+                urlencoding::encode(query.state.as_deref().unwrap_or("")),
+                urlencoding::encode(query.code_challenge.as_deref().unwrap_or("")),
+                urlencoding::encode(query.code_challenge_method.as_deref().unwrap_or(""))
             );
             return HttpResponse::Found()
                 .append_header(("Location", format!("/auth/login?return_to={}", urlencoding::encode(&return_url))))
@@ -34,9 +49,16 @@ pub async fn authorize_get(
         }
     };
 
+    let user_id = match user_id_str.parse::<uuid::Uuid>() {
+        Ok(id) => id,
+        Err(_) => {
+            return HttpResponse::BadRequest().body("Invalid user ID in session");
+        }
+    };
+
     // Валидация параметров
     if query.response_type != "code" {
-        return build_error_redirect(&query.redirect_uri, "unsupported_response_type", Some("Only 'code' response type is supported"), query.state.as_deref());
+        return build_error_redirect(&query.redirect_uri, &config.issuer, "unsupported_response_type", Some("Only 'code' response type is supported"), query.state.as_deref());
     }
 
     // Получение клиента
@@ -57,26 +79,152 @@ pub async fn authorize_get(
     };
 
     // Валидация redirect_uri
-    if let Err(_) = client_service.validate_redirect_uri(&client, &query.redirect_uri) {
+    if client_service.validate_redirect_uri(&client, &query.redirect_uri).is_err() {
         return HttpResponse::BadRequest().json(OAuthErrorResponse {
             error: "invalid_request".to_string(),
             error_description: Some("Invalid redirect_uri".to_string()),
         });
     }
 
-    // Валидация scope
-    let scope = query.scope.as_deref().unwrap_or("");
-    if let Err(_) = client_service.validate_scope(&client, scope) {
-        return build_error_redirect(&query.redirect_uri, "invalid_scope", Some("Requested scope not allowed"), query.state.as_deref());
+    // Без scope в запросе выдаются scopes по умолчанию, разрешенные клиенту
+    let scope = match query.scope.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        Some(scope) => scope.to_string(),
+        None => match scope_service.default_scopes().await {
+            Ok(defaults) => defaults.into_iter()
+                .filter(|s| client.allowed_scopes.contains(s))
+                .collect::<Vec<_>>()
+                .join(" "),
+            Err(e) => {
+                eprintln!("Database error: {}", e);
+                return HttpResponse::InternalServerError().json(OAuthErrorResponse {
+                    error: "server_error".to_string(),
+                    error_description: Some("Database error".to_string()),
+                });
+            }
+        },
+    };
+
+    // Валидация scope по настройкам клиента и реестру scopes
+    if client_service.validate_scope(&client, &scope).is_err() {
+        return build_error_redirect(&query.redirect_uri, &config.issuer, "invalid_scope", Some("Requested scope not allowed"), query.state.as_deref());
     }
 
-    // Отображение consent screen
-    let scopes: Vec<&str> = scope.split_whitespace().collect();
-    let scopes_html = scopes.iter()
-        .map(|s| format!("<li>{}</li>", s))
+    let scopes = parse_scope(&scope);
+    let (definitions, translations) = match tokio::try_join!(
+        scope_service.get_scopes(&scopes),
+        scope_service.get_translations(&scopes),
+    ) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(OAuthErrorResponse {
+                error: "server_error".to_string(),
+                error_description: Some("Database error".to_string()),
+            });
+        }
+    };
+
+    if let Err(e) = check_scopes(&scopes, &definitions, client.is_first_party) {
+        return build_error_redirect(&query.redirect_uri, &config.issuer, "invalid_scope", Some(&e.to_string()), query.state.as_deref());
+    }
+
+    // Валидация PKCE по политике клиента
+    let code_challenge = non_empty(&query.code_challenge);
+    let code_challenge_method = non_empty(&query.code_challenge_method);
+    if let Err(e) = oauth_service.pkce_rules(&client).validate_challenge(
+        code_challenge.as_deref(),
+        code_challenge_method.as_deref(),
+    ) {
+        return build_error_redirect(&query.redirect_uri, &config.issuer, "invalid_request", Some(&e.to_string()), query.state.as_deref());
+    }
+
+    // Проверка ранее выданного согласия
+    let consent = match consent_service.get_consent(user_id, &client.client_id).await {
+        Ok(consent) => consent,
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(OAuthErrorResponse {
+                error: "server_error".to_string(),
+                error_description: Some("Database error".to_string()),
+            });
+        }
+    };
+
+    // Необязательные scopes, от которых пользователь ранее отказался
+    let optional = optional_scopes(&scopes, &definitions);
+    let declined: Vec<&str> = match &consent {
+        Some(consent) => optional.iter().filter(|s| consent.is_declined(s)).copied().collect(),
+        None => Vec::new(),
+    };
+
+    let new_scopes: Vec<&str> = match &consent {
+        Some(consent) => consent.missing_scopes(&scopes),
+        None => scopes.clone(),
+    };
+    let new_scopes: Vec<&str> = new_scopes.into_iter().filter(|s| !declined.contains(s)).collect();
+    let needs_consent = scopes_requiring_consent(&new_scopes, &definitions);
+
+    // Все запрошенные scopes уже одобрены или не требуют согласия — выдаем код без consent screen
+    if needs_consent.is_empty() && (consent.is_some() || !scopes.is_empty()) {
+        // Отклоненные ранее необязательные scopes не выдаются
+        let granted: Vec<&str> = scopes.iter().filter(|s| !declined.contains(s)).copied().collect();
+        return redirect_with_code(
+            &oauth_service,
+            &config.issuer,
+            NewAuthorizationCode {
+                client: &client,
+                user_id,
+                redirect_uri: &query.redirect_uri,
+                scope: &granted.join(" "),
+                code_challenge,
+                code_challenge_method,
+                auth_context: session.get::<AuthContext>("auth_context").ok().flatten(),
+            },
+            query.state.as_deref(),
+        ).await;
+    }
+
+    // Отображение consent screen: только новые scopes (incremental authorization)
+    // с описаниями на языке пользователя
+    let locales = req.headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map(parse_accept_language)
+        .unwrap_or_default();
+    let descriptions = describe_scopes(&scopes, &definitions, &translations, &locales);
+
+    // На экран выносятся новые scopes и ранее отклоненные необязательные:
+    // необязательные можно снять, обязательные отмечены как таковые
+    let in_review = |name: &str| needs_consent.contains(&name) || declined.contains(&name);
+    let scopes_html = descriptions.iter()
+        .filter(|d| in_review(&d.scope_name))
+        .map(|d| {
+            let name = d.scope_name.as_str();
+            consent_item_html(d, optional.contains(&name), !declined.contains(&name))
+        })
         .collect::<Vec<_>>()
         .join("");
 
+    // Scopes без переключателя одобряются всегда
+    let fixed_scope = scopes.iter()
+        .filter(|s| !(in_review(s) && optional.contains(s)))
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let granted_items: Vec<String> = descriptions.iter()
+        .filter(|d| !in_review(&d.scope_name))
+        .map(scope_item_html)
+        .collect();
+    let granted_html = if granted_items.is_empty() {
+        String::new()
+    } else {
+        format!(
+            r#"<div class="scopes granted"><p><strong>Уже разрешено:</strong></p><ul>{}</ul></div>"#,
+            granted_items.join("")
+        )
+    };
+
     let html = format!(r#"
 <!DOCTYPE html>
 <html>
@@ -90,6 +238,9 @@ pub async fn authorize_get(
         .scopes {{ margin: 20px 0; }}
         .scopes ul {{ list-style: none; padding: 0; }}
         .scopes li {{ padding: 8px; background: #e3f2fd; margin: 5px 0; border-radius: 4px; }}
+        .scopes li small {{ color: #666; }}
+        .granted li {{ background: #f1f8e9; color: #555; }}
+        .required {{ color: #666; font-size: 14px; }}
         .buttons {{ display: flex; gap: 10px; justify-content: center; }}
         button {{ padding: 10px 20px; border: none; border-radius: 4px; cursor: pointer; font-size: 16px; }}
         .approve {{ background-color: #28a745; color: white; }}
@@ -108,6 +259,7 @@ pub async fn authorize_get(
         <p><strong>Запрашиваемые разрешения:</strong></p>
         <ul>{}</ul>
     </div>
+    {}
     <form id="consentForm" method="POST" action="/oauth/authorize">
         <input type="hidden" name="client_id" value="{}">
         <input type="hidden" name="redirect_uri" value="{}">
@@ -116,6 +268,8 @@ pub async fn authorize_get(
         <input type="hidden" name="code_challenge" value="{}">
         <input type="hidden" name="code_challenge_method" value="{}">
         <input type="hidden" name="approved" value="false" id="approvedField">
+        <input type="hidden" name="approved_scope" value="" id="approvedScopeField">
+        <input type="hidden" value="{}" id="fixedScopes">
         <div class="buttons">
             <button type="button" class="approve" onclick="approve()">Разрешить</button>
             <button type="button" class="deny" onclick="deny()">Отклонить</button>
@@ -124,6 +278,9 @@ pub async fn authorize_get(
 
     <script>
         function approve() {{
+            const fixed = document.getElementById('fixedScopes').value.split(' ').filter(Boolean);
+            const checked = Array.from(document.querySelectorAll('.optional-scope:checked')).map(c => c.value);
+            document.getElementById('approvedScopeField').value = fixed.concat(checked).join(' ');
             document.getElementById('approvedField').value = 'true';
             document.getElementById('consentForm').submit();
         }}
@@ -135,15 +292,17 @@ pub async fn authorize_get(
 </body>
 </html>
     "#,
-        client.client_name,
-        client.client_id,
+        html_escape(&client.client_name),
+        html_escape(&client.client_id),
         scopes_html,
-        query.client_id,
-        query.redirect_uri,
-        scope,
-        query.state.as_deref().unwrap_or(""),
-        query.code_challenge.as_deref().unwrap_or(""),
-        query.code_challenge_method.as_deref().unwrap_or("")
+        granted_html,
+        html_escape(&query.client_id),
+        html_escape(&query.redirect_uri),
+        html_escape(&scope),
+        html_escape(query.state.as_deref().unwrap_or("")),
+        html_escape(query.code_challenge.as_deref().unwrap_or("")),
+        html_escape(query.code_challenge_method.as_deref().unwrap_or("")),
+        html_escape(&fixed_scope)
     );
 
     HttpResponse::Ok()
@@ -156,66 +315,195 @@ pub async fn authorize_post(
     form: web::Form<ConsentRequest>,
     oauth_service: web::Data<OAuthService>,
     client_service: web::Data<ClientService>,
+    consent_service: web::Data<ConsentService>,
+    scope_service: web::Data<ScopeService>,
+    config: web::Data<AppConfig>,
     session: Session,
 ) -> impl Responder {
     // Получение user_id из сессии
     let user_id_str = match session.get::<String>("user_id") {
         Ok(Some(id)) => id,
         _ => {
-            return build_error_redirect(&form.redirect_uri, "access_denied", Some("User not authenticated"), form.state.as_deref());
+            return build_error_redirect(&form.redirect_uri, &config.issuer, "access_denied", Some("User not authenticated"), form.state.as_deref());
         }
     };
 
     let user_id = match user_id_str.parse::<uuid::Uuid>() {
         Ok(id) => id,
         Err(_) => {
-            return build_error_redirect(&form.redirect_uri, "server_error", Some("Invalid user ID"), form.state.as_deref());
+            return build_error_redirect(&form.redirect_uri, &config.issuer, "server_error", Some("Invalid user ID"), form.state.as_deref());
         }
     };
 
     // Проверка согласия
     if !form.approved {
-        return build_error_redirect(&form.redirect_uri, "access_denied", Some("User denied authorization"), form.state.as_deref());
+        return build_error_redirect(&form.redirect_uri, &config.issuer, "access_denied", Some("User denied authorization"), form.state.as_deref());
     }
 
     // Получение клиента
-    let _client = match client_service.get_client_by_id(&form.client_id).await {
+    let client = match client_service.get_client_by_id(&form.client_id).await {
         Ok(Some(client)) => client,
         _ => {
-            return build_error_redirect(&form.redirect_uri, "invalid_client", Some("Client not found"), form.state.as_deref());
+            return build_error_redirect(&form.redirect_uri, &config.issuer, "invalid_client", Some("Client not found"), form.state.as_deref());
+        }
+    };
+
+    // Повторная валидация redirect_uri и scope: форма могла быть изменена
+    if client_service.validate_redirect_uri(&client, &form.redirect_uri).is_err() {
+        return HttpResponse::BadRequest().json(OAuthErrorResponse {
+            error: "invalid_request".to_string(),
+            error_description: Some("Invalid redirect_uri".to_string()),
+        });
+    }
+
+    if client_service.validate_scope(&client, &form.scope).is_err() {
+        return build_error_redirect(&form.redirect_uri, &config.issuer, "invalid_scope", Some("Requested scope not allowed"), form.state.as_deref());
+    }
+
+    let scopes = parse_scope(&form.scope);
+    let definitions = match scope_service.get_scopes(&scopes).await {
+        Ok(definitions) => definitions,
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            return build_error_redirect(&form.redirect_uri, &config.issuer, "server_error", Some("Database error"), form.state.as_deref());
         }
     };
 
+    if let Err(e) = check_scopes(&scopes, &definitions, client.is_first_party) {
+        return build_error_redirect(&form.redirect_uri, &config.issuer, "invalid_scope", Some(&e.to_string()), form.state.as_deref());
+    }
+
+    // Одобренные пользователем scopes должны входить в запрошенные
+    let approved = form.approved_scope.as_deref().map(parse_scope).unwrap_or_else(|| scopes.clone());
+    if !approved.iter().all(|s| scopes.contains(s)) {
+        return build_error_redirect(&form.redirect_uri, &config.issuer, "invalid_scope", Some("Approved scope exceeds requested scope"), form.state.as_deref());
+    }
+
+    // Пустые скрытые поля формы означают отсутствие PKCE
+    let code_challenge = non_empty(&form.code_challenge);
+    let code_challenge_method = non_empty(&form.code_challenge_method);
+
+    // Повторная проверка PKCE: форма могла быть изменена
+    if let Err(e) = oauth_service.pkce_rules(&client).validate_challenge(
+        code_challenge.as_deref(),
+        code_challenge_method.as_deref(),
+    ) {
+        return build_error_redirect(&form.redirect_uri, &config.issuer, "invalid_request", Some(&e.to_string()), form.state.as_deref());
+    }
+
+    // Итоговый scope: обязательные и одобренные необязательные scopes
+    let optional = optional_scopes(&scopes, &definitions);
+    let granted = resolve_approved_scopes(&scopes, &optional, &approved);
+    let declined: Vec<&str> = optional.iter().filter(|s| !granted.contains(s)).copied().collect();
+
+    // Сохранение согласия, чтобы не показывать consent screen повторно
+    if let Err(e) = consent_service.grant_consent(user_id, &client.client_id, &granted, &declined).await {
+        eprintln!("Failed to store consent: {}", e);
+        return build_error_redirect(&form.redirect_uri, &config.issuer, "server_error", Some("Failed to store consent"), form.state.as_deref());
+    }
+
+    // Контекст аутентификации (время входа, acr) для claims токенов
+    let auth_context = session.get::<AuthContext>("auth_context").ok().flatten();
+
     // Создание authorization code
-    match oauth_service.create_authorization_code(
-        &form.client_id,
-        user_id,
-        &form.redirect_uri,
-        &form.scope,
-        form.code_challenge.clone(),
-        form.code_challenge_method.clone(),
-    ).await {
+    redirect_with_code(
+        &oauth_service,
+        &config.issuer,
+        NewAuthorizationCode {
+            client: &client,
+            user_id,
+            redirect_uri: &form.redirect_uri,
+            scope: &granted.join(" "),
+            code_challenge,
+            code_challenge_method,
+            auth_context,
+        },
+        form.state.as_deref(),
+    ).await
+}
+
+// Создание authorization code и redirect обратно в приложение
+async fn redirect_with_code(
+    oauth_service: &OAuthService,
+    issuer: &str,
+    params: NewAuthorizationCode<'_>,
+    state: Option<&str>,
+) -> HttpResponse {
+    let redirect_uri = params.redirect_uri.to_string();
+
+    match oauth_service.create_authorization_code(params).await {
         Ok(auth_code) => {
-            // Redirect обратно в приложение с кодом
-            let mut redirect_url = format!("{}?code={}", form.redirect_uri, auth_code.code);
-            if let Some(state) = &form.state {
-                redirect_url.push_str(&format!("&state={}", state));
+            let mut redirect_url = format!("{}?code={}", redirect_uri, auth_code.code);
+            if let Some(state) = state {
+                redirect_url.push_str(&format!("&state={}", urlencoding::encode(state)));
             }
+            redirect_url.push_str(&format!("&iss={}", urlencoding::encode(issuer)));
             HttpResponse::Found()
                 .append_header(("Location", redirect_url))
                 .finish()
         }
         Err(_) => {
-            build_error_redirect(&form.redirect_uri, "server_error", Some("Failed to create authorization code"), form.state.as_deref())
+            build_error_redirect(&redirect_uri, issuer, "server_error", Some("Failed to create authorization code"), state)
         }
     }
 }
 
+// Элемент списка scopes на consent screen
+fn scope_item_html(scope: &ScopeDescription) -> String {
+    format!(
+        "<li>{}<br><small>{}</small></li>",
+        html_escape(&scope.description),
+        html_escape(&scope.scope_name)
+    )
+}
+
+// Элемент списка scopes, требующих решения пользователя
+fn consent_item_html(scope: &ScopeDescription, optional: bool, checked: bool) -> String {
+    let (control, note) = if optional {
+        (
+            format!(
+                r#"<input type="checkbox" class="optional-scope" value="{}"{}>"#,
+                html_escape(&scope.scope_name),
+                if checked { " checked" } else { "" }
+            ),
+            "",
+        )
+    } else {
+        (
+            r#"<input type="checkbox" checked disabled>"#.to_string(),
+            r#" <span class="required">(обязательно)</span>"#,
+        )
+    };
+
+    format!(
+        "<li><label>{} {}{}</label><br><small>{}</small></li>",
+        control,
+        html_escape(&scope.description),
+        note,
+        html_escape(&scope.scope_name)
+    )
+}
+
+// Экранирование текста для вставки в HTML
+pub(crate) fn html_escape(value: &str) -> String {
+    value.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#x27;")
+}
+
+// Пустое значение параметра формы равносильно его отсутствию
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value.clone().filter(|v| !v.is_empty())
+}
+
 // POST /oauth/token - обмен кода/refresh token на access token
 pub async fn token(
     form: web::Form<TokenRequest>,
     oauth_service: web::Data<OAuthService>,
     client_service: web::Data<ClientService>,
+    scope_service: web::Data<ScopeService>,
 ) -> impl Responder {
     // Получение client_id и client_secret
     let client_id = form.client_id.as_deref().unwrap_or("");
@@ -265,12 +553,35 @@ pub async fn token(
                 Err(e) => {
                     HttpResponse::BadRequest().json(OAuthErrorResponse {
                         error: e.to_string(),
-                        error_description: Some("Failed to exchange code for tokens".to_string()),
+                        error_description: Some(e.description().unwrap_or("Failed to exchange code for tokens").to_string()),
                     })
                 }
             }
         }
         "client_credentials" => {
+            if !OAuthService::client_credentials_allowed(&client) {
+                return HttpResponse::BadRequest().json(OAuthErrorResponse {
+                    error: "unauthorized_client".to_string(),
+                    error_description: Some("Client is not allowed to use client_credentials".to_string()),
+                });
+            }
+
+            // Scope проверяется по настройкам клиента и реестру scopes
+            let scope = form.scope.as_deref().unwrap_or("");
+            if client_service.validate_scope(&client, scope).is_err() {
+                return HttpResponse::BadRequest().json(OAuthErrorResponse {
+                    error: "invalid_scope".to_string(),
+                    error_description: Some("Requested scope not allowed".to_string()),
+                });
+            }
+
+            if let Err(e) = scope_service.validate_scopes(&parse_scope(scope), client.is_first_party).await {
+                return HttpResponse::BadRequest().json(OAuthErrorResponse {
+                    error: "invalid_scope".to_string(),
+                    error_description: Some(e.to_string()),
+                });
+            }
+
             match oauth_service.issue_client_credentials_token(&client, form.scope.as_deref()).await {
                 Ok(token_response) => HttpResponse::Ok().json(token_response),
                 Err(e) => {
@@ -292,12 +603,12 @@ pub async fn token(
                 }
             };
 
-            match oauth_service.refresh_access_token(refresh_token, &client).await {
+            match oauth_service.refresh_access_token(refresh_token, &client, form.scope.as_deref()).await {
                 Ok(token_response) => HttpResponse::Ok().json(token_response),
                 Err(e) => {
                     HttpResponse::BadRequest().json(OAuthErrorResponse {
                         error: e.to_string(),
-                        error_description: Some("Failed to refresh token".to_string()),
+                        error_description: Some(e.description().unwrap_or("Failed to refresh token").to_string()),
                     })
                 }
             }
@@ -337,9 +648,42 @@ pub async fn revoke(
     }
 }
 
+// POST /oauth/introspect - introspection токена (RFC 7662)
+pub async fn introspect(
+    form: web::Form<IntrospectionRequest>,
+    token_service: web::Data<TokenService>,
+    client_service: web::Data<ClientService>,
+) -> impl Responder {
+    // Introspection доступен только аутентифицированным конфиденциальным клиентам
+    let client_id = form.client_id.as_deref().unwrap_or("");
+    let client_secret = form.client_secret.as_deref().unwrap_or("");
+
+    match client_service.validate_client_credentials(client_id, client_secret).await {
+        Ok(client) if client.is_confidential => {}
+        _ => {
+            return HttpResponse::Unauthorized().json(OAuthErrorResponse {
+                error: "invalid_client".to_string(),
+                error_description: Some("Invalid client credentials".to_string()),
+            });
+        }
+    }
+
+    match token_service.introspect(&form.token, form.token_type_hint.as_deref()).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(e) => {
+            eprintln!("Database error during introspection: {}", e);
+            HttpResponse::InternalServerError().json(OAuthErrorResponse {
+                error: "server_error".to_string(),
+                error_description: Some("Failed to introspect token".to_string()),
+            })
+        }
+    }
+}
+
 // POST /oauth/clients - регистрация нового OAuth клиента (административный endpoint)
 pub async fn register_client(
     client_service: web::Data<ClientService>,
+    scope_service: web::Data<ScopeService>,
     request: web::Json<CreateClientRequest>,
 ) -> impl Responder {
     // В продакшене здесь должна быть проверка прав администратора
@@ -351,15 +695,45 @@ pub async fn register_client(
         });
     }
 
+    // Все scopes клиента должны быть в реестре; новый клиент не является first-party
+    let allowed_scopes: Vec<&str> = request.allowed_scopes.iter().map(String::as_str).collect();
+    match scope_service.validate_scopes(&allowed_scopes, false).await {
+        Ok(()) => {}
+        Err(ScopeError::DatabaseError(e)) => {
+            eprintln!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(OAuthErrorResponse {
+                error: "server_error".to_string(),
+                error_description: Some("Failed to register client".to_string()),
+            });
+        }
+        Err(e) => {
+            return HttpResponse::BadRequest().json(OAuthErrorResponse {
+                error: "invalid_scope".to_string(),
+                error_description: Some(e.to_string()),
+            });
+        }
+    }
+
     match client_service.register_client(request.into_inner()).await {
         Ok((client, client_secret)) => {
             HttpResponse::Created().json(CreateClientResponse {
+                access_token_format: client.token_format(),
                 client_id: client.client_id,
                 client_secret,
                 client_name: client.client_name,
                 redirect_uris: client.redirect_uris,
                 allowed_scopes: client.allowed_scopes,
                 grant_types: client.grant_types,
+                access_token_ttl: client.access_token_ttl,
+                refresh_token_ttl: client.refresh_token_ttl,
+                refresh_token_absolute_ttl: client.refresh_token_absolute_ttl,
+                authorization_code_ttl: client.authorization_code_ttl,
+            })
+        }
+        Err(e @ ClientError::RedirectUriRejected(..)) => {
+            HttpResponse::BadRequest().json(OAuthErrorResponse {
+                error: "invalid_redirect_uri".to_string(),
+                error_description: Some(e.to_string()),
             })
         }
         Err(e) => {
@@ -373,7 +747,7 @@ pub async fn register_client(
 }
 
 // Helper function to build error redirect
-fn build_error_redirect(redirect_uri: &str, error: &str, description: Option<&str>, state: Option<&str>) -> HttpResponse {
+fn build_error_redirect(redirect_uri: &str, issuer: &str, error: &str, description: Option<&str>, state: Option<&str>) -> HttpResponse {
     let mut redirect_url = format!("{}?error={}", redirect_uri, error);
 
     if let Some(desc) = description {
@@ -381,9 +755,12 @@ fn build_error_redirect(redirect_uri: &str, error: &str, description: Option<&st
     }
 
     if let Some(state) = state {
-        redirect_url.push_str(&format!("&state={}", state));
+        redirect_url.push_str(&format!("&state={}", urlencoding::encode(state)));
     }
 
+    // RFC 9207: идентификатор issuer для защиты от mix-up атак
+    redirect_url.push_str(&format!("&iss={}", urlencoding::encode(issuer)));
+
     HttpResponse::Found()
         .append_header(("Location", redirect_url))
         .finish()
@@ -397,6 +774,7 @@ pub fn configure_oauth_routes(cfg: &mut web::ServiceConfig) {
             .route("/authorize", web::post().to(authorize_post))
             .route("/token", web::post().to(token))
             .route("/revoke", web::post().to(revoke))
+            .route("/introspect", web::post().to(introspect))
             .route("/clients", web::post().to(register_client))
     );
 }
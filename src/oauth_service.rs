@@ -1,12 +1,12 @@
 use sqlx::{Pool, Postgres};
 use uuid::Uuid;
-use chrono::{Utc, Duration};
+use chrono::{DateTime, Utc, Duration};
 use rand::{thread_rng, Rng};
 use rand::distributions::Alphanumeric;
-use sha2::{Sha256, Digest};
-use base64::{Engine as _, engine::general_purpose};
-use crate::models::{AuthorizationCode, TokenResponse, OAuthClient};
-use crate::token_service::TokenService;
+use crate::models::{AuthorizationCode, TokenResponse, OAuthClient, AuthContext, AccessTokenFormat, UserStatus};
+use crate::token_service::{TokenService, NewRefreshToken};
+use crate::pkce::{PkcePolicy, PkceError, ClientPkceRules};
+use crate::scope_utils::{contains_scope, is_subset, parse_scope, OFFLINE_ACCESS_SCOPE};
 
 #[derive(Debug)]
 pub enum OAuthError {
@@ -20,6 +20,8 @@ pub enum OAuthError {
     CodeExpired,
     CodeAlreadyUsed,
     InvalidCodeVerifier,
+    EmailNotVerified,
+    UserSuspended,
 }
 
 impl std::fmt::Display for OAuthError {
@@ -35,20 +37,56 @@ impl std::fmt::Display for OAuthError {
             OAuthError::CodeExpired => write!(f, "Code expired"),
             OAuthError::CodeAlreadyUsed => write!(f, "Code already used"),
             OAuthError::InvalidCodeVerifier => write!(f, "Invalid code verifier"),
+            OAuthError::EmailNotVerified => write!(f, "invalid_grant"),
+            OAuthError::UserSuspended => write!(f, "invalid_grant"),
+        }
+    }
+}
+
+impl OAuthError {
+    // Пояснение для error_description, если оно точнее общего сообщения handler
+    pub fn description(&self) -> Option<&'static str> {
+        match self {
+            OAuthError::EmailNotVerified => Some("User email address is not verified"),
+            OAuthError::UserSuspended => Some("User account is suspended"),
+            _ => None,
         }
     }
 }
 
 impl std::error::Error for OAuthError {}
 
+// Параметры создания authorization code
+pub struct NewAuthorizationCode<'a> {
+    pub client: &'a OAuthClient,
+    pub user_id: Uuid,
+    pub redirect_uri: &'a str,
+    pub scope: &'a str,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub auth_context: Option<AuthContext>,
+}
+
+// Колонки oauth_authorization_codes, возвращаемые во всех запросах
+const CODE_COLUMNS: &str = "id, code, client_id, user_id, redirect_uri, scope, \
+    code_challenge, code_challenge_method, expires_at, used, created_at, auth_time, acr, amr";
+
 pub struct OAuthService {
     pool: Pool<Postgres>,
     token_service: TokenService,
+    pkce_policy: PkcePolicy,
+    // Выдавать токены только пользователям с подтвержденным email
+    require_verified_email: bool,
 }
 
 impl OAuthService {
-    pub fn new(pool: Pool<Postgres>, token_service: TokenService) -> Self {
-        Self { pool, token_service }
+    pub fn new(
+        pool: Pool<Postgres>,
+        token_service: TokenService,
+        pkce_policy: PkcePolicy,
+        require_verified_email: bool,
+    ) -> Self {
+        Self { pool, token_service, pkce_policy, require_verified_email }
     }
 
     // Генерация authorization code
@@ -63,40 +101,44 @@ impl OAuthService {
     // Создание authorization code в БД
     pub async fn create_authorization_code(
         &self,
-        client_id: &str,
-        user_id: Uuid,
-        redirect_uri: &str,
-        scope: &str,
-        code_challenge: Option<String>,
-        code_challenge_method: Option<String>,
+        params: NewAuthorizationCode<'_>,
     ) -> Result<AuthorizationCode, OAuthError> {
         let code = Self::generate_authorization_code();
         let id = Uuid::new_v4();
         let now = Utc::now();
-        let expires_at = now + Duration::minutes(10); // Authorization code valid for 10 minutes
-
-        let auth_code = sqlx::query_as::<_, AuthorizationCode>(
+        let ttl = self.token_service.lifetimes_for(params.client).authorization_code_ttl;
+        let expires_at = now + Duration::seconds(ttl);
+        let (auth_time, acr, amr) = match params.auth_context {
+            Some(ctx) => (Some(ctx.auth_time), Some(ctx.acr), Some(ctx.amr)),
+            None => (None, None, None),
+        };
+
+        let auth_code = sqlx::query_as::<_, AuthorizationCode>(&format!(
             r#"
             INSERT INTO oauth_authorization_codes (
                 id, code, client_id, user_id, redirect_uri, scope,
-                code_challenge, code_challenge_method, expires_at, used, created_at
+                code_challenge, code_challenge_method, expires_at, used, created_at,
+                auth_time, acr, amr
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
-            RETURNING id, code, client_id, user_id, redirect_uri, scope,
-                      code_challenge, code_challenge_method, expires_at, used, created_at
-            "#
-        )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+            RETURNING {}
+            "#,
+            CODE_COLUMNS
+        ))
         .bind(id)
         .bind(&code)
-        .bind(client_id)
-        .bind(user_id)
-        .bind(redirect_uri)
-        .bind(scope)
-        .bind(code_challenge)
-        .bind(code_challenge_method)
+        .bind(&params.client.client_id)
+        .bind(params.user_id)
+        .bind(params.redirect_uri)
+        .bind(params.scope)
+        .bind(params.code_challenge)
+        .bind(params.code_challenge_method)
         .bind(expires_at)
         .bind(false)
         .bind(now)
+        .bind(auth_time)
+        .bind(acr)
+        .bind(amr)
         .fetch_one(&self.pool)
         .await
         .map_err(OAuthError::DatabaseError)?;
@@ -106,14 +148,14 @@ impl OAuthService {
 
     // Получение authorization code
     async fn get_authorization_code(&self, code: &str) -> Result<Option<AuthorizationCode>, OAuthError> {
-        let auth_code = sqlx::query_as::<_, AuthorizationCode>(
+        let auth_code = sqlx::query_as::<_, AuthorizationCode>(&format!(
             r#"
-            SELECT id, code, client_id, user_id, redirect_uri, scope,
-                   code_challenge, code_challenge_method, expires_at, used, created_at
+            SELECT {}
             FROM oauth_authorization_codes
             WHERE code = $1
-            "#
-        )
+            "#,
+            CODE_COLUMNS
+        ))
         .bind(code)
         .fetch_optional(&self.pool)
         .await
@@ -133,21 +175,96 @@ impl OAuthService {
         Ok(())
     }
 
-    // Проверка PKCE code_verifier
-    fn verify_pkce(&self, code_verifier: &str, code_challenge: &str, method: &str) -> bool {
-        match method {
-            "S256" => {
-                let mut hasher = Sha256::new();
-                hasher.update(code_verifier.as_bytes());
-                let result = hasher.finalize();
-                let computed_challenge = general_purpose::URL_SAFE_NO_PAD.encode(result);
-                computed_challenge == code_challenge
-            }
-            "plain" => code_verifier == code_challenge,
-            _ => false,
+    // Правила PKCE для клиента с учетом глобальной политики
+    pub fn pkce_rules(&self, client: &OAuthClient) -> ClientPkceRules {
+        self.pkce_policy.rules_for(client)
+    }
+
+    // Проверка владельца гранта: аккаунт не приостановлен и email подтвержден (при REQUIRE_VERIFIED_EMAIL)
+    async fn ensure_user_allowed(&self, user_id: Option<Uuid>) -> Result<(), OAuthError> {
+        let Some(user_id) = user_id else {
+            return Ok(());
+        };
+
+        let (verified, status) = sqlx::query_as::<_, (bool, String)>(
+            "SELECT email_verified, status FROM users WHERE id = $1"
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(OAuthError::DatabaseError)?
+        .ok_or(OAuthError::InvalidGrant)?;
+
+        if UserStatus::from_db(&status) == UserStatus::Suspended {
+            Err(OAuthError::UserSuspended)
+        } else if self.require_verified_email && !verified {
+            Err(OAuthError::EmailNotVerified)
+        } else {
+            Ok(())
         }
     }
 
+    // Refresh token выдается только пользовательским грантам с offline_access,
+    // если клиенту разрешен grant_type refresh_token
+    fn refresh_token_allowed(client: &OAuthClient, user_id: Option<Uuid>, grant_scope: &str) -> bool {
+        user_id.is_some()
+            && client.grant_types.iter().any(|g| g == "refresh_token")
+            && contains_scope(grant_scope, OFFLINE_ACCESS_SCOPE)
+    }
+
+    // Генерация и сохранение access (и опционально refresh) токена.
+    // refresh_absolute_expires_at — предел цепочки refresh token при ротации
+    async fn issue_tokens(
+        &self,
+        client: &OAuthClient,
+        user_id: Option<Uuid>,
+        scope: &str,
+        grant_scope: &str,
+        auth_context: Option<&AuthContext>,
+        refresh_absolute_expires_at: Option<DateTime<Utc>>,
+    ) -> Result<TokenResponse, OAuthError> {
+        let lifetimes = self.token_service.lifetimes_for(client);
+        let claims = self.token_service.build_claims(
+            user_id,
+            &client.client_id,
+            scope,
+            auth_context,
+            lifetimes.access_token_ttl,
+        );
+        let access_token = match client.token_format() {
+            AccessTokenFormat::Jwt => self.token_service.create_jwt(&claims)
+                .map_err(|_| OAuthError::InvalidRequest)?,
+            AccessTokenFormat::Opaque => self.token_service.generate_opaque_token(),
+        };
+
+        let refresh_token = Self::refresh_token_allowed(client, user_id, grant_scope)
+            .then(|| self.token_service.generate_refresh_token());
+        let (refresh_expires_at, absolute_expires_at) =
+            lifetimes.refresh_expiry(Utc::now(), refresh_absolute_expires_at);
+        let new_refresh = refresh_token.as_deref().map(|token| NewRefreshToken {
+            token,
+            expires_at: refresh_expires_at,
+            absolute_expires_at,
+        });
+
+        // Сохранение токенов в БД
+        self.token_service.store_tokens(
+            &access_token,
+            new_refresh.as_ref(),
+            user_id,
+            &claims,
+            grant_scope,
+        ).await.map_err(OAuthError::DatabaseError)?;
+
+        Ok(TokenResponse {
+            access_token,
+            token_type: "Bearer".to_string(),
+            expires_in: lifetimes.access_token_ttl,
+            refresh_token,
+            scope: scope.to_string(),
+        })
+    }
+
     // Обмен authorization code на токены (Authorization Code Flow)
     pub async fn exchange_code_for_tokens(
         &self,
@@ -178,44 +295,38 @@ impl OAuthService {
             return Err(OAuthError::InvalidGrant);
         }
 
-        // Проверка PKCE если использовался
-        if let Some(challenge) = &auth_code.code_challenge {
-            let verifier = code_verifier.ok_or(OAuthError::InvalidRequest)?;
-            let method = auth_code.code_challenge_method.as_deref().unwrap_or("plain");
+        // Проверка PKCE по политике клиента
+        self.pkce_rules(client)
+            .verify(
+                auth_code.code_challenge.as_deref(),
+                auth_code.code_challenge_method.as_deref(),
+                code_verifier.as_deref(),
+            )
+            .map_err(|e| match e {
+                PkceError::ChallengeRequired | PkceError::MissingVerifier | PkceError::MalformedVerifier => OAuthError::InvalidRequest,
+                _ => OAuthError::InvalidCodeVerifier,
+            })?;
 
-            if !self.verify_pkce(&verifier, challenge, method) {
-                return Err(OAuthError::InvalidCodeVerifier);
-            }
-        }
+        self.ensure_user_allowed(Some(auth_code.user_id)).await?;
 
         // Пометить код как использованный
         self.mark_code_as_used(code).await?;
 
         // Генерация токенов
-        let access_token = self.token_service.create_jwt(
+        self.issue_tokens(
+            client,
             Some(auth_code.user_id),
-            &client.client_id,
             &auth_code.scope,
-        ).map_err(|_| OAuthError::InvalidRequest)?;
-
-        let refresh_token = self.token_service.generate_refresh_token();
-
-        // Сохранение токенов в БД
-        self.token_service.store_tokens(
-            &access_token,
-            Some(&refresh_token),
-            &client.client_id,
-            Some(auth_code.user_id),
             &auth_code.scope,
-        ).await.map_err(OAuthError::DatabaseError)?;
+            auth_code.auth_context().as_ref(),
+            None,
+        ).await
+    }
 
-        Ok(TokenResponse {
-            access_token,
-            token_type: "Bearer".to_string(),
-            expires_in: self.token_service.get_access_token_ttl(),
-            refresh_token: Some(refresh_token),
-            scope: auth_code.scope,
-        })
+    // Client credentials доступен только конфиденциальному клиенту с этим grant_type:
+    // публичный клиент не может подтвердить свою личность без пользователя
+    pub fn client_credentials_allowed(client: &OAuthClient) -> bool {
+        client.is_confidential && client.grant_types.iter().any(|g| g == "client_credentials")
     }
 
     // Client Credentials Flow
@@ -224,31 +335,14 @@ impl OAuthService {
         client: &OAuthClient,
         scope: Option<&str>,
     ) -> Result<TokenResponse, OAuthError> {
-        let scope = scope.unwrap_or("").to_string();
-
-        // Генерация access token
-        let access_token = self.token_service.create_jwt(
-            None,
-            &client.client_id,
-            &scope,
-        ).map_err(|_| OAuthError::InvalidRequest)?;
+        if !Self::client_credentials_allowed(client) {
+            return Err(OAuthError::UnauthorizedClient);
+        }
 
-        // Сохранение токена в БД (без refresh token для client credentials)
-        self.token_service.store_tokens(
-            &access_token,
-            None,
-            &client.client_id,
-            None,
-            &scope,
-        ).await.map_err(OAuthError::DatabaseError)?;
+        let scope = scope.unwrap_or("").to_string();
 
-        Ok(TokenResponse {
-            access_token,
-            token_type: "Bearer".to_string(),
-            expires_in: self.token_service.get_access_token_ttl(),
-            refresh_token: None,
-            scope,
-        })
+        // Без refresh token для client credentials
+        self.issue_tokens(client, None, &scope, &scope, None, None).await
     }
 
     // Refresh Token Flow
@@ -256,7 +350,12 @@ impl OAuthService {
         &self,
         refresh_token: &str,
         client: &OAuthClient,
+        requested_scope: Option<&str>,
     ) -> Result<TokenResponse, OAuthError> {
+        if !client.grant_types.iter().any(|g| g == "refresh_token") {
+            return Err(OAuthError::UnauthorizedClient);
+        }
+
         // Получение старого токена
         let old_token = self.token_service.get_token_by_refresh(refresh_token)
             .await
@@ -268,35 +367,30 @@ impl OAuthService {
             return Err(OAuthError::InvalidClient);
         }
 
+        // Сужение scope: запрошенный scope должен входить в исходный грант
+        let grant_scope = old_token.granted_scope().to_string();
+        let scope = match requested_scope.map(str::trim).filter(|s| !s.is_empty()) {
+            Some(requested) if is_subset(requested, &grant_scope) => parse_scope(requested).join(" "),
+            Some(_) => return Err(OAuthError::InvalidScope),
+            None => grant_scope.clone(),
+        };
+
+        self.ensure_user_allowed(old_token.user_id).await?;
+
         // Отзыв старого токена
-        self.token_service.revoke_token(&old_token.access_token)
+        self.token_service.revoke_token_record(old_token.id)
             .await
             .map_err(OAuthError::DatabaseError)?;
 
-        // Генерация новых токенов
-        let new_access_token = self.token_service.create_jwt(
+        // Генерация новых токенов с исходным контекстом аутентификации;
+        // новый refresh token сохраняет scope и абсолютный срок исходного гранта
+        self.issue_tokens(
+            client,
             old_token.user_id,
-            &client.client_id,
-            &old_token.scope,
-        ).map_err(|_| OAuthError::InvalidRequest)?;
-
-        let new_refresh_token = self.token_service.generate_refresh_token();
-
-        // Сохранение новых токенов
-        self.token_service.store_tokens(
-            &new_access_token,
-            Some(&new_refresh_token),
-            &client.client_id,
-            old_token.user_id,
-            &old_token.scope,
-        ).await.map_err(OAuthError::DatabaseError)?;
-
-        Ok(TokenResponse {
-            access_token: new_access_token,
-            token_type: "Bearer".to_string(),
-            expires_in: self.token_service.get_access_token_ttl(),
-            refresh_token: Some(new_refresh_token),
-            scope: old_token.scope,
-        })
+            &scope,
+            &grant_scope,
+            old_token.auth_context().as_ref(),
+            old_token.refresh_absolute_expires_at,
+        ).await
     }
 }
@@ -0,0 +1,304 @@
+use actix_web::{web, HttpResponse, Responder};
+use actix_session::Session;
+use uuid::Uuid;
+use validator::Validate;
+use crate::account_handlers::session_user_id;
+use crate::models::{ErrorResponse, PasskeyRegistrationRequest};
+use crate::passkey_service::{PasskeyError, PasskeyService};
+use crate::services::UserService;
+use crate::webauthn;
+
+// Преобразование ошибки PasskeyService в HTTP ответ
+fn passkey_error_response(error: PasskeyError) -> HttpResponse {
+    match error {
+        PasskeyError::AlreadyRegistered => HttpResponse::Conflict().json(ErrorResponse {
+            error: error.to_string(),
+        }),
+        PasskeyError::WebAuthn(_) | PasskeyError::InvalidChallenge | PasskeyError::UnknownCredential => {
+            HttpResponse::BadRequest().json(ErrorResponse {
+                error: error.to_string(),
+            })
+        }
+        PasskeyError::DatabaseError(_) => {
+            eprintln!("Passkey error: {}", error);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            })
+        }
+    }
+}
+
+// GET /auth/passkeys/setup - страница управления ключами доступа
+pub async fn passkeys_page(session: Session) -> impl Responder {
+    if session_user_id(&session).is_err() {
+        return HttpResponse::Found()
+            .append_header(("Location", "/auth/login?return_to=%2Fauth%2Fpasskeys%2Fsetup"))
+            .finish();
+    }
+
+    let html = r#"
+<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="UTF-8">
+    <title>Ключи доступа</title>
+    <style>
+        body { font-family: Arial, sans-serif; max-width: 500px; margin: 50px auto; padding: 20px; }
+        h1 { text-align: center; }
+        form { display: flex; flex-direction: column; gap: 15px; margin-top: 20px; }
+        input { padding: 10px; border: 1px solid #ddd; border-radius: 4px; }
+        button { padding: 10px; background-color: #007bff; color: white; border: none; border-radius: 4px; cursor: pointer; }
+        .passkey { border: 1px solid #ddd; border-radius: 8px; padding: 15px; margin: 10px 0; }
+        .passkey h3 { margin: 0 0 10px 0; }
+        .meta { color: #666; font-size: 14px; }
+        .passkey button { background-color: #dc3545; margin-top: 10px; }
+        .empty { text-align: center; color: #666; }
+        .error { color: red; text-align: center; }
+    </style>
+</head>
+<body>
+    <h1>Ключи доступа</h1>
+    <div id="passkeys"></div>
+
+    <form id="registerForm">
+        <input type="text" name="name" placeholder="Название (например, ноутбук)" maxlength="100">
+        <button type="submit">Добавить ключ доступа</button>
+    </form>
+
+    <div class="error" id="error"></div>
+
+    <script>
+        function fromBase64url(value) {
+            const base64 = value.replace(/-/g, '+').replace(/_/g, '/');
+            return Uint8Array.from(atob(base64), c => c.charCodeAt(0));
+        }
+
+        function toBase64url(buffer) {
+            const bytes = String.fromCharCode(...new Uint8Array(buffer));
+            return btoa(bytes).replace(/\+/g, '-').replace(/\//g, '_').replace(/=+$/, '');
+        }
+
+        function formatDate(value) {
+            return value ? new Date(value).toLocaleString() : 'никогда';
+        }
+
+        async function loadPasskeys() {
+            const container = document.getElementById('passkeys');
+            const response = await fetch('/auth/passkeys');
+            if (!response.ok) {
+                document.getElementById('error').textContent = 'Не удалось загрузить ключи доступа';
+                return;
+            }
+            const passkeys = await response.json();
+            container.innerHTML = '';
+            if (passkeys.length === 0) {
+                container.innerHTML = '<p class="empty">Ключи доступа не добавлены</p>';
+                return;
+            }
+            for (const passkey of passkeys) {
+                const item = document.createElement('div');
+                item.className = 'passkey';
+                const title = document.createElement('h3');
+                title.textContent = passkey.name;
+                const meta = document.createElement('div');
+                meta.className = 'meta';
+                meta.textContent = 'Добавлен: ' + formatDate(passkey.created_at) +
+                    ', последний вход: ' + formatDate(passkey.last_used_at);
+                const button = document.createElement('button');
+                button.textContent = 'Удалить';
+                button.addEventListener('click', async () => {
+                    if (!confirm('Удалить ключ доступа "' + passkey.name + '"?')) return;
+                    const result = await fetch('/auth/passkeys/' + passkey.id, { method: 'DELETE' });
+                    if (result.ok) {
+                        loadPasskeys();
+                    } else {
+                        document.getElementById('error').textContent = 'Не удалось удалить ключ доступа';
+                    }
+                });
+                item.append(title, meta, button);
+                container.appendChild(item);
+            }
+        }
+
+        document.getElementById('registerForm').addEventListener('submit', async (e) => {
+            e.preventDefault();
+            document.getElementById('error').textContent = '';
+            if (!window.PublicKeyCredential) {
+                document.getElementById('error').textContent = 'Браузер не поддерживает ключи доступа';
+                return;
+            }
+            const formData = new FormData(e.target);
+
+            try {
+                const optionsResponse = await fetch('/auth/passkeys/register/options', { method: 'POST' });
+                const options = await optionsResponse.json();
+                if (!optionsResponse.ok) {
+                    document.getElementById('error').textContent = options.error || 'Ошибка';
+                    return;
+                }
+                options.challenge = fromBase64url(options.challenge);
+                options.user.id = fromBase64url(options.user.id);
+                options.excludeCredentials = options.excludeCredentials.map(c => ({ ...c, id: fromBase64url(c.id) }));
+
+                const credential = await navigator.credentials.create({ publicKey: options });
+                const response = await fetch('/auth/passkeys/register', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({
+                        name: formData.get('name') || null,
+                        id: credential.id,
+                        client_data_json: toBase64url(credential.response.clientDataJSON),
+                        attestation_object: toBase64url(credential.response.attestationObject)
+                    })
+                });
+                const result = await response.json();
+                if (!response.ok) {
+                    document.getElementById('error').textContent = result.error || 'Ошибка регистрации';
+                    return;
+                }
+                e.target.reset();
+                loadPasskeys();
+            } catch (err) {
+                document.getElementById('error').textContent = 'Регистрация ключа доступа отменена';
+            }
+        });
+
+        loadPasskeys();
+    </script>
+</body>
+</html>
+    "#;
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html)
+}
+
+// GET /auth/passkeys - ключи доступа текущего пользователя
+pub async fn list_passkeys(
+    passkey_service: web::Data<PasskeyService>,
+    session: Session,
+) -> impl Responder {
+    let user_id = match session_user_id(&session) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    match passkey_service.list_passkeys(user_id).await {
+        Ok(passkeys) => HttpResponse::Ok().json(passkeys),
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            })
+        }
+    }
+}
+
+// POST /auth/passkeys/register/options - параметры для navigator.credentials.create
+pub async fn registration_options(
+    user_service: web::Data<UserService>,
+    passkey_service: web::Data<PasskeyService>,
+    session: Session,
+) -> impl Responder {
+    let user_id = match session_user_id(&session) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let user = match user_service.get_user_by_id(user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "User not found".to_string(),
+            })
+        }
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            });
+        }
+    };
+
+    match passkey_service.registration_options(&user).await {
+        Ok(options) => HttpResponse::Ok().json(options),
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            })
+        }
+    }
+}
+
+// POST /auth/passkeys/register - проверка ответа аутентификатора и сохранение ключа
+pub async fn register_passkey(
+    passkey_service: web::Data<PasskeyService>,
+    session: Session,
+    request: web::Json<PasskeyRegistrationRequest>,
+) -> impl Responder {
+    let user_id = match session_user_id(&session) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+    if let Err(errors) = request.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Validation error: {}", errors),
+        });
+    }
+
+    let (client_data_json, attestation_object) = match (
+        webauthn::decode_b64url(&request.client_data_json),
+        webauthn::decode_b64url(&request.attestation_object),
+    ) {
+        (Some(client_data_json), Some(attestation_object)) => (client_data_json, attestation_object),
+        _ => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                error: "Invalid base64url encoding".to_string(),
+            });
+        }
+    };
+
+    match passkey_service
+        .finish_registration(user_id, request.name.as_deref(), &client_data_json, &attestation_object)
+        .await
+    {
+        Ok(passkey) => HttpResponse::Created().json(passkey),
+        Err(e) => passkey_error_response(e),
+    }
+}
+
+// DELETE /auth/passkeys/{id} - удаление ключа доступа
+pub async fn delete_passkey(
+    passkey_service: web::Data<PasskeyService>,
+    session: Session,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let user_id = match session_user_id(&session) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    match passkey_service.delete_passkey(user_id, path.into_inner()).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Passkey not found".to_string(),
+        }),
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            })
+        }
+    }
+}
+
+// Маршруты управления ключами доступа (подключаются внутри scope /auth)
+pub fn configure_passkey_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/passkeys/setup", web::get().to(passkeys_page))
+        .route("/passkeys", web::get().to(list_passkeys))
+        .route("/passkeys/register/options", web::post().to(registration_options))
+        .route("/passkeys/register", web::post().to(register_passkey))
+        .route("/passkeys/{id}", web::delete().to(delete_passkey));
+}
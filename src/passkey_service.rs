@@ -0,0 +1,317 @@
+// Ключи доступа (passkeys): регистрация, вход и хранение challenges регистрации WebAuthn
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+use crate::config::WebAuthnConfig;
+use crate::models::{PasskeyResponse, User, WebAuthnCredential};
+use crate::secrets::hash_secret;
+use crate::webauthn::{
+    self, Assertion, WebAuthnError, CEREMONY_CREATE, COSE_ALG_ES256,
+};
+
+// Время на ответ аутентификатора
+const CHALLENGE_TTL_SECS: i64 = 300;
+const DEFAULT_PASSKEY_NAME: &str = "Ключ доступа";
+
+// Колонки webauthn_credentials, возвращаемые во всех запросах
+const CREDENTIAL_COLUMNS: &str = "id, user_id, credential_id, public_key, sign_count, name, created_at, last_used_at";
+
+#[derive(Debug)]
+pub enum PasskeyError {
+    DatabaseError(sqlx::Error),
+    WebAuthn(WebAuthnError),
+    // Challenge неизвестен, истек, уже использован или выдан для другого входа
+    InvalidChallenge,
+    UnknownCredential,
+    AlreadyRegistered,
+}
+
+impl std::fmt::Display for PasskeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PasskeyError::DatabaseError(e) => write!(f, "Database error: {}", e),
+            PasskeyError::WebAuthn(e) => write!(f, "WebAuthn verification failed: {}", e),
+            PasskeyError::InvalidChallenge => write!(f, "Invalid or expired challenge"),
+            PasskeyError::UnknownCredential => write!(f, "Unknown passkey"),
+            PasskeyError::AlreadyRegistered => write!(f, "Passkey is already registered"),
+        }
+    }
+}
+
+impl std::error::Error for PasskeyError {}
+
+impl From<sqlx::Error> for PasskeyError {
+    fn from(e: sqlx::Error) -> Self {
+        PasskeyError::DatabaseError(e)
+    }
+}
+
+impl From<WebAuthnError> for PasskeyError {
+    fn from(e: WebAuthnError) -> Self {
+        PasskeyError::WebAuthn(e)
+    }
+}
+
+// Challenge входа по ключу доступа. Хранится в зашифрованной cookie-сессии, а не в БД:
+// параметры входа запрашиваются без аутентификации и не должны писать в базу
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginChallenge {
+    pub challenge: String,
+    pub user_id: Option<Uuid>,
+    pub expires_at: i64,
+}
+
+pub struct PasskeyService {
+    pool: Pool<Postgres>,
+    config: WebAuthnConfig,
+}
+
+impl PasskeyService {
+    pub fn new(pool: Pool<Postgres>, config: &WebAuthnConfig) -> Self {
+        Self { pool, config: config.clone() }
+    }
+
+    // Есть ли у пользователя ключи доступа (тогда вход по паролю требует второй фактор)
+    pub async fn has_passkeys(&self, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM webauthn_credentials WHERE user_id = $1)")
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    async fn user_credentials(&self, user_id: Uuid) -> Result<Vec<WebAuthnCredential>, sqlx::Error> {
+        sqlx::query_as::<_, WebAuthnCredential>(&format!(
+            "SELECT {} FROM webauthn_credentials WHERE user_id = $1 ORDER BY created_at",
+            CREDENTIAL_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn list_passkeys(&self, user_id: Uuid) -> Result<Vec<PasskeyResponse>, sqlx::Error> {
+        Ok(self.user_credentials(user_id).await?.into_iter().map(PasskeyResponse::from).collect())
+    }
+
+    // Удаление ключа доступа пользователя; false, если ключ не найден
+    pub async fn delete_passkey(&self, user_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM webauthn_credentials WHERE id = $1 AND user_id = $2")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Параметры для navigator.credentials.create (PublicKeyCredentialCreationOptions)
+    pub async fn registration_options(&self, user: &User) -> Result<Value, sqlx::Error> {
+        let challenge = self.store_challenge(CEREMONY_CREATE, Some(user.id)).await?;
+        let exclude: Vec<Value> = self
+            .user_credentials(user.id)
+            .await?
+            .into_iter()
+            .map(|c| serde_json::json!({ "type": "public-key", "id": c.credential_id }))
+            .collect();
+
+        Ok(serde_json::json!({
+            "challenge": challenge,
+            "rp": { "id": self.config.rp_id, "name": self.config.rp_name },
+            "user": {
+                "id": webauthn::encode_b64url(user.id.as_bytes()),
+                "name": user.email,
+                "displayName": user.username,
+            },
+            "pubKeyCredParams": [{ "type": "public-key", "alg": COSE_ALG_ES256 }],
+            "timeout": CHALLENGE_TTL_SECS * 1000,
+            "attestation": "none",
+            "authenticatorSelection": { "residentKey": "preferred", "userVerification": "preferred" },
+            "excludeCredentials": exclude,
+        }))
+    }
+
+    // Завершение регистрации: проверка ответа аутентификатора и сохранение ключа
+    pub async fn finish_registration(
+        &self,
+        user_id: Uuid,
+        name: Option<&str>,
+        client_data_json: &[u8],
+        attestation_object: &[u8],
+    ) -> Result<PasskeyResponse, PasskeyError> {
+        let (challenge, challenge_user) = self.consume_challenge(CEREMONY_CREATE, client_data_json).await?;
+        if challenge_user != Some(user_id) {
+            return Err(PasskeyError::InvalidChallenge);
+        }
+
+        let registered = webauthn::verify_registration(&self.config, &challenge, client_data_json, attestation_object)?;
+        let name = name.map(str::trim).filter(|n| !n.is_empty()).unwrap_or(DEFAULT_PASSKEY_NAME);
+
+        let credential = sqlx::query_as::<_, WebAuthnCredential>(&format!(
+            r#"
+            INSERT INTO webauthn_credentials (id, user_id, credential_id, public_key, sign_count, name, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (credential_id) DO NOTHING
+            RETURNING {}
+            "#,
+            CREDENTIAL_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(&registered.credential_id)
+        .bind(&registered.public_key)
+        .bind(registered.sign_count as i64)
+        .bind(name)
+        .bind(Utc::now())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(PasskeyError::AlreadyRegistered)?;
+
+        Ok(PasskeyResponse::from(credential))
+    }
+
+    // Параметры для navigator.credentials.get. С user_id — второй фактор после пароля
+    // (только ключи пользователя), без него — вход без пароля по discoverable ключу
+    // Возвращает challenge для сохранения в сессии
+    pub async fn authentication_options(&self, user_id: Option<Uuid>) -> Result<(Value, LoginChallenge), sqlx::Error> {
+        let challenge = webauthn::new_challenge();
+        let allow: Vec<Value> = match user_id {
+            Some(user_id) => self
+                .user_credentials(user_id)
+                .await?
+                .into_iter()
+                .map(|c| serde_json::json!({ "type": "public-key", "id": c.credential_id }))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let options = serde_json::json!({
+            "challenge": challenge,
+            "rpId": self.config.rp_id,
+            "timeout": CHALLENGE_TTL_SECS * 1000,
+            "userVerification": if user_id.is_some() { "preferred" } else { "required" },
+            "allowCredentials": allow,
+        });
+        let login_challenge = LoginChallenge {
+            challenge,
+            user_id,
+            expires_at: (Utc::now() + Duration::seconds(CHALLENGE_TTL_SECS)).timestamp(),
+        };
+
+        Ok((options, login_challenge))
+    }
+
+    // Проверка входа по ключу доступа. login_challenge — challenge из сессии (уже удаленный из нее),
+    // expected_user — пользователь, прошедший проверку пароля (второй фактор); без него требуется
+    // проверка пользователя на устройстве (PIN, биометрия). Возвращает id вошедшего пользователя
+    pub async fn finish_authentication(
+        &self,
+        login_challenge: &LoginChallenge,
+        expected_user: Option<Uuid>,
+        credential_id: &str,
+        user_handle: Option<&[u8]>,
+        assertion: &Assertion<'_>,
+    ) -> Result<Uuid, PasskeyError> {
+        if login_challenge.expires_at <= Utc::now().timestamp() || login_challenge.user_id != expected_user {
+            return Err(PasskeyError::InvalidChallenge);
+        }
+        let challenge = &login_challenge.challenge;
+
+        let credential = sqlx::query_as::<_, WebAuthnCredential>(&format!(
+            "SELECT {} FROM webauthn_credentials WHERE credential_id = $1",
+            CREDENTIAL_COLUMNS
+        ))
+        .bind(credential_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(PasskeyError::UnknownCredential)?;
+
+        if expected_user.is_some_and(|user_id| user_id != credential.user_id)
+            || user_handle.is_some_and(|handle| handle != credential.user_id.as_bytes())
+        {
+            return Err(PasskeyError::UnknownCredential);
+        }
+
+        let sign_count = webauthn::verify_assertion(
+            &self.config,
+            challenge,
+            assertion,
+            &credential.public_key,
+            credential.sign_count as u32,
+            expected_user.is_none(),
+        )?;
+
+        // Условие на счетчик не дает принять один ответ аутентификатора дважды при гонке
+        let updated = sqlx::query(
+            r#"
+            UPDATE webauthn_credentials SET sign_count = $1, last_used_at = $2
+            WHERE id = $3 AND sign_count = $4
+            "#
+        )
+        .bind(sign_count as i64)
+        .bind(Utc::now())
+        .bind(credential.id)
+        .bind(credential.sign_count)
+        .execute(&self.pool)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Err(PasskeyError::WebAuthn(WebAuthnError::CounterRegression));
+        }
+
+        Ok(credential.user_id)
+    }
+
+    async fn store_challenge(&self, ceremony: &str, user_id: Option<Uuid>) -> Result<String, sqlx::Error> {
+        let now = Utc::now();
+        let challenge = webauthn::new_challenge();
+
+        sqlx::query("DELETE FROM webauthn_challenges WHERE expires_at < $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO webauthn_challenges (id, challenge_hash, ceremony, user_id, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#
+        )
+        .bind(Uuid::new_v4())
+        .bind(hash_secret(&challenge))
+        .bind(ceremony)
+        .bind(user_id)
+        .bind(now + Duration::seconds(CHALLENGE_TTL_SECS))
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(challenge)
+    }
+
+    // Погашение challenge из clientDataJSON: возвращает его и пользователя, для которого он выдан
+    async fn consume_challenge(
+        &self,
+        ceremony: &str,
+        client_data_json: &[u8],
+    ) -> Result<(String, Option<Uuid>), PasskeyError> {
+        let challenge = webauthn::parse_client_data(client_data_json)?.challenge;
+
+        let user_id = sqlx::query_scalar::<_, Option<Uuid>>(
+            r#"
+            DELETE FROM webauthn_challenges
+            WHERE challenge_hash = $1 AND ceremony = $2 AND expires_at > $3
+            RETURNING user_id
+            "#
+        )
+        .bind(hash_secret(&challenge))
+        .bind(ceremony)
+        .bind(Utc::now())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(PasskeyError::InvalidChallenge)?;
+
+        Ok((challenge, user_id))
+    }
+}
@@ -0,0 +1,100 @@
+// Хеширование паролей пользователей: новые хеши Argon2id, bcrypt только для проверки старых.
+// Хеш с устаревшим алгоритмом или параметрами заменяется при успешном входе
+use argon2::password_hash::{self, PasswordHash, PasswordHasher as _, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::rngs::OsRng;
+use crate::config::PasswordHashingConfig;
+
+// Префиксы хешей bcrypt ($2a$, $2b$, $2x$, $2y$)
+const BCRYPT_PREFIXES: [&str; 4] = ["$2a$", "$2b$", "$2x$", "$2y$"];
+
+#[derive(Debug)]
+pub enum PasswordHashError {
+    // Недопустимые параметры Argon2 в конфигурации
+    InvalidParams(String),
+    // Хеш в базе не распознан или поврежден
+    UnsupportedHash,
+    HashFailed(String),
+}
+
+impl std::fmt::Display for PasswordHashError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PasswordHashError::InvalidParams(e) => write!(f, "Недопустимые параметры Argon2: {}", e),
+            PasswordHashError::UnsupportedHash => write!(f, "Неподдерживаемый формат хеша пароля"),
+            PasswordHashError::HashFailed(e) => write!(f, "Ошибка хеширования пароля: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PasswordHashError {}
+
+#[derive(Clone)]
+pub struct PasswordHasher {
+    params: Params,
+}
+
+impl Default for PasswordHasher {
+    fn default() -> Self {
+        Self::new(&PasswordHashingConfig::default()).expect("Параметры Argon2 по умолчанию допустимы")
+    }
+}
+
+impl PasswordHasher {
+    pub fn new(config: &PasswordHashingConfig) -> Result<Self, PasswordHashError> {
+        let params = Params::new(config.memory_kib, config.iterations, config.parallelism, None)
+            .map_err(|e| PasswordHashError::InvalidParams(e.to_string()))?;
+        Ok(Self { params })
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+
+    // Хеш в формате PHC: $argon2id$v=19$m=...,t=...,p=...$salt$hash
+    pub fn hash(&self, password: &str) -> Result<String, PasswordHashError> {
+        let salt = SaltString::generate(&mut OsRng);
+        self.argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| PasswordHashError::HashFailed(e.to_string()))
+    }
+
+    // Проверка пароля по хешу Argon2 (параметры берутся из хеша) или bcrypt
+    pub fn verify(&self, password: &str, hash: &str) -> Result<bool, PasswordHashError> {
+        if is_bcrypt(hash) {
+            return bcrypt::verify(password, hash).map_err(|_| PasswordHashError::UnsupportedHash);
+        }
+
+        let parsed = PasswordHash::new(hash).map_err(|_| PasswordHashError::UnsupportedHash)?;
+        match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+            Ok(()) => Ok(true),
+            Err(password_hash::Error::Password) => Ok(false),
+            Err(_) => Err(PasswordHashError::UnsupportedHash),
+        }
+    }
+
+    // Нужно ли пересчитать хеш: не Argon2id, другая версия или параметры отличаются от текущих
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        let parsed = match PasswordHash::new(hash) {
+            Ok(parsed) => parsed,
+            Err(_) => return true,
+        };
+        if parsed.algorithm != argon2::ARGON2ID_IDENT || parsed.version != Some(Version::V0x13.into()) {
+            return true;
+        }
+
+        match Params::try_from(&parsed) {
+            Ok(params) => {
+                params.m_cost() != self.params.m_cost()
+                    || params.t_cost() != self.params.t_cost()
+                    || params.p_cost() != self.params.p_cost()
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+fn is_bcrypt(hash: &str) -> bool {
+    BCRYPT_PREFIXES.iter().any(|prefix| hash.starts_with(prefix))
+}
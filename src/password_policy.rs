@@ -0,0 +1,155 @@
+// Проверка новых паролей: длина, сходство с именем пользователя и email, утекшие пароли
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use crate::config::PasswordPolicyConfig;
+
+// Минимальная длина фрагмента имени или email, который проверяется на сходство
+const MIN_IDENTITY_TOKEN_LEN: usize = 3;
+// Допустимое число правок между паролем и именем пользователя или email
+const MAX_SIMILARITY_DISTANCE: usize = 2;
+
+// Нарушенное правило политики паролей
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PasswordViolation {
+    pub rule: &'static str,
+    pub message: String,
+}
+
+pub struct PasswordPolicy {
+    config: PasswordPolicyConfig,
+}
+
+impl PasswordPolicy {
+    pub fn new(config: &PasswordPolicyConfig) -> Self {
+        Self { config: config.clone() }
+    }
+
+    // Все нарушенные правила (пустой список — пароль подходит)
+    pub async fn check(&self, password: &str, username: &str, email: &str) -> Vec<PasswordViolation> {
+        let mut violations = check_rules(&self.config, password, username, email);
+
+        if let Some(dir) = &self.config.breached_corpus_dir {
+            match is_breached(dir, password).await {
+                Ok(true) => violations.push(PasswordViolation {
+                    rule: "breached",
+                    message: "Пароль встречается в утечках данных, выберите другой".to_string(),
+                }),
+                Ok(false) => {}
+                // Недоступный корпус не блокирует регистрацию и сброс пароля
+                Err(e) => eprintln!("Breached password corpus error: {}", e),
+            }
+        }
+
+        violations
+    }
+}
+
+// Правила, не требующие обращения к корпусу утекших паролей
+pub fn check_rules(
+    config: &PasswordPolicyConfig,
+    password: &str,
+    username: &str,
+    email: &str,
+) -> Vec<PasswordViolation> {
+    let mut violations = Vec::new();
+
+    if password.chars().count() < config.min_length {
+        violations.push(PasswordViolation {
+            rule: "min_length",
+            message: format!("Пароль должен содержать не менее {} символов", config.min_length),
+        });
+    }
+
+    if password.len() > config.max_bytes {
+        violations.push(PasswordViolation {
+            rule: "max_length",
+            message: format!("Пароль должен занимать не более {} байт", config.max_bytes),
+        });
+    }
+
+    if config.reject_similar_to_identity && is_similar_to_identity(password, username, email) {
+        violations.push(PasswordViolation {
+            rule: "similar_to_identity",
+            message: "Пароль не должен совпадать с именем пользователя или email".to_string(),
+        });
+    }
+
+    violations
+}
+
+// Пароль содержит имя пользователя или email (или наоборот) либо отличается от них на пару символов.
+// Сравнение без учета регистра и символов, кроме букв и цифр
+pub fn is_similar_to_identity(password: &str, username: &str, email: &str) -> bool {
+    let password = normalize(password);
+    if password.is_empty() {
+        return false;
+    }
+
+    let local_part = email.split('@').next().unwrap_or_default();
+    [username, local_part, email]
+        .iter()
+        .map(|token| normalize(token))
+        .filter(|token| token.chars().count() >= MIN_IDENTITY_TOKEN_LEN)
+        .any(|token| {
+            password.contains(&token)
+                || token.contains(&password)
+                || edit_distance(&password, &token) <= MAX_SIMILARITY_DISTANCE
+        })
+}
+
+fn normalize(value: &str) -> String {
+    value.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// Расстояние Левенштейна по символам
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+// Поиск в корпусе hash-prefix: SHA-1 пароля, файл по первым 5 символам, строки SUFFIX[:COUNT]
+pub async fn is_breached(dir: &Path, password: &str) -> std::io::Result<bool> {
+    let digest: String = Sha1::digest(password.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect();
+    let (prefix, suffix) = digest.split_at(5);
+
+    let contents = match read_prefix_file(dir, prefix).await? {
+        Some(contents) => contents,
+        None => return Ok(false),
+    };
+
+    Ok(contents.lines().any(|line| {
+        let hash = line.split(':').next().unwrap_or_default().trim();
+        hash.eq_ignore_ascii_case(suffix)
+    }))
+}
+
+// Файл префикса с расширением .txt или без него; отсутствие файла — префикс не встречался
+async fn read_prefix_file(dir: &Path, prefix: &str) -> std::io::Result<Option<String>> {
+    let candidates: [PathBuf; 2] = [dir.join(format!("{}.txt", prefix)), dir.join(prefix)];
+    for path in candidates {
+        match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => return Ok(Some(contents)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
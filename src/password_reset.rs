@@ -0,0 +1,132 @@
+// Сброс пароля по одноразовой ссылке из письма
+use std::sync::Arc;
+use chrono::{Duration, Utc};
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::mail::{EmailMessage, MailError, MailSender};
+use crate::models::User;
+use crate::secrets::{hash_secret, random_secret};
+
+#[derive(Debug)]
+pub enum PasswordResetError {
+    DatabaseError(sqlx::Error),
+    // Токен не найден, истек или уже использован
+    InvalidToken,
+    MailError(MailError),
+}
+
+impl std::fmt::Display for PasswordResetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PasswordResetError::DatabaseError(e) => write!(f, "Database error: {}", e),
+            PasswordResetError::InvalidToken => write!(f, "Invalid or expired reset token"),
+            PasswordResetError::MailError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PasswordResetError {}
+
+impl From<sqlx::Error> for PasswordResetError {
+    fn from(e: sqlx::Error) -> Self {
+        PasswordResetError::DatabaseError(e)
+    }
+}
+
+pub struct PasswordResetService {
+    pool: Pool<Postgres>,
+    mailer: Arc<dyn MailSender>,
+    issuer: String,
+    token_ttl: i64,
+}
+
+impl PasswordResetService {
+    pub fn new(pool: Pool<Postgres>, mailer: Arc<dyn MailSender>, config: &AppConfig) -> Self {
+        Self {
+            pool,
+            mailer,
+            issuer: config.issuer.clone(),
+            token_ttl: config.password_reset_ttl,
+        }
+    }
+
+    // Создание токена сброса и отправка ссылки; предыдущие ссылки перестают действовать
+    pub async fn request_reset(&self, user: &User) -> Result<(), PasswordResetError> {
+        let token = random_secret(48);
+        let now = Utc::now();
+
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NULL")
+            .bind(user.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO password_reset_tokens (id, user_id, token_hash, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#
+        )
+        .bind(Uuid::new_v4())
+        .bind(user.id)
+        .bind(hash_secret(&token))
+        .bind(now + Duration::seconds(self.token_ttl))
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        let message = EmailMessage {
+            to: user.email.clone(),
+            subject: "Сброс пароля".to_string(),
+            body: format!(
+                "Здравствуйте, {}!\n\nДля установки нового пароля перейдите по ссылке:\n{}/auth/password/reset?token={}\n\nСсылка действительна {} мин. Если вы не запрашивали сброс пароля, проигнорируйте это письмо.\n",
+                user.username,
+                self.issuer,
+                urlencoding::encode(&token),
+                self.token_ttl / 60
+            ),
+        };
+
+        self.mailer.send(message).await.map_err(PasswordResetError::MailError)
+    }
+
+    // Пользователь действующего токена без его погашения (для проверки нового пароля)
+    pub async fn find_token_user(&self, token: &str) -> Result<Option<Uuid>, PasswordResetError> {
+        let user_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT user_id FROM password_reset_tokens
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > $2
+            "#
+        )
+        .bind(hash_secret(token))
+        .bind(Utc::now())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user_id)
+    }
+
+    // Погашение токена: проверка и отметка об использовании одним запросом,
+    // чтобы одну ссылку нельзя было использовать дважды. Возвращает ID пользователя
+    pub async fn consume_token(&self, token: &str) -> Result<Uuid, PasswordResetError> {
+        let now = Utc::now();
+
+        sqlx::query_scalar::<_, Uuid>(
+            r#"
+            UPDATE password_reset_tokens
+            SET used_at = $1
+            WHERE token_hash = $2 AND used_at IS NULL AND expires_at > $1
+            RETURNING user_id
+            "#
+        )
+        .bind(now)
+        .bind(hash_secret(token))
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(PasswordResetError::InvalidToken)
+    }
+}
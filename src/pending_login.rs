@@ -0,0 +1,111 @@
+// Незавершенный вход со вторым фактором. Состояние и счетчик попыток хранятся в БД,
+// в cookie-сессии только случайный идентификатор: повтор старой cookie не сбрасывает счетчик
+use chrono::{Duration, Utc};
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+use crate::secrets::{hash_secret, random_secret};
+
+// Время на ввод второго фактора после проверки пароля и число попыток
+pub const PENDING_LOGIN_TTL_SECS: i64 = 300;
+pub const PENDING_LOGIN_MAX_ATTEMPTS: i32 = 5;
+
+// Попытка ввода второго фактора
+#[derive(Debug, Clone, Copy, sqlx::FromRow)]
+pub struct PendingAttempt {
+    pub user_id: Uuid,
+    pub attempts: i32,
+}
+
+impl PendingAttempt {
+    // Последняя попытка: при неудаче вход нужно начинать заново с пароля
+    pub fn is_last(&self) -> bool {
+        self.attempts >= PENDING_LOGIN_MAX_ATTEMPTS
+    }
+}
+
+pub struct PendingLoginService {
+    pool: Pool<Postgres>,
+}
+
+impl PendingLoginService {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    // Начало входа после проверки пароля. Возвращает идентификатор для cookie-сессии
+    // (в БД хранится только SHA-256)
+    pub async fn start(&self, user_id: Uuid) -> Result<String, sqlx::Error> {
+        let token = random_secret(48);
+        let now = Utc::now();
+
+        let mut tx = self.pool.begin().await?;
+
+        // Истекшие и исчерпанные попытки пользователя больше не нужны
+        sqlx::query(
+            "DELETE FROM mfa_pending_logins WHERE user_id = $1 AND (expires_at <= $2 OR attempts >= $3)"
+        )
+        .bind(user_id)
+        .bind(now)
+        .bind(PENDING_LOGIN_MAX_ATTEMPTS)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO mfa_pending_logins (token_hash, user_id, attempts, expires_at, created_at)
+            VALUES ($1, $2, 0, $3, $4)
+            "#
+        )
+        .bind(hash_secret(&token))
+        .bind(user_id)
+        .bind(now + Duration::seconds(PENDING_LOGIN_TTL_SECS))
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(token)
+    }
+
+    // Пользователь действующего незавершенного входа (без расхода попытки)
+    pub async fn user_id(&self, token: &str) -> Result<Option<Uuid>, sqlx::Error> {
+        sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT user_id FROM mfa_pending_logins
+            WHERE token_hash = $1 AND expires_at > $2 AND attempts < $3
+            "#
+        )
+        .bind(hash_secret(token))
+        .bind(Utc::now())
+        .bind(PENDING_LOGIN_MAX_ATTEMPTS)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    // Расход попытки до проверки второго фактора: параллельные запросы не получают
+    // больше попыток, чем задано. None, если вход истек или попытки исчерпаны
+    pub async fn begin_attempt(&self, token: &str) -> Result<Option<PendingAttempt>, sqlx::Error> {
+        sqlx::query_as::<_, PendingAttempt>(
+            r#"
+            UPDATE mfa_pending_logins SET attempts = attempts + 1
+            WHERE token_hash = $1 AND expires_at > $2 AND attempts < $3
+            RETURNING user_id, attempts
+            "#
+        )
+        .bind(hash_secret(token))
+        .bind(Utc::now())
+        .bind(PENDING_LOGIN_MAX_ATTEMPTS)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    // Завершение входа (успех, отмена или исчерпание попыток): идентификатор больше не действует
+    pub async fn finish(&self, token: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM mfa_pending_logins WHERE token_hash = $1")
+            .bind(hash_secret(token))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
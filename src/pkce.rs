@@ -0,0 +1,178 @@
+use sha2::{Sha256, Digest};
+use base64::{Engine as _, engine::general_purpose};
+use crate::models::OAuthClient;
+
+// Для каких клиентов PKCE обязателен
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PkceRequirement {
+    None,
+    PublicClients,
+    AllClients,
+}
+
+impl PkceRequirement {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "none" => Some(PkceRequirement::None),
+            "public" => Some(PkceRequirement::PublicClients),
+            "all" => Some(PkceRequirement::AllClients),
+            _ => None,
+        }
+    }
+}
+
+// Глобальная политика PKCE (переопределяется настройками клиента)
+#[derive(Debug, Clone, Copy)]
+pub struct PkcePolicy {
+    pub requirement: PkceRequirement,
+    pub allow_plain: bool,
+}
+
+impl Default for PkcePolicy {
+    fn default() -> Self {
+        Self {
+            requirement: PkceRequirement::PublicClients,
+            allow_plain: false,
+        }
+    }
+}
+
+// Итоговые правила PKCE для конкретного клиента
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientPkceRules {
+    pub required: bool,
+    pub allow_plain: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PkceError {
+    ChallengeRequired,
+    UnsupportedMethod,
+    PlainNotAllowed,
+    MalformedChallenge,
+    MissingVerifier,
+    MalformedVerifier,
+    VerifierMismatch,
+}
+
+impl std::fmt::Display for PkceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PkceError::ChallengeRequired => write!(f, "code_challenge is required for this client"),
+            PkceError::UnsupportedMethod => write!(f, "Unsupported code_challenge_method"),
+            PkceError::PlainNotAllowed => write!(f, "code_challenge_method 'plain' is not allowed, use 'S256'"),
+            PkceError::MalformedChallenge => write!(f, "Malformed code_challenge"),
+            PkceError::MissingVerifier => write!(f, "Missing code_verifier"),
+            PkceError::MalformedVerifier => write!(f, "Malformed code_verifier"),
+            PkceError::VerifierMismatch => write!(f, "Invalid code verifier"),
+        }
+    }
+}
+
+impl std::error::Error for PkceError {}
+
+impl PkcePolicy {
+    // Применение переопределений клиента к глобальной политике
+    pub fn rules_for(&self, client: &OAuthClient) -> ClientPkceRules {
+        let required_by_default = match self.requirement {
+            PkceRequirement::None => false,
+            PkceRequirement::PublicClients => !client.is_confidential,
+            PkceRequirement::AllClients => true,
+        };
+
+        ClientPkceRules {
+            required: client.require_pkce.unwrap_or(required_by_default),
+            allow_plain: client.allow_plain_pkce.unwrap_or(self.allow_plain),
+        }
+    }
+
+    // Поддерживаемые методы для метаданных сервера
+    pub fn supported_methods(&self) -> Vec<String> {
+        let mut methods = vec!["S256".to_string()];
+        if self.allow_plain {
+            methods.push("plain".to_string());
+        }
+        methods
+    }
+}
+
+impl ClientPkceRules {
+    // Проверка параметров PKCE в запросе авторизации
+    pub fn validate_challenge(&self, challenge: Option<&str>, method: Option<&str>) -> Result<(), PkceError> {
+        let challenge = match challenge.filter(|c| !c.is_empty()) {
+            Some(challenge) => challenge,
+            None if self.required => return Err(PkceError::ChallengeRequired),
+            None => return Ok(()),
+        };
+
+        // RFC 7636: при отсутствии метода подразумевается plain
+        match method.filter(|m| !m.is_empty()).unwrap_or("plain") {
+            "S256" => {
+                // base64url(SHA-256) без padding — ровно 43 символа
+                if challenge.len() != 43 || !challenge.chars().all(is_base64url_char) {
+                    return Err(PkceError::MalformedChallenge);
+                }
+            }
+            "plain" => {
+                if !self.allow_plain {
+                    return Err(PkceError::PlainNotAllowed);
+                }
+                if !is_valid_verifier(challenge) {
+                    return Err(PkceError::MalformedChallenge);
+                }
+            }
+            _ => return Err(PkceError::UnsupportedMethod),
+        }
+
+        Ok(())
+    }
+
+    // Проверка code_verifier при обмене кода на токены
+    pub fn verify(
+        &self,
+        challenge: Option<&str>,
+        method: Option<&str>,
+        verifier: Option<&str>,
+    ) -> Result<(), PkceError> {
+        let challenge = match challenge {
+            Some(challenge) => challenge,
+            None if self.required => return Err(PkceError::ChallengeRequired),
+            None => return Ok(()),
+        };
+
+        let verifier = verifier.ok_or(PkceError::MissingVerifier)?;
+        if !is_valid_verifier(verifier) {
+            return Err(PkceError::MalformedVerifier);
+        }
+
+        let matches = match method.unwrap_or("plain") {
+            "S256" => s256_challenge(verifier) == challenge,
+            "plain" if self.allow_plain => verifier == challenge,
+            "plain" => return Err(PkceError::PlainNotAllowed),
+            _ => return Err(PkceError::UnsupportedMethod),
+        };
+
+        if !matches {
+            return Err(PkceError::VerifierMismatch);
+        }
+
+        Ok(())
+    }
+}
+
+// Вычисление S256 code_challenge для code_verifier
+pub fn s256_challenge(verifier: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(verifier.as_bytes());
+    general_purpose::URL_SAFE_NO_PAD.encode(hasher.finalize())
+}
+
+// RFC 7636: 43-128 символов из набора unreserved
+fn is_valid_verifier(value: &str) -> bool {
+    (43..=128).contains(&value.len())
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~'))
+}
+
+fn is_base64url_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
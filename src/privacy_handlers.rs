@@ -0,0 +1,249 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use actix_session::Session;
+use validator::Validate;
+use crate::account_deletion::{AccountDeletionError, AccountDeletionService};
+use crate::account_handlers::{session_user, session_user_id, verify_current_password};
+use crate::data_export::DataExportService;
+use crate::login_throttle::LoginThrottle;
+use crate::models::{DeleteAccountRequest, ErrorResponse};
+use crate::services::UserService;
+use crate::token_service::TokenService;
+
+fn internal_error(e: &dyn std::fmt::Display) -> HttpResponse {
+    eprintln!("Database error: {}", e);
+    HttpResponse::InternalServerError().json(ErrorResponse {
+        error: "Internal server error".to_string(),
+    })
+}
+
+// GET /account/api/export - выгрузка персональных данных (JSON файл)
+pub async fn export_data(
+    data_export_service: web::Data<DataExportService>,
+    session: Session,
+) -> impl Responder {
+    let user_id = match session_user_id(&session) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    match data_export_service.export(user_id).await {
+        Ok(Some(export)) => HttpResponse::Ok()
+            .append_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"account-export-{}.json\"", user_id),
+            ))
+            .append_header(("Cache-Control", "no-store"))
+            .json(export),
+        Ok(None) => HttpResponse::NotFound().json(ErrorResponse {
+            error: "User not found".to_string(),
+        }),
+        Err(e) => internal_error(&e),
+    }
+}
+
+// GET /account/api/deletion - запланировано ли удаление аккаунта
+pub async fn deletion_status(
+    deletion_service: web::Data<AccountDeletionService>,
+    session: Session,
+) -> impl Responder {
+    let user_id = match session_user_id(&session) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    match deletion_service.get_status(user_id).await {
+        Ok(Some(status)) => HttpResponse::Ok().json(serde_json::json!({
+            "scheduled": true,
+            "requested_at": status.requested_at,
+            "scheduled_for": status.scheduled_for
+        })),
+        Ok(None) => HttpResponse::Ok().json(serde_json::json!({ "scheduled": false })),
+        Err(e) => internal_error(&e),
+    }
+}
+
+// POST /account/api/deletion - планирование удаления аккаунта (требует текущий пароль).
+// Токены приложений отзываются сразу, вход остается доступным для отмены удаления
+pub async fn schedule_deletion(
+    req: HttpRequest,
+    user_service: web::Data<UserService>,
+    login_throttle: web::Data<LoginThrottle>,
+    token_service: web::Data<TokenService>,
+    deletion_service: web::Data<AccountDeletionService>,
+    session: Session,
+    request: web::Json<DeleteAccountRequest>,
+) -> impl Responder {
+    let user = match session_user(&user_service, &session).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    if let Err(errors) = request.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Validation error: {}", errors),
+        });
+    }
+    if let Err(response) = verify_current_password(
+        &req, &user_service, &login_throttle, &user, &request.current_password,
+    ).await {
+        return response;
+    }
+
+    let status = match deletion_service.schedule(&user).await {
+        Ok(status) => status,
+        Err(AccountDeletionError::AlreadyScheduled) => {
+            return HttpResponse::Conflict().json(ErrorResponse {
+                error: AccountDeletionError::AlreadyScheduled.to_string(),
+            });
+        }
+        Err(e) => return internal_error(&e),
+    };
+
+    if let Err(e) = token_service.revoke_user_tokens(user.id).await {
+        return internal_error(&e);
+    }
+
+    HttpResponse::Accepted().json(serde_json::json!({
+        "message": "Account deletion has been scheduled",
+        "requested_at": status.requested_at,
+        "scheduled_for": status.scheduled_for
+    }))
+}
+
+// DELETE /account/api/deletion - отмена запланированного удаления
+pub async fn cancel_deletion(
+    deletion_service: web::Data<AccountDeletionService>,
+    session: Session,
+) -> impl Responder {
+    let user_id = match session_user_id(&session) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    match deletion_service.cancel(user_id).await {
+        Ok(()) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "Account deletion has been cancelled"
+        })),
+        Err(AccountDeletionError::NotScheduled) => HttpResponse::NotFound().json(ErrorResponse {
+            error: AccountDeletionError::NotScheduled.to_string(),
+        }),
+        Err(e) => internal_error(&e),
+    }
+}
+
+// GET /account/privacy - страница выгрузки данных и удаления аккаунта
+pub async fn privacy_page(session: Session) -> impl Responder {
+    if session_user_id(&session).is_err() {
+        return HttpResponse::Found()
+            .append_header(("Location", "/auth/login?return_to=%2Faccount%2Fprivacy"))
+            .finish();
+    }
+
+    let html = r#"
+<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="UTF-8">
+    <title>Данные и удаление аккаунта</title>
+    <style>
+        body { font-family: Arial, sans-serif; max-width: 500px; margin: 50px auto; padding: 20px; }
+        h1, h2 { text-align: center; }
+        form { display: flex; flex-direction: column; gap: 15px; }
+        label { display: flex; flex-direction: column; gap: 5px; color: #333; }
+        input { padding: 10px; border: 1px solid #ddd; border-radius: 4px; }
+        button, .button { padding: 10px; background-color: #007bff; color: white; border: none; border-radius: 4px; cursor: pointer; text-align: center; text-decoration: none; }
+        .danger { background-color: #dc3545; }
+        .meta { color: #666; font-size: 14px; text-align: center; }
+        .error { color: red; text-align: center; }
+        .success { color: green; text-align: center; }
+    </style>
+</head>
+<body>
+    <h1>Данные и удаление аккаунта</h1>
+
+    <h2>Выгрузка данных</h2>
+    <p class="meta">Профиль, согласия, сессии, подключенные приложения и история действий в формате JSON.</p>
+    <a class="button" href="/account/api/export">Скачать данные</a>
+
+    <h2>Удаление аккаунта</h2>
+    <div id="scheduled" style="display: none;">
+        <p class="meta" id="scheduledFor"></p>
+        <form id="cancelForm">
+            <button type="submit">Отменить удаление</button>
+        </form>
+    </div>
+    <form id="deleteForm" style="display: none;">
+        <p class="meta">Аккаунт будет удален после периода ожидания, в течение которого удаление можно отменить. Доступ приложений отзывается сразу.</p>
+        <label>Текущий пароль <input type="password" name="current_password" required></label>
+        <button type="submit" class="danger">Удалить аккаунт</button>
+    </form>
+
+    <div class="error" id="error"></div>
+    <div class="success" id="success"></div>
+
+    <script>
+        async function loadStatus() {
+            const response = await fetch('/account/api/deletion');
+            if (!response.ok) {
+                document.getElementById('error').textContent = 'Не удалось загрузить статус';
+                return;
+            }
+            const status = await response.json();
+            document.getElementById('scheduled').style.display = status.scheduled ? 'block' : 'none';
+            document.getElementById('deleteForm').style.display = status.scheduled ? 'none' : 'flex';
+            if (status.scheduled) {
+                document.getElementById('scheduledFor').textContent =
+                    'Аккаунт будет удален ' + new Date(status.scheduled_for).toLocaleString();
+            }
+        }
+
+        function clearMessages() {
+            document.getElementById('error').textContent = '';
+            document.getElementById('success').textContent = '';
+        }
+
+        document.getElementById('deleteForm').addEventListener('submit', async (e) => {
+            e.preventDefault();
+            clearMessages();
+            if (!confirm('Удалить аккаунт?')) {
+                return;
+            }
+
+            const response = await fetch('/account/api/deletion', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ current_password: e.target.elements['current_password'].value })
+            });
+            const result = await response.json();
+            if (response.ok) {
+                e.target.reset();
+                document.getElementById('success').textContent = 'Удаление аккаунта запланировано';
+                loadStatus();
+            } else {
+                document.getElementById('error').textContent = result.error || 'Ошибка удаления';
+            }
+        });
+
+        document.getElementById('cancelForm').addEventListener('submit', async (e) => {
+            e.preventDefault();
+            clearMessages();
+
+            const response = await fetch('/account/api/deletion', { method: 'DELETE' });
+            const result = await response.json();
+            if (response.ok) {
+                document.getElementById('success').textContent = 'Удаление отменено';
+                loadStatus();
+            } else {
+                document.getElementById('error').textContent = result.error || 'Ошибка отмены';
+            }
+        });
+
+        loadStatus();
+    </script>
+</body>
+</html>
+    "#;
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html)
+}
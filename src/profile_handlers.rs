@@ -0,0 +1,282 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use actix_session::Session;
+use uuid::Uuid;
+use validator::Validate;
+use crate::account_handlers::session_user_id;
+use crate::middleware::get_claims_from_request;
+use crate::models::{ErrorResponse, UpdateProfileRequest};
+use crate::services::{UserError, UserService};
+
+// Пользователь access token; токен client_credentials выдан клиенту, а не пользователю
+fn token_user_id(req: &HttpRequest) -> Result<Uuid, HttpResponse> {
+    let claims = get_claims_from_request(req).ok_or_else(|| {
+        HttpResponse::Unauthorized().json(ErrorResponse {
+            error: "Authentication required".to_string(),
+        })
+    })?;
+
+    claims.sub.parse::<Uuid>().map_err(|_| {
+        HttpResponse::Forbidden().json(ErrorResponse {
+            error: "Token is not issued to a user".to_string(),
+        })
+    })
+}
+
+async fn profile_response(user_service: &UserService, user_id: Uuid) -> HttpResponse {
+    match user_service.get_profile(user_id).await {
+        Ok(Some(profile)) => HttpResponse::Ok().json(profile),
+        Ok(None) => HttpResponse::NotFound().json(ErrorResponse {
+            error: "User not found".to_string(),
+        }),
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            })
+        }
+    }
+}
+
+async fn update_profile_response(
+    user_service: &UserService,
+    user_id: Uuid,
+    request: &UpdateProfileRequest,
+) -> HttpResponse {
+    if let Err(errors) = request.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Validation error: {}", errors),
+        });
+    }
+    if request.is_empty() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "No profile fields to update".to_string(),
+        });
+    }
+
+    match user_service.update_profile(user_id, request).await {
+        Ok(profile) => HttpResponse::Ok().json(profile),
+        Err(UserError::UsernameExists) => HttpResponse::Conflict().json(ErrorResponse {
+            error: "Username already exists".to_string(),
+        }),
+        Err(UserError::NotFound) => HttpResponse::NotFound().json(ErrorResponse {
+            error: "User not found".to_string(),
+        }),
+        Err(e) => {
+            eprintln!("Profile update error: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            })
+        }
+    }
+}
+
+// GET /api/protected/profile - профиль владельца токена (scope read:profile)
+pub async fn get_token_profile(
+    req: HttpRequest,
+    user_service: web::Data<UserService>,
+) -> impl Responder {
+    match token_user_id(&req) {
+        Ok(user_id) => profile_response(&user_service, user_id).await,
+        Err(response) => response,
+    }
+}
+
+// PATCH /api/protected/profile - изменение профиля владельца токена (scope write:profile)
+pub async fn update_token_profile(
+    req: HttpRequest,
+    user_service: web::Data<UserService>,
+    request: web::Json<UpdateProfileRequest>,
+) -> impl Responder {
+    match token_user_id(&req) {
+        Ok(user_id) => update_profile_response(&user_service, user_id, &request).await,
+        Err(response) => response,
+    }
+}
+
+// GET /account/api/profile - профиль пользователя сессии
+pub async fn get_session_profile(
+    user_service: web::Data<UserService>,
+    session: Session,
+) -> impl Responder {
+    match session_user_id(&session) {
+        Ok(user_id) => profile_response(&user_service, user_id).await,
+        Err(response) => response,
+    }
+}
+
+// PATCH /account/api/profile - изменение профиля пользователя сессии
+pub async fn update_session_profile(
+    user_service: web::Data<UserService>,
+    session: Session,
+    request: web::Json<UpdateProfileRequest>,
+) -> impl Responder {
+    match session_user_id(&session) {
+        Ok(user_id) => update_profile_response(&user_service, user_id, &request).await,
+        Err(response) => response,
+    }
+}
+
+// GET /account/profile - страница редактирования профиля
+pub async fn profile_page(session: Session) -> impl Responder {
+    if session_user_id(&session).is_err() {
+        return HttpResponse::Found()
+            .append_header(("Location", "/auth/login?return_to=%2Faccount%2Fprofile"))
+            .finish();
+    }
+
+    let html = r#"
+<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="UTF-8">
+    <title>Профиль</title>
+    <style>
+        body { font-family: Arial, sans-serif; max-width: 500px; margin: 50px auto; padding: 20px; }
+        h1, h2 { text-align: center; }
+        form { display: flex; flex-direction: column; gap: 15px; }
+        label { display: flex; flex-direction: column; gap: 5px; color: #333; }
+        input { padding: 10px; border: 1px solid #ddd; border-radius: 4px; }
+        button { padding: 10px; background-color: #007bff; color: white; border: none; border-radius: 4px; cursor: pointer; }
+        .checkbox { flex-direction: row; align-items: center; }
+        .meta { color: #666; font-size: 14px; text-align: center; }
+        .error { color: red; text-align: center; }
+        .success { color: green; text-align: center; }
+    </style>
+</head>
+<body>
+    <h1>Профиль</h1>
+    <p class="meta" id="email"></p>
+
+    <form id="profileForm">
+        <label>Имя пользователя <input type="text" name="username" minlength="3" maxlength="50" required></label>
+        <label>Отображаемое имя <input type="text" name="display_name" maxlength="100"></label>
+        <label>Язык <input type="text" name="locale" placeholder="ru-RU" maxlength="35"></label>
+        <label>Часовой пояс <input type="text" name="timezone" placeholder="Europe/Moscow" maxlength="64"></label>
+        <label>URL аватара <input type="url" name="avatar_url" placeholder="https://"></label>
+        <button type="submit">Сохранить</button>
+    </form>
+
+    <div class="error" id="error"></div>
+    <div class="success" id="success"></div>
+
+    <p class="meta"><a href="/account/privacy">Выгрузка данных и удаление аккаунта</a></p>
+
+    <h2>Смена email</h2>
+    <form id="emailForm">
+        <label>Новый email <input type="email" name="new_email" required></label>
+        <label>Текущий пароль <input type="password" name="current_password" required></label>
+        <button type="submit">Сменить email</button>
+    </form>
+
+    <div class="error" id="emailError"></div>
+    <div class="success" id="emailSuccess"></div>
+
+    <h2>Смена пароля</h2>
+    <form id="passwordForm">
+        <label>Текущий пароль <input type="password" name="current_password" required></label>
+        <label>Новый пароль <input type="password" name="new_password" minlength="8" required></label>
+        <label class="checkbox"><input type="checkbox" name="sign_out_other_sessions" checked> Выйти на остальных устройствах и отозвать доступ приложений</label>
+        <button type="submit">Сменить пароль</button>
+    </form>
+
+    <div class="error" id="passwordError"></div>
+    <div class="success" id="passwordSuccess"></div>
+
+    <script>
+        const form = document.getElementById('profileForm');
+        const fields = ['username', 'display_name', 'locale', 'timezone', 'avatar_url'];
+
+        async function loadProfile() {
+            const response = await fetch('/account/api/profile');
+            if (!response.ok) {
+                document.getElementById('error').textContent = 'Не удалось загрузить профиль';
+                return;
+            }
+            const profile = await response.json();
+            document.getElementById('email').textContent = profile.email;
+            for (const field of fields) {
+                form.elements[field].value = profile[field] || '';
+            }
+        }
+
+        form.addEventListener('submit', async (e) => {
+            e.preventDefault();
+            document.getElementById('error').textContent = '';
+            document.getElementById('success').textContent = '';
+            const data = {};
+            for (const field of fields) {
+                data[field] = form.elements[field].value;
+            }
+
+            const response = await fetch('/account/api/profile', {
+                method: 'PATCH',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify(data)
+            });
+            const result = await response.json();
+            if (response.ok) {
+                document.getElementById('success').textContent = 'Профиль сохранен';
+            } else {
+                document.getElementById('error').textContent = result.error || 'Ошибка сохранения';
+            }
+        });
+
+        const emailForm = document.getElementById('emailForm');
+        emailForm.addEventListener('submit', async (e) => {
+            e.preventDefault();
+            document.getElementById('emailError').textContent = '';
+            document.getElementById('emailSuccess').textContent = '';
+
+            const response = await fetch('/auth/email/change', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({
+                    new_email: emailForm.elements['new_email'].value,
+                    current_password: emailForm.elements['current_password'].value
+                })
+            });
+            const result = await response.json();
+            if (response.ok) {
+                emailForm.reset();
+                document.getElementById('emailSuccess').textContent = 'Ссылка для подтверждения отправлена на новый адрес';
+            } else {
+                document.getElementById('emailError').textContent = result.error || 'Ошибка смены email';
+            }
+        });
+
+        const passwordForm = document.getElementById('passwordForm');
+        passwordForm.addEventListener('submit', async (e) => {
+            e.preventDefault();
+            document.getElementById('passwordError').textContent = '';
+            document.getElementById('passwordSuccess').textContent = '';
+
+            const response = await fetch('/auth/password/change', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({
+                    current_password: passwordForm.elements['current_password'].value,
+                    new_password: passwordForm.elements['new_password'].value,
+                    sign_out_other_sessions: passwordForm.elements['sign_out_other_sessions'].checked
+                })
+            });
+            const result = await response.json();
+            if (response.ok) {
+                passwordForm.reset();
+                document.getElementById('passwordSuccess').textContent = 'Пароль изменен';
+            } else {
+                const details = (result.violations || []).map(v => v.message).join('. ');
+                document.getElementById('passwordError').textContent =
+                    details || result.error || 'Ошибка смены пароля';
+            }
+        });
+
+        loadProfile();
+    </script>
+</body>
+</html>
+    "#;
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html)
+}
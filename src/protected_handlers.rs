@@ -1,23 +1,7 @@
 use actix_web::{web, HttpRequest, HttpResponse, Responder};
-use crate::middleware::get_claims_from_request;
+use crate::middleware::{get_claims_from_request, ScopeValidator};
 use crate::models::ErrorResponse;
-
-// Protected endpoint - требует аутентификации
-pub async fn protected_profile(req: HttpRequest) -> impl Responder {
-    match get_claims_from_request(&req) {
-        Some(claims) => {
-            HttpResponse::Ok().json(serde_json::json!({
-                "message": "This is a protected resource",
-                "user_id": claims.sub,
-                "client_id": claims.client_id,
-                "scopes": claims.scope,
-            }))
-        }
-        None => HttpResponse::Unauthorized().json(ErrorResponse {
-            error: "Authentication required".to_string(),
-        }),
-    }
-}
+use crate::profile_handlers::{get_token_profile, update_token_profile};
 
 // Protected endpoint - требует определенный scope
 pub async fn protected_data(req: HttpRequest) -> impl Responder {
@@ -48,6 +32,10 @@ pub async fn protected_data(req: HttpRequest) -> impl Responder {
 
 // Конфигурация защищенных маршрутов
 pub fn configure_protected_routes(cfg: &mut web::ServiceConfig) {
-    cfg.route("/profile", web::get().to(protected_profile))
+    cfg.service(
+           web::resource("/profile")
+               .route(web::get().to(get_token_profile).wrap(ScopeValidator::new(vec!["read:profile".to_string()])))
+               .route(web::patch().to(update_token_profile).wrap(ScopeValidator::new(vec!["write:profile".to_string()])))
+       )
        .route("/data", web::get().to(protected_data));
 }
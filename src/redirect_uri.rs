@@ -0,0 +1,140 @@
+// Правила redirect URI для web и native приложений (RFC 6749 3.1.2, RFC 8252)
+use std::net::IpAddr;
+use url::{Host, Url};
+
+// Политика проверки redirect URI
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RedirectUriPolicy {
+    // Разрешить wildcard в первой метке хоста: https://*.example.com/cb
+    pub allow_wildcards: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedirectUriError {
+    Malformed,
+    FragmentNotAllowed,
+    HttpsRequired,
+    InvalidPrivateScheme,
+    WildcardNotAllowed,
+}
+
+impl std::fmt::Display for RedirectUriError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RedirectUriError::Malformed => write!(f, "redirect_uri must be an absolute URI"),
+            RedirectUriError::FragmentNotAllowed => write!(f, "redirect_uri must not contain a fragment"),
+            RedirectUriError::HttpsRequired => write!(f, "redirect_uri must use https (http is allowed only for loopback in native apps)"),
+            RedirectUriError::InvalidPrivateScheme => write!(f, "Private-use URI scheme must be a reverse domain name (e.g. com.example.app)"),
+            RedirectUriError::WildcardNotAllowed => write!(f, "Wildcards in redirect_uri are not allowed"),
+        }
+    }
+}
+
+impl std::error::Error for RedirectUriError {}
+
+impl RedirectUriPolicy {
+    // Проверка redirect URI при регистрации клиента
+    pub fn validate_registration(&self, uri: &str, is_confidential: bool) -> Result<(), RedirectUriError> {
+        let url = Url::parse(uri).map_err(|_| RedirectUriError::Malformed)?;
+
+        if url.fragment().is_some() || uri.contains('#') {
+            return Err(RedirectUriError::FragmentNotAllowed);
+        }
+
+        if uri.contains('*') {
+            if !self.allow_wildcards {
+                return Err(RedirectUriError::WildcardNotAllowed);
+            }
+            // Wildcard допускается только как первая метка https хоста
+            let valid_wildcard = url.scheme() == "https"
+                && url.host_str().is_some_and(is_wildcard_host)
+                && !url[url::Position::BeforePath..].contains('*');
+            if !valid_wildcard {
+                return Err(RedirectUriError::WildcardNotAllowed);
+            }
+        }
+
+        match url.scheme() {
+            "https" => Ok(()),
+            // Confidential клиенты — web приложения, им нужен https
+            _ if is_confidential => Err(RedirectUriError::HttpsRequired),
+            "http" if is_loopback(&url) => Ok(()),
+            "http" => Err(RedirectUriError::HttpsRequired),
+            scheme if is_private_use_scheme(scheme) => Ok(()),
+            _ => Err(RedirectUriError::InvalidPrivateScheme),
+        }
+    }
+
+    // Соответствие redirect URI из запроса зарегистрированному значению
+    pub fn matches(&self, registered: &str, requested: &str) -> bool {
+        if registered == requested {
+            return true;
+        }
+
+        let (registered_url, requested_url) = match (Url::parse(registered), Url::parse(requested)) {
+            (Ok(r), Ok(q)) => (r, q),
+            _ => return false,
+        };
+
+        if requested_url.fragment().is_some() {
+            return false;
+        }
+
+        // Loopback (RFC 8252 7.3): порт выбирается приложением при запуске
+        if registered_url.scheme() == "http" && is_loopback_ip(&registered_url) {
+            return requested_url.scheme() == "http"
+                && registered_url.host() == requested_url.host()
+                && registered_url.path() == requested_url.path()
+                && registered_url.query() == requested_url.query();
+        }
+
+        if self.allow_wildcards {
+            if let Some(pattern) = registered_url.host_str().filter(|h| is_wildcard_host(h)) {
+                return requested_url.scheme() == registered_url.scheme()
+                    && requested_url.host_str().is_some_and(|host| wildcard_host_matches(pattern, host))
+                    && requested_url.port() == registered_url.port()
+                    && requested_url.path() == registered_url.path()
+                    && requested_url.query() == registered_url.query();
+            }
+        }
+
+        false
+    }
+}
+
+// Loopback адрес: IP-литералы 127.0.0.0/8, ::1 и имя localhost
+fn is_loopback(url: &Url) -> bool {
+    is_loopback_ip(url) || url.host_str() == Some("localhost")
+}
+
+// Порт не фиксируется только для IP-литералов (RFC 8252 7.3)
+fn is_loopback_ip(url: &Url) -> bool {
+    match url.host() {
+        Some(Host::Ipv4(ip)) => IpAddr::V4(ip).is_loopback(),
+        Some(Host::Ipv6(ip)) => IpAddr::V6(ip).is_loopback(),
+        _ => false,
+    }
+}
+
+// Private-use схема — обратное доменное имя: com.example.app (RFC 8252 7.1)
+fn is_private_use_scheme(scheme: &str) -> bool {
+    let labels: Vec<&str> = scheme.split('.').collect();
+    labels.len() >= 2 && labels.iter().all(|label| !label.is_empty())
+}
+
+// Хост вида *.example.com (wildcard только в первой метке, минимум два уровня после нее)
+fn is_wildcard_host(host: &str) -> bool {
+    match host.strip_prefix("*.") {
+        Some(rest) => !rest.contains('*') && rest.split('.').count() >= 2,
+        None => false,
+    }
+}
+
+// Wildcard заменяет ровно одну метку хоста
+fn wildcard_host_matches(pattern: &str, host: &str) -> bool {
+    let suffix = &pattern[1..]; // ".example.com"
+    match host.strip_suffix(suffix) {
+        Some(label) => !label.is_empty() && !label.contains('.'),
+        None => false,
+    }
+}
@@ -0,0 +1,369 @@
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+use chrono::Utc;
+use std::collections::HashMap;
+use crate::models::{Scope, ScopeTranslation, ScopeDescription, CreateScopeRequest, UpdateScopeRequest};
+
+#[derive(Debug)]
+pub enum ScopeError {
+    DatabaseError(sqlx::Error),
+    NotFound,
+    AlreadyExists,
+    InUse,
+    UnknownScope(String),
+    RestrictedScope(String),
+}
+
+impl std::fmt::Display for ScopeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScopeError::DatabaseError(e) => write!(f, "Database error: {}", e),
+            ScopeError::NotFound => write!(f, "Scope not found"),
+            ScopeError::AlreadyExists => write!(f, "Scope already exists"),
+            ScopeError::InUse => write!(f, "Scope is used by registered clients"),
+            ScopeError::UnknownScope(name) => write!(f, "Unknown scope: {}", name),
+            ScopeError::RestrictedScope(name) => write!(f, "Scope {} is restricted to first-party clients", name),
+        }
+    }
+}
+
+impl std::error::Error for ScopeError {}
+
+impl From<sqlx::Error> for ScopeError {
+    fn from(e: sqlx::Error) -> Self {
+        match &e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => ScopeError::AlreadyExists,
+            _ => ScopeError::DatabaseError(e),
+        }
+    }
+}
+
+// Колонки oauth_scopes, возвращаемые во всех запросах
+const SCOPE_COLUMNS: &str = "id, scope_name, description, requires_consent, is_default, \
+    restricted_to_first_party, is_optional, created_at, updated_at";
+
+pub struct ScopeService {
+    pool: Pool<Postgres>,
+}
+
+impl ScopeService {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    // Список всех scopes
+    pub async fn list_scopes(&self) -> Result<Vec<Scope>, ScopeError> {
+        let scopes = sqlx::query_as::<_, Scope>(&format!(
+            "SELECT {} FROM oauth_scopes ORDER BY scope_name",
+            SCOPE_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(scopes)
+    }
+
+    // Получение scope по имени
+    pub async fn get_scope(&self, scope_name: &str) -> Result<Option<Scope>, ScopeError> {
+        let scope = sqlx::query_as::<_, Scope>(&format!(
+            "SELECT {} FROM oauth_scopes WHERE scope_name = $1",
+            SCOPE_COLUMNS
+        ))
+        .bind(scope_name)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(scope)
+    }
+
+    // Получение определений для списка scopes (неизвестные пропускаются)
+    pub async fn get_scopes(&self, scope_names: &[&str]) -> Result<Vec<Scope>, ScopeError> {
+        let names: Vec<String> = scope_names.iter().map(|s| s.to_string()).collect();
+        let scopes = sqlx::query_as::<_, Scope>(&format!(
+            "SELECT {} FROM oauth_scopes WHERE scope_name = ANY($1)",
+            SCOPE_COLUMNS
+        ))
+        .bind(&names)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(scopes)
+    }
+
+    // Scopes, выдаваемые при пустом scope в запросе авторизации
+    pub async fn default_scopes(&self) -> Result<Vec<String>, ScopeError> {
+        let names = sqlx::query_scalar::<_, String>(
+            "SELECT scope_name FROM oauth_scopes WHERE is_default = true ORDER BY scope_name"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(names)
+    }
+
+    // Переводы описаний для списка scopes
+    pub async fn get_translations(&self, scope_names: &[&str]) -> Result<Vec<ScopeTranslation>, ScopeError> {
+        let names: Vec<String> = scope_names.iter().map(|s| s.to_string()).collect();
+        let translations = sqlx::query_as::<_, ScopeTranslation>(
+            r#"
+            SELECT scope_name, locale, description
+            FROM oauth_scope_translations
+            WHERE scope_name = ANY($1)
+            ORDER BY scope_name, locale
+            "#
+        )
+        .bind(&names)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(translations)
+    }
+
+    // Создание scope вместе с переводами
+    pub async fn create_scope(&self, request: &CreateScopeRequest) -> Result<Scope, ScopeError> {
+        let mut tx = self.pool.begin().await?;
+
+        let scope = sqlx::query_as::<_, Scope>(&format!(
+            r#"
+            INSERT INTO oauth_scopes (
+                id, scope_name, description, requires_consent, is_default,
+                restricted_to_first_party, is_optional, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $8)
+            RETURNING {}
+            "#,
+            SCOPE_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(&request.scope_name)
+        .bind(&request.description)
+        .bind(request.requires_consent)
+        .bind(request.is_default)
+        .bind(request.restricted_to_first_party)
+        .bind(request.is_optional)
+        .bind(Utc::now())
+        .fetch_one(&mut *tx)
+        .await?;
+
+        for (locale, description) in &request.translations {
+            sqlx::query(
+                "INSERT INTO oauth_scope_translations (scope_name, locale, description) VALUES ($1, $2, $3)"
+            )
+            .bind(&scope.scope_name)
+            .bind(normalize_locale(locale))
+            .bind(description)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(scope)
+    }
+
+    // Частичное обновление описания и флагов scope
+    pub async fn update_scope(&self, scope_name: &str, request: &UpdateScopeRequest) -> Result<Scope, ScopeError> {
+        let scope = sqlx::query_as::<_, Scope>(&format!(
+            r#"
+            UPDATE oauth_scopes
+            SET description = COALESCE($2, description),
+                requires_consent = COALESCE($3, requires_consent),
+                is_default = COALESCE($4, is_default),
+                restricted_to_first_party = COALESCE($5, restricted_to_first_party),
+                is_optional = COALESCE($6, is_optional),
+                updated_at = NOW()
+            WHERE scope_name = $1
+            RETURNING {}
+            "#,
+            SCOPE_COLUMNS
+        ))
+        .bind(scope_name)
+        .bind(&request.description)
+        .bind(request.requires_consent)
+        .bind(request.is_default)
+        .bind(request.restricted_to_first_party)
+        .bind(request.is_optional)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        scope.ok_or(ScopeError::NotFound)
+    }
+
+    // Удаление scope, если он не используется клиентами
+    pub async fn delete_scope(&self, scope_name: &str) -> Result<(), ScopeError> {
+        let in_use = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM oauth_clients WHERE $1 = ANY(allowed_scopes))"
+        )
+        .bind(scope_name)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if in_use {
+            return Err(ScopeError::InUse);
+        }
+
+        let result = sqlx::query("DELETE FROM oauth_scopes WHERE scope_name = $1")
+            .bind(scope_name)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(ScopeError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    // Добавление или замена перевода описания
+    pub async fn set_translation(&self, scope_name: &str, locale: &str, description: &str) -> Result<ScopeTranslation, ScopeError> {
+        if self.get_scope(scope_name).await?.is_none() {
+            return Err(ScopeError::NotFound);
+        }
+
+        let translation = sqlx::query_as::<_, ScopeTranslation>(
+            r#"
+            INSERT INTO oauth_scope_translations (scope_name, locale, description)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (scope_name, locale) DO UPDATE SET description = EXCLUDED.description
+            RETURNING scope_name, locale, description
+            "#
+        )
+        .bind(scope_name)
+        .bind(normalize_locale(locale))
+        .bind(description)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(translation)
+    }
+
+    // Удаление перевода
+    pub async fn delete_translation(&self, scope_name: &str, locale: &str) -> Result<(), ScopeError> {
+        let result = sqlx::query("DELETE FROM oauth_scope_translations WHERE scope_name = $1 AND locale = $2")
+            .bind(scope_name)
+            .bind(normalize_locale(locale))
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(ScopeError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    // Проверка запрошенных scopes по реестру с учетом типа клиента
+    pub async fn validate_scopes(&self, requested: &[&str], is_first_party: bool) -> Result<(), ScopeError> {
+        let definitions = self.get_scopes(requested).await?;
+        check_scopes(requested, &definitions, is_first_party)
+    }
+}
+
+// Проверка scopes: все должны быть в реестре, restricted — только для first-party клиентов
+pub fn check_scopes(requested: &[&str], definitions: &[Scope], is_first_party: bool) -> Result<(), ScopeError> {
+    for name in requested {
+        match definitions.iter().find(|d| d.scope_name == *name) {
+            None => return Err(ScopeError::UnknownScope(name.to_string())),
+            Some(d) if d.restricted_to_first_party && !is_first_party => {
+                return Err(ScopeError::RestrictedScope(name.to_string()));
+            }
+            Some(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+// Scopes, на которые требуется явное согласие (неизвестные считаются требующими)
+pub fn scopes_requiring_consent<'a>(scopes: &[&'a str], definitions: &[Scope]) -> Vec<&'a str> {
+    scopes.iter()
+        .filter(|name| {
+            !definitions.iter()
+                .any(|d| d.scope_name == **name && !d.requires_consent)
+        })
+        .copied()
+        .collect()
+}
+
+// Необязательные scopes (пользователь может от них отказаться)
+pub fn optional_scopes<'a>(scopes: &[&'a str], definitions: &[Scope]) -> Vec<&'a str> {
+    scopes.iter()
+        .filter(|name| definitions.iter().any(|d| d.scope_name == **name && d.is_optional))
+        .copied()
+        .collect()
+}
+
+// Итоговый набор scopes после consent screen: обязательные выдаются всегда,
+// необязательные — только если пользователь их одобрил
+pub fn resolve_approved_scopes<'a>(requested: &[&'a str], optional: &[&str], approved: &[&str]) -> Vec<&'a str> {
+    requested.iter()
+        .filter(|name| !optional.contains(name) || approved.contains(name))
+        .copied()
+        .collect()
+}
+
+// Выбор описания scope: первый подходящий перевод из списка языков, иначе описание по умолчанию
+pub fn describe_scopes(
+    scope_names: &[&str],
+    definitions: &[Scope],
+    translations: &[ScopeTranslation],
+    locales: &[String],
+) -> Vec<ScopeDescription> {
+    let by_scope: HashMap<(&str, &str), &str> = translations.iter()
+        .map(|t| ((t.scope_name.as_str(), t.locale.as_str()), t.description.as_str()))
+        .collect();
+
+    scope_names.iter()
+        .map(|name| {
+            let translated = locales.iter()
+                .find_map(|locale| by_scope.get(&(*name, locale.as_str())).copied());
+            let fallback = definitions.iter()
+                .find(|d| d.scope_name == *name)
+                .map(|d| d.description.as_str());
+
+            ScopeDescription {
+                scope_name: name.to_string(),
+                description: translated.or(fallback).unwrap_or(name).to_string(),
+            }
+        })
+        .collect()
+}
+
+// Языки из заголовка Accept-Language в порядке предпочтения.
+// Для региональных тегов добавляется базовый язык: "en-US" -> ["en-us", "en"]
+pub fn parse_accept_language(header: &str) -> Vec<String> {
+    let mut weighted: Vec<(String, f32)> = header.split(',')
+        .filter_map(|part| {
+            let mut pieces = part.split(';');
+            let tag = normalize_locale(pieces.next()?);
+            if tag.is_empty() || tag == "*" {
+                return None;
+            }
+
+            let quality = pieces
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            (quality > 0.0).then_some((tag, quality))
+        })
+        .collect();
+
+    // Стабильная сортировка сохраняет порядок тегов с одинаковым весом
+    weighted.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut locales: Vec<String> = Vec::new();
+    for (tag, _) in weighted {
+        let base = tag.split('-').next().unwrap_or(&tag).to_string();
+        for locale in [tag, base] {
+            if !locales.contains(&locale) {
+                locales.push(locale);
+            }
+        }
+    }
+    locales
+}
+
+// Locale хранится в нижнем регистре: "en-US" -> "en-us"
+fn normalize_locale(locale: &str) -> String {
+    locale.trim().to_ascii_lowercase()
+}
@@ -0,0 +1,32 @@
+// Вспомогательные функции для работы со строкой scope (RFC 6749, раздел 3.3)
+
+// Scope, без которого refresh token не выдается
+pub const OFFLINE_ACCESS_SCOPE: &str = "offline_access";
+
+// Разбор строки scope в список без повторов с сохранением порядка
+pub fn parse_scope(scope: &str) -> Vec<&str> {
+    let mut scopes: Vec<&str> = Vec::new();
+    for s in scope.split_whitespace() {
+        if !scopes.contains(&s) {
+            scopes.push(s);
+        }
+    }
+    scopes
+}
+
+// Содержит ли строка scope указанное значение
+pub fn contains_scope(scope: &str, value: &str) -> bool {
+    scope.split_whitespace().any(|s| s == value)
+}
+
+// Все ли запрошенные scopes входят в выданные
+pub fn is_subset(requested: &str, granted: &str) -> bool {
+    let granted = parse_scope(granted);
+    parse_scope(requested).iter().all(|s| granted.contains(s))
+}
+
+// Допустимое имя scope: scope-token из RFC 6749 (печатные ASCII без пробела, '"' и '\')
+pub fn is_valid_scope_token(value: &str) -> bool {
+    !value.is_empty()
+        && value.bytes().all(|b| b == 0x21 || (0x23..=0x5B).contains(&b) || (0x5D..=0x7E).contains(&b))
+}
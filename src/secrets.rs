@@ -0,0 +1,95 @@
+// Генерация и хеширование одноразовых секретов (ссылки из писем и т.п.)
+// и шифрование секретов, хранимых в БД
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use hmac::{Hmac, Mac};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use sha2::{Digest, Sha256};
+
+// Случайная строка из букв и цифр
+pub fn random_secret(len: usize) -> String {
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+// SHA-256 секрета для хранения в БД (base64url, 43 символа)
+pub fn hash_secret(secret: &str) -> String {
+    general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(secret.as_bytes()))
+}
+
+// Ключ для отдельного назначения, производный от общего секрета: HMAC-SHA256(master, purpose).
+// Подпись одним ключом не подходит для другого назначения
+pub fn derive_key(master: &str, purpose: &str) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(master.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(purpose.as_bytes());
+    general_purpose::URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+}
+
+// Сравнение без раннего выхода, время не зависит от позиции первого отличия
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// Шифрование секретов, которые нужно восстанавливать (например, TOTP), ключом AES-256-GCM.
+// Хранимое значение: "v1:" + base64(nonce || ciphertext); aad привязывает его к владельцу
+#[derive(Clone)]
+pub struct SecretCipher {
+    cipher: Aes256Gcm,
+}
+
+const CIPHER_PREFIX: &str = "v1:";
+const NONCE_LEN: usize = 12;
+
+impl SecretCipher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self { cipher: Aes256Gcm::new(key.into()) }
+    }
+
+    // Ключ из переменной окружения: 32 байта в base64
+    pub fn from_base64(key: &str) -> Result<Self, String> {
+        let bytes = general_purpose::STANDARD
+            .decode(key.trim())
+            .map_err(|e| format!("invalid base64: {}", e))?;
+        let key: [u8; 32] = bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| format!("expected 32 bytes, got {}", bytes.len()))?;
+        Ok(Self::new(&key))
+    }
+
+    pub fn encrypt(&self, plaintext: &str, aad: &[u8]) -> String {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, Payload { msg: plaintext.as_bytes(), aad })
+            .expect("AES-GCM encryption does not fail for short inputs");
+
+        let mut stored = nonce.to_vec();
+        stored.extend_from_slice(&ciphertext);
+        format!("{}{}", CIPHER_PREFIX, general_purpose::STANDARD_NO_PAD.encode(stored))
+    }
+
+    // None, если значение повреждено, зашифровано другим ключом или принадлежит другому владельцу
+    pub fn decrypt(&self, stored: &str, aad: &[u8]) -> Option<String> {
+        let encoded = stored.strip_prefix(CIPHER_PREFIX)?;
+        let bytes = general_purpose::STANDARD_NO_PAD.decode(encoded).ok()?;
+        if bytes.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+            .ok()?;
+        String::from_utf8(plaintext).ok()
+    }
+
+    // Значения, сохраненные до включения шифрования
+    pub fn is_encrypted(stored: &str) -> bool {
+        stored.starts_with(CIPHER_PREFIX)
+    }
+}
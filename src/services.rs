@@ -1,8 +1,13 @@
 use sqlx::{Pool, Postgres};
-use crate::models::{RegisterUserRequest, User};
-use bcrypt::{hash, DEFAULT_COST};
+use crate::models::{
+    AdminUser, AdminUserList, AdminUserQuery, RegisterUserRequest, UpdateProfileRequest, User, UserProfile, UserStatus,
+};
+use std::sync::Arc;
+use crate::hashing_pool::HashingPool;
+use crate::password_hasher::{PasswordHashError, PasswordHasher};
 use uuid::Uuid;
 use chrono::Utc;
+use tokio::sync::OnceCell;
 
 #[derive(Debug)]
 pub enum RegistrationError {
@@ -25,13 +30,64 @@ impl std::fmt::Display for RegistrationError {
 
 impl std::error::Error for RegistrationError {}
 
+// Ошибки операций над существующим пользователем
+#[derive(Debug)]
+pub enum UserError {
+    DatabaseError(sqlx::Error),
+    NotFound,
+    HashError,
+    UsernameExists,
+}
+
+impl std::fmt::Display for UserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UserError::DatabaseError(e) => write!(f, "Ошибка базы данных: {}", e),
+            UserError::NotFound => write!(f, "Пользователь не найден"),
+            UserError::UsernameExists => write!(f, "Пользователь с таким именем уже существует"),
+            UserError::HashError => write!(f, "Ошибка хеширования пароля"),
+        }
+    }
+}
+
+impl std::error::Error for UserError {}
+
+// Колонки users, возвращаемые во всех запросах
+const USER_COLUMNS: &str = "id, username, email, password_hash, created_at, updated_at, \
+    email_verified, email_verified_at, status, password_reset_required";
+
+// Колонки пользователя в административном API
+const ADMIN_USER_COLUMNS: &str = "id, username, email, email_verified, status, suspended_at, suspension_reason, \
+    password_reset_required, is_admin, deletion_scheduled_for, created_at, updated_at";
+
+// Колонки профиля пользователя
+pub(crate) const PROFILE_COLUMNS: &str = "id, username, email, email_verified, display_name, locale, timezone, \
+    avatar_url, created_at, updated_at";
+
+// Пароль хеша, с которым сравнивается ввод при входе в несуществующий аккаунт
+const DUMMY_PASSWORD: &str = "dummy-password-for-unknown-accounts";
+
 pub struct UserService {
     pool: Pool<Postgres>,
+    hasher: PasswordHasher,
+    hashing_pool: Arc<HashingPool>,
+    // Хеш DUMMY_PASSWORD с текущими параметрами, вычисляется при первом обращении
+    dummy_hash: OnceCell<String>,
 }
 
 impl UserService {
-    pub fn new(pool: Pool<Postgres>) -> Self {
-        Self { pool }
+    pub fn new(pool: Pool<Postgres>, hasher: PasswordHasher, hashing_pool: Arc<HashingPool>) -> Self {
+        Self { pool, hasher, hashing_pool, dummy_hash: OnceCell::new() }
+    }
+
+    // Хеширование пароля в пуле хеширования
+    async fn hash_password(&self, password: &str) -> Result<String, PasswordHashError> {
+        let hasher = self.hasher.clone();
+        let password = password.to_string();
+        self.hashing_pool
+            .run(move || hasher.hash(&password))
+            .await
+            .map_err(|e| PasswordHashError::HashFailed(e.to_string()))?
     }
 
     // Проверка существования пользователя по username
@@ -47,7 +103,7 @@ impl UserService {
     }
 
     // Проверка существования пользователя по email
-    async fn email_exists(&self, email: &str) -> Result<bool, sqlx::Error> {
+    pub async fn email_exists(&self, email: &str) -> Result<bool, sqlx::Error> {
         let result = sqlx::query_scalar::<_, bool>(
             "SELECT EXISTS(SELECT 1 FROM users WHERE email = $1)"
         )
@@ -71,20 +127,21 @@ impl UserService {
         }
 
         // Хеширование пароля
-        let password_hash = hash(&request.password, DEFAULT_COST)
+        let password_hash = self.hash_password(&request.password).await
             .map_err(|_| RegistrationError::HashError)?;
 
         // Создание нового пользователя
         let user_id = Uuid::new_v4();
         let now = Utc::now();
 
-        let user = sqlx::query_as::<_, User>(
+        let user = sqlx::query_as::<_, User>(&format!(
             r#"
             INSERT INTO users (id, username, email, password_hash, created_at, updated_at)
             VALUES ($1, $2, $3, $4, $5, $6)
-            RETURNING id, username, email, password_hash, created_at, updated_at
-            "#
-        )
+            RETURNING {}
+            "#,
+            USER_COLUMNS
+        ))
         .bind(user_id)
         .bind(&request.username)
         .bind(&request.email)
@@ -100,9 +157,10 @@ impl UserService {
 
     // Получение пользователя по ID
     pub async fn get_user_by_id(&self, user_id: Uuid) -> Result<Option<User>, sqlx::Error> {
-        let user = sqlx::query_as::<_, User>(
-            "SELECT id, username, email, password_hash, created_at, updated_at FROM users WHERE id = $1"
-        )
+        let user = sqlx::query_as::<_, User>(&format!(
+            "SELECT {} FROM users WHERE id = $1",
+            USER_COLUMNS
+        ))
         .bind(user_id)
         .fetch_optional(&self.pool)
         .await?;
@@ -110,16 +168,251 @@ impl UserService {
         Ok(user)
     }
 
-    // Получение пользователя по email (для авторизации)
+    // Есть ли у пользователя роль администратора (неизвестный пользователь — нет)
+    pub async fn is_admin(&self, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        let is_admin = sqlx::query_scalar::<_, bool>("SELECT is_admin FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(is_admin.unwrap_or(false))
+    }
+
+        // Получение пользователя по email (для авторизации)
     pub async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, sqlx::Error> {
-        let user = sqlx::query_as::<_, User>(
-            "SELECT id, username, email, password_hash, created_at, updated_at FROM users WHERE email = $1"
-        )
+        let user = sqlx::query_as::<_, User>(&format!(
+            "SELECT {} FROM users WHERE email = $1",
+            USER_COLUMNS
+        ))
         .bind(email)
         .fetch_optional(&self.pool)
         .await?;
 
         Ok(user)
     }
+
+    // Установка нового пароля
+    pub async fn update_password(&self, user_id: Uuid, new_password: &str) -> Result<(), UserError> {
+        let password_hash = self.hash_password(new_password).await
+            .map_err(|_| UserError::HashError)?;
+
+        // Новый пароль снимает требование администратора сменить пароль
+        let result = sqlx::query(
+            "UPDATE users SET password_hash = $1, password_reset_required = false, updated_at = $2 WHERE id = $3"
+        )
+        .bind(&password_hash)
+        .bind(Utc::now())
+        .bind(user_id)
+        .execute(&self.pool)
+        .await
+        .map_err(UserError::DatabaseError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    // Проверка пароля при входе. Хеш с устаревшим алгоритмом или параметрами
+    // после успешной проверки заменяется на Argon2id с текущими параметрами
+    pub async fn verify_password(&self, user: &User, password: &str) -> Result<bool, UserError> {
+        let hasher = self.hasher.clone();
+        let (candidate, hash) = (password.to_string(), user.password_hash.clone());
+        let verified = self.hashing_pool
+            .run(move || hasher.verify(&candidate, &hash))
+            .await
+            .map_err(|_| UserError::HashError)?
+            .map_err(|_| UserError::HashError)?;
+
+        if verified && self.hasher.needs_rehash(&user.password_hash) {
+            if let Err(e) = self.rehash_password(user, password).await {
+                // Вход не зависит от обновления хеша, попытка повторится при следующем входе
+                eprintln!("Password rehash error: {}", e);
+            }
+        }
+
+        Ok(verified)
+    }
+
+    // Проверка пароля без аккаунта (неизвестный email, заблокированный вход): та же работа
+    // в пуле хеширования, что и при проверке настоящего хеша, чтобы время ответа
+    // не выдавало существование аккаунта
+    pub async fn verify_dummy_password(&self, password: &str) -> Result<(), UserError> {
+        let dummy_hash = self.dummy_hash
+            .get_or_try_init(|| self.hash_password(DUMMY_PASSWORD))
+            .await
+            .map_err(|_| UserError::HashError)?
+            .clone();
+
+        let hasher = self.hasher.clone();
+        let candidate = password.to_string();
+        self.hashing_pool
+            .run(move || hasher.verify(&candidate, &dummy_hash))
+            .await
+            .map_err(|_| UserError::HashError)?
+            .map_err(|_| UserError::HashError)?;
+
+        Ok(())
+    }
+
+    // Условие на старый хеш не дает затереть пароль, смененный параллельно
+    async fn rehash_password(&self, user: &User, password: &str) -> Result<(), UserError> {
+        let password_hash = self.hash_password(password).await
+            .map_err(|_| UserError::HashError)?;
+
+        sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2 AND password_hash = $3")
+            .bind(&password_hash)
+            .bind(user.id)
+            .bind(&user.password_hash)
+            .execute(&self.pool)
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    // Профиль пользователя
+    pub async fn get_profile(&self, user_id: Uuid) -> Result<Option<UserProfile>, sqlx::Error> {
+        sqlx::query_as::<_, UserProfile>(&format!(
+            "SELECT {} FROM users WHERE id = $1",
+            PROFILE_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    // Изменение профиля: NULL в параметре — поле не меняется, пустая строка очищает поле.
+    // Уникальность username гарантирует ограничение таблицы, без отдельной проверки и гонки
+    pub async fn update_profile(&self, user_id: Uuid, request: &UpdateProfileRequest) -> Result<UserProfile, UserError> {
+        sqlx::query_as::<_, UserProfile>(&format!(
+            r#"
+            UPDATE users SET
+                username = COALESCE($2, username),
+                display_name = CASE WHEN $3::TEXT IS NULL THEN display_name ELSE NULLIF($3, '') END,
+                locale = CASE WHEN $4::TEXT IS NULL THEN locale ELSE NULLIF($4, '') END,
+                timezone = CASE WHEN $5::TEXT IS NULL THEN timezone ELSE NULLIF($5, '') END,
+                avatar_url = CASE WHEN $6::TEXT IS NULL THEN avatar_url ELSE NULLIF($6, '') END,
+                updated_at = $7
+            WHERE id = $1
+            RETURNING {}
+            "#,
+            PROFILE_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(request.username.as_deref())
+        .bind(request.display_name.as_deref().map(str::trim))
+        .bind(request.locale.as_deref())
+        .bind(request.timezone.as_deref())
+        .bind(request.avatar_url.as_deref())
+        .bind(Utc::now())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.is_unique_violation() => UserError::UsernameExists,
+            e => UserError::DatabaseError(e),
+        })?
+        .ok_or(UserError::NotFound)
+    }
+
+    // ============= Административные операции =============
+
+    // Поиск пользователей по подстроке username/email с фильтрами, новые первыми
+    pub async fn list_users(&self, query: &AdminUserQuery) -> Result<AdminUserList, sqlx::Error> {
+        let pattern = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()).map(like_pattern);
+        let status = query.status.map(|status| status.as_str());
+        let filter = r#"
+            WHERE ($1::TEXT IS NULL OR username ILIKE $1 ESCAPE '\' OR email ILIKE $1 ESCAPE '\')
+              AND ($2::TEXT IS NULL OR status = $2)
+              AND ($3::BOOLEAN IS NULL OR email_verified = $3)
+        "#;
+
+        let total = sqlx::query_scalar::<_, i64>(&format!("SELECT COUNT(*) FROM users {}", filter))
+            .bind(&pattern)
+            .bind(status)
+            .bind(query.email_verified)
+            .fetch_one(&self.pool)
+            .await?;
+
+        let users = sqlx::query_as::<_, AdminUser>(&format!(
+            "SELECT {} FROM users {} ORDER BY created_at DESC, id LIMIT $4 OFFSET $5",
+            ADMIN_USER_COLUMNS, filter
+        ))
+        .bind(&pattern)
+        .bind(status)
+        .bind(query.email_verified)
+        .bind(query.per_page())
+        .bind(query.offset())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(AdminUserList {
+            users,
+            page: query.page(),
+            per_page: query.per_page(),
+            total,
+        })
+    }
+
+    pub async fn get_admin_user(&self, user_id: Uuid) -> Result<Option<AdminUser>, sqlx::Error> {
+        sqlx::query_as::<_, AdminUser>(&format!(
+            "SELECT {} FROM users WHERE id = $1",
+            ADMIN_USER_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    // Приостановка или восстановление пользователя. Сессии и токены отзывает вызывающий
+    pub async fn set_status(
+        &self,
+        user_id: Uuid,
+        status: UserStatus,
+        reason: Option<&str>,
+    ) -> Result<AdminUser, UserError> {
+        let suspended = status == UserStatus::Suspended;
+        sqlx::query_as::<_, AdminUser>(&format!(
+            r#"
+            UPDATE users SET
+                status = $2,
+                suspended_at = CASE WHEN $3 THEN COALESCE(suspended_at, $5) END,
+                suspension_reason = CASE WHEN $3 THEN $4 END,
+                updated_at = $5
+            WHERE id = $1
+            RETURNING {}
+            "#,
+            ADMIN_USER_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(status.as_str())
+        .bind(suspended)
+        .bind(reason)
+        .bind(Utc::now())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(UserError::DatabaseError)?
+        .ok_or(UserError::NotFound)
+    }
+
+    // Требование сменить пароль: вход по паролю запрещен до сброса по ссылке из письма
+    pub async fn require_password_reset(&self, user_id: Uuid) -> Result<User, UserError> {
+        sqlx::query_as::<_, User>(&format!(
+            "UPDATE users SET password_reset_required = true, updated_at = $2 WHERE id = $1 RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(Utc::now())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(UserError::DatabaseError)?
+        .ok_or(UserError::NotFound)
+    }
 }
 
+// Шаблон ILIKE для поиска подстроки: %, _ и \ в запросе ищутся буквально
+pub fn like_pattern(query: &str) -> String {
+    let escaped = query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
@@ -0,0 +1,112 @@
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+use chrono::{DateTime, Duration, Utc};
+
+// Серверный учет браузерных сессий: cookie содержит session_id,
+// отозванная в user_sessions сессия перестает действовать при следующем запросе
+// Как часто обновляется время последней активности: проверка отзыва выполняется
+// на каждом запросе, запись в БД — не чаще этого интервала
+pub const SESSION_TOUCH_INTERVAL_SECS: i64 = 300;
+
+#[derive(Clone)]
+pub struct SessionService {
+    pool: Pool<Postgres>,
+}
+
+impl SessionService {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    // Регистрация сессии при входе
+    pub async fn create_session(
+        &self,
+        user_id: Uuid,
+        user_agent: Option<&str>,
+        ip_address: Option<&str>,
+    ) -> Result<Uuid, sqlx::Error> {
+        let session_id = Uuid::new_v4();
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO user_sessions (id, user_id, user_agent, ip_address, created_at, last_seen_at)
+            VALUES ($1, $2, $3, $4, $5, $5)
+            "#
+        )
+        .bind(session_id)
+        .bind(user_id)
+        .bind(user_agent)
+        .bind(ip_address)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(session_id)
+    }
+
+    // Проверка, что сессия не отозвана, с обновлением времени последней активности
+    pub async fn touch_session(&self, session_id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        let last_seen_at = sqlx::query_scalar::<_, DateTime<Utc>>(
+            "SELECT last_seen_at FROM user_sessions WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL"
+        )
+        .bind(session_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(last_seen_at) = last_seen_at else {
+            return Ok(false);
+        };
+
+        let now = Utc::now();
+        let stale_before = now - Duration::seconds(SESSION_TOUCH_INTERVAL_SECS);
+        if last_seen_at < stale_before {
+            // Условие на last_seen_at: из параллельных запросов пишет только один
+            sqlx::query("UPDATE user_sessions SET last_seen_at = $1 WHERE id = $2 AND last_seen_at < $3")
+                .bind(now)
+                .bind(session_id)
+                .bind(stale_before)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(true)
+    }
+
+    // Отзыв одной сессии (выход)
+    pub async fn revoke_session(&self, session_id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE user_sessions SET revoked_at = $1 WHERE id = $2 AND revoked_at IS NULL")
+            .bind(Utc::now())
+            .bind(session_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Отзыв всех сессий пользователя (сброс пароля и т.п.)
+    pub async fn revoke_user_sessions(&self, user_id: Uuid) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("UPDATE user_sessions SET revoked_at = $1 WHERE user_id = $2 AND revoked_at IS NULL")
+            .bind(Utc::now())
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Отзыв всех сессий пользователя, кроме текущей (смена пароля)
+    pub async fn revoke_other_sessions(&self, user_id: Uuid, current_session_id: Uuid) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE user_sessions SET revoked_at = $1 WHERE user_id = $2 AND id <> $3 AND revoked_at IS NULL"
+        )
+        .bind(Utc::now())
+        .bind(user_id)
+        .bind(current_session_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
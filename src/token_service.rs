@@ -1,44 +1,90 @@
-use jsonwebtoken::{encode, decode, Header, Validation, EncodingKey, DecodingKey, Algorithm};
+use jsonwebtoken::{encode, decode, decode_header, Header, Validation, EncodingKey, DecodingKey, Algorithm};
+use jsonwebtoken::errors::ErrorKind;
 use sqlx::{Pool, Postgres};
 use uuid::Uuid;
-use chrono::{Utc, Duration};
+use chrono::{DateTime, Duration, Utc, TimeZone};
 use rand::{thread_rng, Rng};
 use rand::distributions::Alphanumeric;
-use crate::models::{TokenClaims, OAuthToken};
+use crate::config::{AppConfig, TokenLifetimes};
+use crate::models::{TokenClaims, OAuthToken, OAuthClient, AuthContext, IntrospectionResponse};
+use crate::secrets::hash_secret;
+
+// Тип JWT access token по RFC 9068
+pub const ACCESS_TOKEN_JWT_TYPE: &str = "at+jwt";
+
+// Как часто обновляется время последнего использования токена
+pub const TOKEN_TOUCH_INTERVAL_SECS: i64 = 300;
+
+// Колонки oauth_tokens, возвращаемые во всех запросах
+const TOKEN_COLUMNS: &str = "id, access_token_hash, refresh_token_hash, client_id, user_id, scope, \
+    token_type, expires_at, refresh_expires_at, revoked, created_at, jti, auth_time, acr, grant_scope, \
+    refresh_absolute_expires_at, amr";
+
+// Выпускаемый refresh token и сроки его действия
+pub struct NewRefreshToken<'a> {
+    pub token: &'a str,
+    pub expires_at: DateTime<Utc>,
+    pub absolute_expires_at: Option<DateTime<Utc>>,
+}
 
 pub struct TokenService {
     pool: Pool<Postgres>,
     jwt_secret: String,
-    access_token_ttl: i64,  // seconds
-    refresh_token_ttl: i64, // seconds
+    issuer: String,
+    audience: String,
+    lifetimes: TokenLifetimes,
 }
 
 impl TokenService {
-    pub fn new(pool: Pool<Postgres>, jwt_secret: String) -> Self {
+    pub fn new(pool: Pool<Postgres>, jwt_secret: String, config: &AppConfig) -> Self {
         Self {
             pool,
             jwt_secret,
-            access_token_ttl: 3600,        // 1 hour
-            refresh_token_ttl: 2592000,    // 30 days
+            issuer: config.issuer.clone(),
+            audience: config.access_token_audience.clone(),
+            lifetimes: config.token_lifetimes,
         }
     }
 
-    // Генерация JWT access token
-    pub fn create_jwt(&self, user_id: Option<Uuid>, client_id: &str, scope: &str) -> Result<String, jsonwebtoken::errors::Error> {
+    // Время жизни токенов для клиента с учетом глобальных значений
+    pub fn lifetimes_for(&self, client: &OAuthClient) -> TokenLifetimes {
+        self.lifetimes.for_client(client)
+    }
+
+    // Формирование claims access token (RFC 9068)
+    pub fn build_claims(
+        &self,
+        user_id: Option<Uuid>,
+        client_id: &str,
+        scope: &str,
+        auth_context: Option<&AuthContext>,
+        access_token_ttl: i64,
+    ) -> TokenClaims {
         let now = Utc::now().timestamp();
-        let exp = now + self.access_token_ttl;
 
-        let claims = TokenClaims {
+        TokenClaims {
+            iss: self.issuer.clone(),
             sub: user_id.map(|id| id.to_string()).unwrap_or_else(|| client_id.to_string()),
+            aud: self.audience.clone(),
             client_id: client_id.to_string(),
             scope: scope.to_string(),
-            exp,
+            jti: Uuid::new_v4().to_string(),
+            exp: now + access_token_ttl,
             iat: now,
-        };
+            auth_time: auth_context.map(|ctx| ctx.auth_time.timestamp()),
+            acr: auth_context.map(|ctx| ctx.acr.clone()),
+            amr: auth_context.map(|ctx| ctx.amr.clone()),
+        }
+    }
+
+    // Генерация JWT access token
+    pub fn create_jwt(&self, claims: &TokenClaims) -> Result<String, jsonwebtoken::errors::Error> {
+        let mut header = Header::new(Algorithm::HS256);
+        header.typ = Some(ACCESS_TOKEN_JWT_TYPE.to_string());
 
         let token = encode(
-            &Header::default(),
-            &claims,
+            &header,
+            claims,
             &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
         )?;
 
@@ -47,7 +93,21 @@ impl TokenService {
 
     // Верификация и декодирование JWT
     pub fn verify_jwt(&self, token: &str) -> Result<TokenClaims, jsonwebtoken::errors::Error> {
-        let validation = Validation::new(Algorithm::HS256);
+        // RFC 9068: access token должен иметь typ "at+jwt"
+        let header = decode_header(token)?;
+        let typ_matches = header.typ
+            .as_deref()
+            .map(|typ| typ.eq_ignore_ascii_case(ACCESS_TOKEN_JWT_TYPE) || typ.eq_ignore_ascii_case("application/at+jwt"))
+            .unwrap_or(false);
+        if !typ_matches {
+            return Err(ErrorKind::InvalidToken.into());
+        }
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&self.audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
         let token_data = decode::<TokenClaims>(
             token,
             &DecodingKey::from_secret(self.jwt_secret.as_bytes()),
@@ -57,6 +117,15 @@ impl TokenService {
         Ok(token_data.claims)
     }
 
+    // Генерация opaque (reference) access token
+    pub fn generate_opaque_token(&self) -> String {
+        thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(48)
+            .map(char::from)
+            .collect()
+    }
+
     // Генерация случайного refresh token
     pub fn generate_refresh_token(&self) -> String {
         thread_rng()
@@ -70,38 +139,45 @@ impl TokenService {
     pub async fn store_tokens(
         &self,
         access_token: &str,
-        refresh_token: Option<&str>,
-        client_id: &str,
+        refresh_token: Option<&NewRefreshToken<'_>>,
         user_id: Option<Uuid>,
-        scope: &str,
+        claims: &TokenClaims,
+        grant_scope: &str,
     ) -> Result<OAuthToken, sqlx::Error> {
         let token_id = Uuid::new_v4();
         let now = Utc::now();
-        let access_expires_at = now + Duration::seconds(self.access_token_ttl);
-        let refresh_expires_at = refresh_token.map(|_| now + Duration::seconds(self.refresh_token_ttl));
+        let access_expires_at = Utc.timestamp_opt(claims.exp, 0).single().unwrap_or(now);
+        let auth_time = claims.auth_time.and_then(|ts| Utc.timestamp_opt(ts, 0).single());
 
-        let token = sqlx::query_as::<_, OAuthToken>(
+        let token = sqlx::query_as::<_, OAuthToken>(&format!(
             r#"
             INSERT INTO oauth_tokens (
-                id, access_token, refresh_token, client_id, user_id, scope,
-                token_type, expires_at, refresh_expires_at, revoked, created_at
+                id, access_token_hash, refresh_token_hash, client_id, user_id, scope,
+                token_type, expires_at, refresh_expires_at, revoked, created_at,
+                jti, auth_time, acr, grant_scope, refresh_absolute_expires_at, amr
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
-            RETURNING id, access_token, refresh_token, client_id, user_id, scope,
-                      token_type, expires_at, refresh_expires_at, revoked, created_at
-            "#
-        )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+            RETURNING {}
+            "#,
+            TOKEN_COLUMNS
+        ))
         .bind(token_id)
-        .bind(access_token)
-        .bind(refresh_token)
-        .bind(client_id)
+        .bind(hash_secret(access_token))
+        .bind(refresh_token.map(|r| hash_secret(r.token)))
+        .bind(&claims.client_id)
         .bind(user_id)
-        .bind(scope)
+        .bind(&claims.scope)
         .bind("Bearer")
         .bind(access_expires_at)
-        .bind(refresh_expires_at)
+        .bind(refresh_token.map(|r| r.expires_at))
         .bind(false)
         .bind(now)
+        .bind(&claims.jti)
+        .bind(auth_time)
+        .bind(&claims.acr)
+        .bind(grant_scope)
+        .bind(refresh_token.and_then(|r| r.absolute_expires_at))
+        .bind(&claims.amr)
         .fetch_one(&self.pool)
         .await?;
 
@@ -110,32 +186,103 @@ impl TokenService {
 
     // Проверка токена в БД (не отозван ли)
     pub async fn validate_token(&self, access_token: &str) -> Result<Option<OAuthToken>, sqlx::Error> {
-        let token = sqlx::query_as::<_, OAuthToken>(
+        let token = sqlx::query_as::<_, OAuthToken>(&format!(
             r#"
-            SELECT id, access_token, refresh_token, client_id, user_id, scope,
-                   token_type, expires_at, refresh_expires_at, revoked, created_at
+            SELECT {}
             FROM oauth_tokens
-            WHERE access_token = $1 AND revoked = false AND expires_at > NOW()
-            "#
-        )
-        .bind(access_token)
+            WHERE access_token_hash = $1 AND revoked = false AND expires_at > NOW()
+            "#,
+            TOKEN_COLUMNS
+        ))
+        .bind(hash_secret(access_token))
         .fetch_optional(&self.pool)
         .await?;
 
         Ok(token)
     }
 
+    // Проверка access token любого формата: JWT проверяется по подписи и в БД,
+    // opaque token разрешается только через запись в oauth_tokens
+    pub async fn resolve_access_token(&self, access_token: &str) -> Result<Option<TokenClaims>, sqlx::Error> {
+        let jwt_claims = if Self::looks_like_jwt(access_token) {
+            match self.verify_jwt(access_token) {
+                Ok(claims) => Some(claims),
+                Err(_) => return Ok(None),
+            }
+        } else {
+            None
+        };
+
+        // Проверка токена в БД (не отозван ли)
+        let record = match self.validate_token(access_token).await? {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+
+        self.touch_token(record.id).await?;
+
+        Ok(Some(jwt_claims.unwrap_or_else(|| self.claims_from_record(&record))))
+    }
+
+    // Introspection токена (RFC 7662): access или refresh token
+    pub async fn introspect(&self, token: &str, token_type_hint: Option<&str>) -> Result<IntrospectionResponse, sqlx::Error> {
+        let check_refresh_first = token_type_hint == Some("refresh_token");
+
+        if !check_refresh_first {
+            if let Some(claims) = self.resolve_access_token(token).await? {
+                return Ok(claims.into());
+            }
+        }
+
+        if let Some(record) = self.get_token_by_refresh(token).await? {
+            let mut response: IntrospectionResponse = self.claims_from_record(&record).into();
+            response.scope = Some(record.granted_scope().to_string());
+            response.exp = record.refresh_expires_at.map(|t| t.timestamp());
+            return Ok(response);
+        }
+
+        if check_refresh_first {
+            if let Some(claims) = self.resolve_access_token(token).await? {
+                return Ok(claims.into());
+            }
+        }
+
+        Ok(IntrospectionResponse::default())
+    }
+
+    // Восстановление claims opaque токена из записи в БД
+    fn claims_from_record(&self, record: &OAuthToken) -> TokenClaims {
+        TokenClaims {
+            iss: self.issuer.clone(),
+            sub: record.user_id.map(|id| id.to_string()).unwrap_or_else(|| record.client_id.clone()),
+            aud: self.audience.clone(),
+            client_id: record.client_id.clone(),
+            scope: record.scope.clone(),
+            jti: record.jti.clone().unwrap_or_else(|| record.id.to_string()),
+            exp: record.expires_at.timestamp(),
+            iat: record.created_at.timestamp(),
+            auth_time: record.auth_time.map(|t| t.timestamp()),
+            acr: record.acr.clone(),
+            amr: record.amr.clone(),
+        }
+    }
+
+    // JWT состоит из трех base64url-частей, разделенных точками
+    fn looks_like_jwt(token: &str) -> bool {
+        token.split('.').count() == 3
+    }
+
     // Получение токена по refresh_token
     pub async fn get_token_by_refresh(&self, refresh_token: &str) -> Result<Option<OAuthToken>, sqlx::Error> {
-        let token = sqlx::query_as::<_, OAuthToken>(
+        let token = sqlx::query_as::<_, OAuthToken>(&format!(
             r#"
-            SELECT id, access_token, refresh_token, client_id, user_id, scope,
-                   token_type, expires_at, refresh_expires_at, revoked, created_at
+            SELECT {}
             FROM oauth_tokens
-            WHERE refresh_token = $1 AND revoked = false AND refresh_expires_at > NOW()
-            "#
-        )
-        .bind(refresh_token)
+            WHERE refresh_token_hash = $1 AND revoked = false AND refresh_expires_at > NOW()
+            "#,
+            TOKEN_COLUMNS
+        ))
+        .bind(hash_secret(refresh_token))
         .fetch_optional(&self.pool)
         .await?;
 
@@ -148,13 +295,80 @@ impl TokenService {
             r#"
             UPDATE oauth_tokens
             SET revoked = true
-            WHERE access_token = $1 OR refresh_token = $1
+            WHERE access_token_hash = $1 OR refresh_token_hash = $1
+            "#
+        )
+        .bind(hash_secret(token))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Отзыв записи по id (токены в БД хранятся только в виде хешей)
+    pub async fn revoke_token_record(&self, token_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE oauth_tokens SET revoked = true WHERE id = $1")
+            .bind(token_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Отметка последнего использования токена не чаще TOKEN_TOUCH_INTERVAL_SECS:
+    // проверка токена на каждом запросе не превращается в запись
+    async fn touch_token(&self, token_id: Uuid) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            UPDATE oauth_tokens SET last_used_at = $1
+            WHERE id = $2 AND (last_used_at IS NULL OR last_used_at < $3)
+            "#
+        )
+        .bind(now)
+        .bind(token_id)
+        .bind(now - Duration::seconds(TOKEN_TOUCH_INTERVAL_SECS))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Отзыв всех access и refresh токенов пользователя для клиента
+    pub async fn revoke_user_client_tokens(&self, user_id: Uuid, client_id: &str) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE oauth_tokens
+            SET revoked = true
+            WHERE user_id = $1 AND client_id = $2 AND revoked = false
             "#
         )
-        .bind(token)
+        .bind(user_id)
+        .bind(client_id)
         .execute(&self.pool)
         .await?;
 
+        Ok(result.rows_affected())
+    }
+
+    // Отзыв всех токенов пользователя (смена или сброс пароля)
+    pub async fn revoke_user_tokens(&self, user_id: Uuid) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("UPDATE oauth_tokens SET revoked = true WHERE user_id = $1 AND revoked = false")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Отзыв токена по jti (для deny list)
+    pub async fn revoke_by_jti(&self, jti: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE oauth_tokens SET revoked = true WHERE jti = $1")
+            .bind(jti)
+            .execute(&self.pool)
+            .await?;
+
         Ok(result.rows_affected() > 0)
     }
 
@@ -171,8 +385,4 @@ impl TokenService {
 
         Ok(result.rows_affected())
     }
-
-    pub fn get_access_token_ttl(&self) -> i64 {
-        self.access_token_ttl
-    }
 }
@@ -0,0 +1,270 @@
+// Проверка церемоний WebAuthn (регистрация и вход по ключу доступа), алгоритм ES256
+use base64::{engine::general_purpose, Engine as _};
+use ciborium::value::Value;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use rand::{thread_rng, RngCore};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use crate::config::WebAuthnConfig;
+use crate::secrets::constant_time_eq;
+
+// COSE идентификатор алгоритма ES256 (ECDSA P-256 с SHA-256)
+pub const COSE_ALG_ES256: i64 = -7;
+
+pub const CEREMONY_CREATE: &str = "webauthn.create";
+pub const CEREMONY_GET: &str = "webauthn.get";
+
+// Флаги authenticator data
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+// rpIdHash (32) + flags (1) + signCount (4)
+const AUTH_DATA_MIN_LEN: usize = 37;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebAuthnError {
+    Malformed(&'static str),
+    CeremonyMismatch,
+    ChallengeMismatch,
+    OriginMismatch,
+    RpIdMismatch,
+    UserNotPresent,
+    UserNotVerified,
+    UnsupportedAlgorithm,
+    InvalidSignature,
+    CounterRegression,
+}
+
+impl std::fmt::Display for WebAuthnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebAuthnError::Malformed(what) => write!(f, "Malformed {}", what),
+            WebAuthnError::CeremonyMismatch => write!(f, "Unexpected ceremony type"),
+            WebAuthnError::ChallengeMismatch => write!(f, "Challenge mismatch"),
+            WebAuthnError::OriginMismatch => write!(f, "Origin mismatch"),
+            WebAuthnError::RpIdMismatch => write!(f, "Relying party ID mismatch"),
+            WebAuthnError::UserNotPresent => write!(f, "User presence was not confirmed"),
+            WebAuthnError::UserNotVerified => write!(f, "User verification is required"),
+            WebAuthnError::UnsupportedAlgorithm => write!(f, "Only ES256 keys are supported"),
+            WebAuthnError::InvalidSignature => write!(f, "Invalid signature"),
+            WebAuthnError::CounterRegression => write!(f, "Signature counter did not increase"),
+        }
+    }
+}
+
+impl std::error::Error for WebAuthnError {}
+
+// Поля clientDataJSON, которые проверяет сервер
+#[derive(Debug, Deserialize)]
+pub struct ClientData {
+    #[serde(rename = "type")]
+    pub ceremony: String,
+    pub challenge: String,
+    pub origin: String,
+    #[serde(rename = "crossOrigin", default)]
+    pub cross_origin: bool,
+}
+
+pub fn parse_client_data(client_data_json: &[u8]) -> Result<ClientData, WebAuthnError> {
+    serde_json::from_slice(client_data_json).map_err(|_| WebAuthnError::Malformed("client data"))
+}
+
+// Новый ключ доступа после успешной регистрации
+#[derive(Debug, Clone)]
+pub struct RegisteredCredential {
+    // Идентификатор учетных данных (base64url)
+    pub credential_id: String,
+    // Открытый ключ P-256 в несжатом SEC1 формате
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+    pub user_verified: bool,
+}
+
+// Ответ аутентификатора при входе
+pub struct Assertion<'a> {
+    pub client_data_json: &'a [u8],
+    pub authenticator_data: &'a [u8],
+    pub signature: &'a [u8],
+}
+
+struct AuthenticatorData<'a> {
+    rp_id_hash: &'a [u8],
+    flags: u8,
+    sign_count: u32,
+    // Идентификатор и COSE ключ (только при регистрации)
+    attested: Option<(Vec<u8>, Value)>,
+}
+
+// Случайный challenge (32 байта, base64url)
+pub fn new_challenge() -> String {
+    let mut bytes = [0u8; 32];
+    thread_rng().fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn encode_b64url(bytes: &[u8]) -> String {
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn decode_b64url(value: &str) -> Option<Vec<u8>> {
+    general_purpose::URL_SAFE_NO_PAD.decode(value.trim_end_matches('=')).ok()
+}
+
+// Проверка регистрации (navigator.credentials.create). Аттестация не проверяется:
+// запрашивается attestation "none", ключ привязывается к уже вошедшему пользователю
+pub fn verify_registration(
+    config: &WebAuthnConfig,
+    expected_challenge: &str,
+    client_data_json: &[u8],
+    attestation_object: &[u8],
+) -> Result<RegisteredCredential, WebAuthnError> {
+    verify_client_data(config, CEREMONY_CREATE, expected_challenge, client_data_json)?;
+
+    let attestation: Value = ciborium::de::from_reader(attestation_object)
+        .map_err(|_| WebAuthnError::Malformed("attestation object"))?;
+    let auth_data = attestation
+        .as_map()
+        .and_then(|map| map.iter().find(|(key, _)| key.as_text() == Some("authData")))
+        .and_then(|(_, value)| value.as_bytes())
+        .ok_or(WebAuthnError::Malformed("attestation object"))?;
+
+    let data = parse_authenticator_data(auth_data)?;
+    verify_rp_and_flags(config, &data, false)?;
+
+    let (credential_id, cose_key) = data.attested.ok_or(WebAuthnError::Malformed("attested credential"))?;
+
+    Ok(RegisteredCredential {
+        credential_id: encode_b64url(&credential_id),
+        public_key: cose_key_to_sec1(&cose_key)?,
+        sign_count: data.sign_count,
+        user_verified: data.flags & FLAG_USER_VERIFIED != 0,
+    })
+}
+
+// Проверка входа (navigator.credentials.get). Возвращает новое значение счетчика подписей
+pub fn verify_assertion(
+    config: &WebAuthnConfig,
+    expected_challenge: &str,
+    assertion: &Assertion<'_>,
+    public_key: &[u8],
+    stored_sign_count: u32,
+    require_user_verification: bool,
+) -> Result<u32, WebAuthnError> {
+    verify_client_data(config, CEREMONY_GET, expected_challenge, assertion.client_data_json)?;
+
+    let data = parse_authenticator_data(assertion.authenticator_data)?;
+    verify_rp_and_flags(config, &data, require_user_verification)?;
+
+    let key = VerifyingKey::from_sec1_bytes(public_key).map_err(|_| WebAuthnError::Malformed("public key"))?;
+    let signature = Signature::from_der(assertion.signature).map_err(|_| WebAuthnError::InvalidSignature)?;
+
+    // Подписываются authenticatorData || SHA-256(clientDataJSON)
+    let mut signed = assertion.authenticator_data.to_vec();
+    signed.extend_from_slice(&Sha256::digest(assertion.client_data_json));
+    key.verify(&signed, &signature).map_err(|_| WebAuthnError::InvalidSignature)?;
+
+    // Аутентификаторы без счетчика всегда присылают 0; иначе счетчик должен расти (защита от клонов)
+    if (data.sign_count != 0 || stored_sign_count != 0) && data.sign_count <= stored_sign_count {
+        return Err(WebAuthnError::CounterRegression);
+    }
+
+    Ok(data.sign_count)
+}
+
+fn verify_client_data(
+    config: &WebAuthnConfig,
+    ceremony: &str,
+    expected_challenge: &str,
+    client_data_json: &[u8],
+) -> Result<(), WebAuthnError> {
+    let client_data = parse_client_data(client_data_json)?;
+
+    if client_data.ceremony != ceremony {
+        return Err(WebAuthnError::CeremonyMismatch);
+    }
+    if !constant_time_eq(client_data.challenge.as_bytes(), expected_challenge.as_bytes()) {
+        return Err(WebAuthnError::ChallengeMismatch);
+    }
+    if client_data.origin != config.origin || client_data.cross_origin {
+        return Err(WebAuthnError::OriginMismatch);
+    }
+
+    Ok(())
+}
+
+fn verify_rp_and_flags(
+    config: &WebAuthnConfig,
+    data: &AuthenticatorData<'_>,
+    require_user_verification: bool,
+) -> Result<(), WebAuthnError> {
+    if !constant_time_eq(data.rp_id_hash, &Sha256::digest(config.rp_id.as_bytes())) {
+        return Err(WebAuthnError::RpIdMismatch);
+    }
+    if data.flags & FLAG_USER_PRESENT == 0 {
+        return Err(WebAuthnError::UserNotPresent);
+    }
+    if require_user_verification && data.flags & FLAG_USER_VERIFIED == 0 {
+        return Err(WebAuthnError::UserNotVerified);
+    }
+
+    Ok(())
+}
+
+fn parse_authenticator_data(data: &[u8]) -> Result<AuthenticatorData<'_>, WebAuthnError> {
+    if data.len() < AUTH_DATA_MIN_LEN {
+        return Err(WebAuthnError::Malformed("authenticator data"));
+    }
+
+    let flags = data[32];
+    let sign_count = u32::from_be_bytes([data[33], data[34], data[35], data[36]]);
+
+    let attested = if flags & FLAG_ATTESTED_CREDENTIAL != 0 {
+        // AAGUID (16) + длина идентификатора (2) + идентификатор + COSE ключ
+        let rest = &data[AUTH_DATA_MIN_LEN..];
+        if rest.len() < 18 {
+            return Err(WebAuthnError::Malformed("attested credential"));
+        }
+        let id_len = u16::from_be_bytes([rest[16], rest[17]]) as usize;
+        let credential_id = rest
+            .get(18..18 + id_len)
+            .ok_or(WebAuthnError::Malformed("attested credential"))?
+            .to_vec();
+        let cose_key: Value = ciborium::de::from_reader(&rest[18 + id_len..])
+            .map_err(|_| WebAuthnError::Malformed("credential public key"))?;
+        Some((credential_id, cose_key))
+    } else {
+        None
+    };
+
+    Ok(AuthenticatorData { rp_id_hash: &data[..32], flags, sign_count, attested })
+}
+
+// COSE_Key EC2 / P-256 / ES256 -> несжатая точка SEC1 (0x04 || x || y)
+fn cose_key_to_sec1(key: &Value) -> Result<Vec<u8>, WebAuthnError> {
+    let map = key.as_map().ok_or(WebAuthnError::Malformed("credential public key"))?;
+    let field = |label: i64| {
+        map.iter()
+            .find(|(key, _)| key.as_integer().map(i128::from) == Some(label as i128))
+            .map(|(_, value)| value)
+    };
+    let int = |label: i64| field(label).and_then(Value::as_integer).map(i128::from);
+
+    // kty = EC2, alg = ES256, crv = P-256
+    if int(1) != Some(2) || int(3) != Some(COSE_ALG_ES256 as i128) || int(-1) != Some(1) {
+        return Err(WebAuthnError::UnsupportedAlgorithm);
+    }
+
+    let x = field(-2).and_then(Value::as_bytes).filter(|x| x.len() == 32);
+    let y = field(-3).and_then(Value::as_bytes).filter(|y| y.len() == 32);
+    let (x, y) = x.zip(y).ok_or(WebAuthnError::Malformed("credential public key"))?;
+
+    let mut point = Vec::with_capacity(65);
+    point.push(0x04);
+    point.extend_from_slice(x);
+    point.extend_from_slice(y);
+
+    VerifyingKey::from_sec1_bytes(&point).map_err(|_| WebAuthnError::Malformed("credential public key"))?;
+    Ok(point)
+}
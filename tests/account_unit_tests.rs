@@ -0,0 +1,432 @@
+#[macro_use]
+mod common;
+
+use common::{app_services, lazy_pool, test_config};
+use uuid::Uuid;
+
+#[cfg(test)]
+mod connected_apps_tests {
+    use actix_web::{http::StatusCode, test};
+    use auth_service::account_handlers::configure_account_routes;
+
+    #[actix_web::test]
+    async fn test_page_redirects_to_login_without_session() {
+        let app = test_app!(configure_account_routes);
+
+        let response = test::call_service(&app, test::TestRequest::get().uri("/account/apps").to_request()).await;
+
+        assert_eq!(response.status(), StatusCode::FOUND);
+        let location = response.headers().get("Location").unwrap().to_str().unwrap();
+        assert!(location.starts_with("/auth/login"));
+    }
+
+    #[actix_web::test]
+    async fn test_api_requires_session() {
+        let app = test_app!(configure_account_routes);
+
+        let list = test::call_service(&app, test::TestRequest::get().uri("/account/api/apps").to_request()).await;
+        assert_eq!(list.status(), StatusCode::UNAUTHORIZED);
+
+        let revoke = test::call_service(
+            &app,
+            test::TestRequest::delete().uri("/account/api/apps/client_abc").to_request(),
+        )
+        .await;
+        assert_eq!(revoke.status(), StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[cfg(test)]
+mod email_verification_tests {
+    use super::*;
+    use auth_service::config::AppConfig;
+    use auth_service::email_verification::{sign_verification_token, verify_verification_token};
+    use auth_service::mail::{EmailMessage, FileMailSender, InMemoryMailSender, MailError, MailSender};
+
+    const SECRET: &[u8] = b"verification-secret";
+
+    fn message(to: &str) -> EmailMessage {
+        EmailMessage {
+            to: to.to_string(),
+            subject: "Подтверждение email".to_string(),
+            body: "https://auth.example.com/auth/verify-email?token=abc".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_signed_token_roundtrip() {
+        let token = sign_verification_token(SECRET, "nonce123");
+
+        assert!(token.starts_with("nonce123."));
+        assert_eq!(verify_verification_token(SECRET, &token), Some("nonce123"));
+    }
+
+    #[test]
+    fn test_tampered_token_is_rejected() {
+        let token = sign_verification_token(SECRET, "nonce123");
+        let (_, signature) = token.split_once('.').unwrap();
+
+        assert_eq!(verify_verification_token(SECRET, &format!("nonce124.{}", signature)), None);
+        assert_eq!(verify_verification_token(b"other-secret", &token), None);
+        assert_eq!(verify_verification_token(SECRET, "nonce123"), None);
+    }
+
+    #[test]
+    fn test_verification_is_optional_by_default() {
+        let config = test_config();
+
+        assert!(!config.email_verification.required);
+        assert_eq!(config.email_verification.link_ttl, 86400);
+    }
+
+    #[test]
+    fn test_unknown_mail_transport_is_rejected() {
+        // Переменную читает только этот тест
+        std::env::set_var("MAIL_TRANSPORT", "smpt");
+        let result = std::panic::catch_unwind(|| AppConfig::from_env("127.0.0.1", "8080"));
+        std::env::remove_var("MAIL_TRANSPORT");
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_outbox_records_messages() {
+        let outbox = InMemoryMailSender::new();
+
+        outbox.send(message("alice@example.com")).await.unwrap();
+        outbox.send(message("bob@example.com")).await.unwrap();
+
+        assert_eq!(outbox.messages().len(), 2);
+        assert_eq!(outbox.last_message_to("bob@example.com"), Some(message("bob@example.com")));
+        assert!(matches!(
+            outbox.send(message("not an address")).await,
+            Err(MailError::InvalidAddress(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_file_outbox_writes_eml() {
+        let dir = std::env::temp_dir().join(format!("mail_outbox_{}", Uuid::new_v4()));
+        let sender = FileMailSender::new(dir.clone(), "no-reply@example.com").unwrap();
+
+        sender.send(message("alice@example.com")).await.unwrap();
+
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(files.len(), 1);
+        let content = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(content.contains("To: alice@example.com"));
+        assert!(content.contains("From: no-reply@example.com"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod profile_validation_tests {
+    use auth_service::models::{validate_avatar_url, validate_locale, validate_timezone, UpdateProfileRequest};
+    use validator::Validate;
+
+    #[test]
+    fn test_profile_field_validators() {
+        assert!(validate_locale("ru").is_ok());
+        assert!(validate_locale("zh-Hant-TW").is_ok());
+        assert!(validate_locale("").is_ok());
+        assert!(validate_locale("r").is_err());
+        assert!(validate_locale("en_US").is_err());
+
+        assert!(validate_timezone("Europe/Moscow").is_ok());
+        assert!(validate_timezone("America/Argentina/Buenos_Aires").is_ok());
+        assert!(validate_timezone("UTC").is_ok());
+        assert!(validate_timezone("Moscow").is_err());
+        assert!(validate_timezone("Europe/../etc").is_err());
+
+        assert!(validate_avatar_url("https://cdn.example.com/a.png").is_ok());
+        assert!(validate_avatar_url("").is_ok());
+        assert!(validate_avatar_url("http://cdn.example.com/a.png").is_err());
+        assert!(validate_avatar_url("javascript:alert(1)").is_err());
+    }
+
+    #[test]
+    fn test_update_request_validation() {
+        let request = UpdateProfileRequest { username: Some("jo".to_string()), ..Default::default() };
+        assert!(request.validate().is_err());
+
+        let request = UpdateProfileRequest { display_name: Some(String::new()), ..Default::default() };
+        assert!(request.validate().is_ok());
+        assert!(!request.is_empty());
+        assert!(UpdateProfileRequest::default().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod profile_tests {
+    use super::*;
+    use actix_web::dev::Service;
+    use actix_web::{http::StatusCode, test, web, App, HttpMessage};
+    use auth_service::models::TokenClaims;
+    use auth_service::protected_handlers::configure_protected_routes;
+
+    fn claims(sub: &str, scope: &str) -> TokenClaims {
+        TokenClaims {
+            iss: "https://auth.example.com".to_string(),
+            sub: sub.to_string(),
+            aud: "https://auth.example.com".to_string(),
+            client_id: "client_test".to_string(),
+            scope: scope.to_string(),
+            jti: "jti".to_string(),
+            exp: i64::MAX,
+            iat: 0,
+            auth_time: None,
+            acr: None,
+            amr: None,
+        }
+    }
+
+    // Вместо AuthMiddleware claims кладутся в extensions напрямую
+    macro_rules! protected_app {
+        ($claims:expr) => {{
+            let claims = $claims;
+            test::init_service(
+                App::new()
+                    .configure(app_services(lazy_pool(), test_config()))
+                    .service(
+                        web::scope("/api/protected")
+                            .wrap_fn(move |req, srv| {
+                                req.extensions_mut().insert(claims.clone());
+                                srv.call(req)
+                            })
+                            .configure(configure_protected_routes),
+                    ),
+            )
+            .await
+        }};
+    }
+
+    #[actix_web::test]
+    async fn test_patch_requires_write_profile_scope() {
+        let app = protected_app!(claims(&Uuid::new_v4().to_string(), "read:profile"));
+
+        let req = test::TestRequest::patch()
+            .uri("/api/protected/profile")
+            .set_json(serde_json::json!({ "display_name": "John" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "Missing required scope: write:profile");
+    }
+
+    #[actix_web::test]
+    async fn test_get_requires_read_profile_scope() {
+        let app = protected_app!(claims(&Uuid::new_v4().to_string(), "write:profile"));
+
+        let req = test::TestRequest::get().uri("/api/protected/profile").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn test_patch_validates_fields() {
+        let app = protected_app!(claims(&Uuid::new_v4().to_string(), "write:profile"));
+
+        let req = test::TestRequest::patch()
+            .uri("/api/protected/profile")
+            .set_json(serde_json::json!({ "timezone": "Not a zone" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::patch()
+            .uri("/api/protected/profile")
+            .set_json(serde_json::json!({}))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_client_credentials_token_has_no_profile() {
+        let app = protected_app!(claims("client_test", "read:profile write:profile"));
+
+        let req = test::TestRequest::get().uri("/api/protected/profile").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "Token is not issued to a user");
+    }
+}
+
+#[cfg(test)]
+mod email_change_tests {
+    use auth_service::config::AppConfig;
+    use auth_service::email_change::{mask_email, EmailChangeError};
+    use auth_service::models::ChangeEmailRequest;
+    use validator::Validate;
+
+    #[test]
+    fn test_mask_email() {
+        assert_eq!(mask_email("john@example.com"), "j***@example.com");
+        assert_eq!(mask_email("жора@example.com"), "ж***@example.com");
+        assert_eq!(mask_email("@example.com"), "***@example.com");
+        assert_eq!(mask_email("invalid"), "***");
+    }
+
+    #[test]
+    fn test_change_email_request_validation() {
+        let request = ChangeEmailRequest {
+            new_email: "new@example.com".to_string(),
+            current_password: "password".to_string(),
+        };
+        assert!(request.validate().is_ok());
+
+        let request = ChangeEmailRequest {
+            new_email: "not-an-email".to_string(),
+            current_password: "password".to_string(),
+        };
+        assert!(request.validate().is_err());
+
+        let request = ChangeEmailRequest {
+            new_email: "new@example.com".to_string(),
+            current_password: String::new(),
+        };
+        assert!(request.validate().is_err());
+    }
+
+    #[test]
+    fn test_email_change_defaults() {
+        let config = AppConfig::default();
+        assert_eq!(config.email_change.link_ttl, 86400);
+        assert_eq!(config.email_change.cancel_ttl, 604800);
+        assert_eq!(EmailChangeError::EmailExists.to_string(), "Email already exists");
+    }
+}
+
+#[cfg(test)]
+mod email_change_link_tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, web, App};
+    use auth_service::auth_handlers::configure_auth_routes;
+    use auth_service::email_change::EmailChangeService;
+    use auth_service::mail::InMemoryMailSender;
+    use common::{create_user, session_middleware, test_database, user_service};
+    use std::sync::Arc;
+
+    // Токен из ссылки в письме
+    fn link_token(body: &str) -> String {
+        let start = body.find("token=").unwrap() + "token=".len();
+        let encoded = body[start..].split_whitespace().next().unwrap();
+        urlencoding::decode(encoded).unwrap().into_owned()
+    }
+
+    #[actix_web::test]
+    async fn test_link_page_does_not_change_state() {
+        let app = test_app!(configure_auth_routes);
+
+        for path in ["/auth/email/change/confirm", "/auth/email/change/cancel"] {
+            let response = test::call_service(
+                &app,
+                test::TestRequest::get().uri(&format!("{}?token=a%22b", path)).to_request(),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+            assert!(body.contains(&format!(r#"<form method="POST" action="{}">"#, path)));
+            assert!(body.contains(r#"name="token" value="a&quot;b""#));
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_confirm_and_cancel_by_post() {
+        let Some(pool) = test_database().await else { return };
+        let config = test_config();
+        let mailer = Arc::new(InMemoryMailSender::new());
+        let email_change = EmailChangeService::new(pool.clone(), mailer.clone(), &config);
+        let user = create_user(&pool).await;
+        let new_email = format!("new_{}@example.com", Uuid::new_v4().simple());
+        email_change.request_change(&user, &new_email).await.unwrap();
+
+        let confirm_token = link_token(&mailer.last_message_to(&new_email).unwrap().body);
+        let cancel_token = link_token(&mailer.last_message_to(&user.email).unwrap().body);
+
+        let app = test::init_service(
+            App::new()
+                .configure(app_services(pool.clone(), config))
+                .app_data(web::Data::new(email_change))
+                .wrap(session_middleware())
+                .configure(configure_auth_routes),
+        )
+        .await;
+        let post = |path: &str, token: &str| {
+            test::TestRequest::post()
+                .uri(path)
+                .set_form([("token", token)])
+                .to_request()
+        };
+        let users = user_service(pool.clone());
+
+        // Открытие ссылки ничего не меняет
+        let response = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri(&format!("/auth/email/change/confirm?token={}", urlencoding::encode(&confirm_token)))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(users.get_user_by_id(user.id).await.unwrap().unwrap().email, user.email);
+
+        let response = test::call_service(&app, post("/auth/email/change/confirm", &confirm_token)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(users.get_user_by_id(user.id).await.unwrap().unwrap().email, new_email);
+
+        let response = test::call_service(&app, post("/auth/email/change/confirm", &confirm_token)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = test::call_service(&app, post("/auth/email/change/cancel", &cancel_token)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(users.get_user_by_id(user.id).await.unwrap().unwrap().email, user.email);
+    }
+}
+
+#[cfg(test)]
+mod session_tracking_tests {
+    use super::*;
+    use auth_service::session_service::{SessionService, SESSION_TOUCH_INTERVAL_SECS};
+    use chrono::{DateTime, Duration, Utc};
+    use common::{create_user, test_database};
+    use sqlx::{Pool, Postgres};
+
+    async fn last_seen_at(pool: &Pool<Postgres>, session_id: Uuid) -> DateTime<Utc> {
+        sqlx::query_scalar("SELECT last_seen_at FROM user_sessions WHERE id = $1")
+            .bind(session_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_touch_writes_last_seen_only_when_stale() {
+        let Some(pool) = test_database().await else { return };
+        let user = create_user(&pool).await;
+        let sessions = SessionService::new(pool.clone());
+        let session_id = sessions.create_session(user.id, None, None).await.unwrap();
+        let created = last_seen_at(&pool, session_id).await;
+
+        // Недавняя активность не перезаписывается
+        assert!(sessions.touch_session(session_id, user.id).await.unwrap());
+        assert_eq!(last_seen_at(&pool, session_id).await, created);
+
+        let stale = Utc::now() - Duration::seconds(SESSION_TOUCH_INTERVAL_SECS + 60);
+        sqlx::query("UPDATE user_sessions SET last_seen_at = $1 WHERE id = $2")
+            .bind(stale)
+            .bind(session_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(sessions.touch_session(session_id, user.id).await.unwrap());
+        assert!(last_seen_at(&pool, session_id).await > stale + Duration::seconds(60));
+
+        // Отзыв проверяется на каждом запросе
+        sessions.revoke_session(session_id).await.unwrap();
+        assert!(!sessions.touch_session(session_id, user.id).await.unwrap());
+        assert!(!sessions.touch_session(Uuid::new_v4(), user.id).await.unwrap());
+    }
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use common::{app_services, lazy_pool, test_config};
+
+#[cfg(test)]
+mod admin_user_tests {
+    use auth_service::models::{AdminUserQuery, UserStatus};
+    use auth_service::oauth_service::OAuthError;
+    use auth_service::services::like_pattern;
+    use validator::Validate;
+
+    #[test]
+    fn test_like_pattern_escapes_wildcards() {
+        assert_eq!(like_pattern("john"), "%john%");
+        assert_eq!(like_pattern("50%_off"), "%50\\%\\_off%");
+        assert_eq!(like_pattern("a\\b"), "%a\\\\b%");
+    }
+
+    #[test]
+    fn test_user_status() {
+        assert_eq!(UserStatus::from_db("suspended"), UserStatus::Suspended);
+        assert_eq!(UserStatus::from_db("active"), UserStatus::Active);
+        assert_eq!(UserStatus::from_db("unknown"), UserStatus::Suspended);
+        assert_eq!(UserStatus::from_db(""), UserStatus::Suspended);
+        assert_eq!(UserStatus::Suspended.as_str(), "suspended");
+        assert_eq!(serde_json::to_value(UserStatus::Suspended).unwrap(), "suspended");
+        assert_eq!(serde_json::from_str::<UserStatus>("\"active\"").unwrap(), UserStatus::Active);
+    }
+
+    #[test]
+    fn test_query_pagination() {
+        let query = AdminUserQuery::default();
+        assert_eq!((query.page(), query.per_page(), query.offset()), (1, AdminUserQuery::DEFAULT_PER_PAGE, 0));
+
+        let query = AdminUserQuery { page: Some(3), per_page: Some(50), ..Default::default() };
+        assert!(query.validate().is_ok());
+        assert_eq!(query.offset(), 100);
+
+        let query = AdminUserQuery { per_page: Some(500), ..Default::default() };
+        assert!(query.validate().is_err());
+        let query = AdminUserQuery { page: Some(0), ..Default::default() };
+        assert!(query.validate().is_err());
+    }
+
+    #[test]
+    fn test_suspended_grant_error() {
+        assert_eq!(OAuthError::UserSuspended.to_string(), "invalid_grant");
+        assert_eq!(OAuthError::UserSuspended.description(), Some("User account is suspended"));
+    }
+}
+
+#[cfg(test)]
+mod admin_users_route_tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, web, App};
+    use auth_service::admin_handlers::configure_admin_routes;
+
+    #[actix_web::test]
+    async fn test_list_users_rejects_invalid_filters() {
+        let app = test::init_service(
+            App::new()
+                .configure(app_services(lazy_pool(), test_config()))
+                .service(web::scope("/api/admin").configure(configure_admin_routes)),
+        )
+        .await;
+
+        for uri in [
+            "/api/admin/users?per_page=500",
+            "/api/admin/users?page=0",
+            "/api/admin/users?status=deleted",
+        ] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST, "{}", uri);
+        }
+    }
+}
+
+#[cfg(test)]
+mod admin_token_revocation_tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, web, App};
+    use auth_service::admin_handlers::configure_admin_routes;
+    use auth_service::token_service::TokenService;
+    use common::{create_user, test_database, TEST_SECRET};
+
+    #[actix_web::test]
+    async fn test_revoke_token_by_jti() {
+        let Some(pool) = test_database().await else { return };
+        let user = create_user(&pool).await;
+        let tokens = TokenService::new(pool.clone(), TEST_SECRET.to_string(), &test_config());
+        let claims = tokens.build_claims(Some(user.id), "client_test", "openid", None, 300);
+        let jwt = tokens.create_jwt(&claims).unwrap();
+        tokens.store_tokens(&jwt, None, Some(user.id), &claims, "openid").await.unwrap();
+        assert!(tokens.resolve_access_token(&jwt).await.unwrap().is_some());
+
+        let app = test::init_service(
+            App::new()
+                .configure(app_services(pool.clone(), test_config()))
+                .service(web::scope("/api/admin").configure(configure_admin_routes)),
+        )
+        .await;
+        let revoke = |jti: &str| test::TestRequest::post().uri(&format!("/api/admin/tokens/{}/revoke", jti)).to_request();
+
+        let response = test::call_service(&app, revoke(&claims.jti)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["jti"], claims.jti);
+        assert!(tokens.resolve_access_token(&jwt).await.unwrap().is_none());
+
+        let response = test::call_service(&app, revoke("unknown-jti")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
+
+#[cfg(test)]
+mod user_status_constraint_tests {
+    use super::*;
+    use common::{create_user, test_database};
+
+    #[actix_web::test]
+    async fn test_unknown_status_is_rejected_by_database() {
+        let Some(pool) = test_database().await else { return };
+        let user = create_user(&pool).await;
+
+        let result = sqlx::query("UPDATE users SET status = 'deleted' WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await;
+        assert!(result.is_err());
+
+        sqlx::query("UPDATE users SET status = 'suspended' WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,184 @@
+// Общие фикстуры интеграционных тестов. Каждый тестовый файл использует только часть из них
+#![allow(dead_code, unused_macros)]
+
+use std::sync::Arc;
+use actix_session::{storage::CookieSessionStore, SessionMiddleware};
+use actix_web::cookie::Key;
+use actix_web::web;
+use auth_service::account_deletion::AccountDeletionService;
+use auth_service::client_service::{ClientSecretKeys, ClientService};
+use auth_service::config::{AppConfig, HashingPoolConfig, WebAuthnConfig};
+use auth_service::consent_service::ConsentService;
+use auth_service::data_export::DataExportService;
+use auth_service::email_change::EmailChangeService;
+use auth_service::email_verification::EmailVerificationService;
+use auth_service::hashing_pool::HashingPool;
+use auth_service::login_throttle::LoginThrottle;
+use auth_service::mail::{InMemoryMailSender, MailSender};
+use auth_service::mfa_service::MfaService;
+use auth_service::models::{OAuthClient, RegisterUserRequest, User};
+use auth_service::oauth_service::OAuthService;
+use auth_service::passkey_service::PasskeyService;
+use auth_service::pending_login::PendingLoginService;
+use auth_service::password_hasher::PasswordHasher;
+use auth_service::password_policy::PasswordPolicy;
+use auth_service::password_reset::PasswordResetService;
+use auth_service::scope_service::ScopeService;
+use auth_service::secrets::SecretCipher;
+use auth_service::services::UserService;
+use auth_service::session_service::SessionService;
+use auth_service::token_service::TokenService;
+use chrono::Utc;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::{Pool, Postgres};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub const TEST_SECRET: &str = "test-secret";
+pub const TEST_PASSWORD: &str = "correct horse battery";
+
+pub fn test_config() -> AppConfig {
+    AppConfig {
+        issuer: "https://auth.example.com".to_string(),
+        access_token_audience: "https://auth.example.com".to_string(),
+        webauthn: WebAuthnConfig::for_issuer("https://auth.example.com", "AuthService"),
+        ..AppConfig::default()
+    }
+}
+
+pub fn client(is_confidential: bool) -> OAuthClient {
+    OAuthClient {
+        id: Uuid::new_v4(),
+        client_id: "client_test".to_string(),
+        client_secret_hash: String::new(),
+        client_name: "Test".to_string(),
+        redirect_uris: vec!["https://app.example.com/cb".to_string()],
+        allowed_scopes: vec![],
+        grant_types: vec!["authorization_code".to_string()],
+        is_confidential,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        access_token_format: "jwt".to_string(),
+        require_pkce: None,
+        allow_plain_pkce: None,
+        access_token_ttl: None,
+        refresh_token_ttl: None,
+        refresh_token_absolute_ttl: None,
+        authorization_code_ttl: None,
+        is_first_party: false,
+    }
+}
+
+pub fn totp_cipher() -> SecretCipher {
+    SecretCipher::new(&[7; 32])
+}
+
+// Ленивый пул: подключение открывается только при первом запросе, поэтому тесты,
+// в которых запрос отклоняется до обращения к БД, работают без базы
+pub fn lazy_pool() -> Pool<Postgres> {
+    PgPoolOptions::new()
+        .connect_lazy("postgres://localhost/unused")
+        .unwrap()
+}
+
+// Пул тестовой базы из TEST_DATABASE_URL с примененными миграциями.
+// Без переменной тесты с БД пропускаются
+pub async fn test_database() -> Option<Pool<Postgres>> {
+    static MIGRATED: Mutex<bool> = Mutex::const_new(false);
+
+    let url = std::env::var("TEST_DATABASE_URL").ok()?;
+    let pool = PgPoolOptions::new()
+        .max_connections(5)
+        .connect(&url)
+        .await
+        .expect("Не удалось подключиться к TEST_DATABASE_URL");
+
+    // Параллельные CREATE ... IF NOT EXISTS конфликтуют, миграции применяются один раз
+    let mut migrated = MIGRATED.lock().await;
+    if !*migrated {
+        auth_service::database::run_migrations(&pool).await.expect("Не удалось применить миграции");
+        *migrated = true;
+    }
+
+    Some(pool)
+}
+
+pub fn user_service(pool: Pool<Postgres>) -> UserService {
+    UserService::new(pool, PasswordHasher::default(), Arc::new(HashingPool::new(&HashingPoolConfig::default())))
+}
+
+// Пользователь с уникальными username и email и паролем TEST_PASSWORD
+pub async fn create_user(pool: &Pool<Postgres>) -> User {
+    let suffix = Uuid::new_v4().simple().to_string();
+    user_service(pool.clone())
+        .register_user(RegisterUserRequest {
+            username: format!("user_{}", &suffix[..12]),
+            email: format!("user_{}@example.com", suffix),
+            password: TEST_PASSWORD.to_string(),
+        })
+        .await
+        .unwrap()
+}
+
+pub fn session_middleware() -> SessionMiddleware<CookieSessionStore> {
+    SessionMiddleware::new(CookieSessionStore::default(), Key::generate())
+}
+
+// Регистрация сервисов приложения так же, как в main.rs; письма складываются в память
+pub fn app_services(pool: Pool<Postgres>, config: AppConfig) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |cfg| {
+        let hashing_pool = Arc::new(HashingPool::new(&config.hashing_pool));
+        let mailer: Arc<dyn MailSender> = Arc::new(InMemoryMailSender::new());
+
+        cfg.app_data(web::Data::new(UserService::new(pool.clone(), PasswordHasher::default(), hashing_pool.clone())))
+            .app_data(web::Data::new(TokenService::new(pool.clone(), TEST_SECRET.to_string(), &config)))
+            .app_data(web::Data::new(ClientService::new(
+                pool.clone(),
+                config.redirect_uri_policy,
+                hashing_pool.clone(),
+                ClientSecretKeys::new(TEST_SECRET),
+                config.client_auth_cache,
+            )))
+            .app_data(web::Data::new(ConsentService::new(pool.clone())))
+            .app_data(web::Data::new(ScopeService::new(pool.clone())))
+            .app_data(web::Data::new(OAuthService::new(
+                pool.clone(),
+                TokenService::new(pool.clone(), TEST_SECRET.to_string(), &config),
+                config.pkce_policy,
+                config.email_verification.required,
+            )))
+            .app_data(web::Data::new(EmailVerificationService::new(pool.clone(), mailer.clone(), TEST_SECRET, &config)))
+            .app_data(web::Data::new(PasswordResetService::new(pool.clone(), mailer.clone(), &config)))
+            .app_data(web::Data::new(EmailChangeService::new(pool.clone(), mailer.clone(), &config)))
+            .app_data(web::Data::new(AccountDeletionService::new(pool.clone(), mailer, &config)))
+            .app_data(web::Data::new(DataExportService::new(pool.clone())))
+            .app_data(web::Data::new(SessionService::new(pool.clone())))
+            .app_data(web::Data::new(MfaService::new(pool.clone(), &config.totp_issuer, totp_cipher())))
+            .app_data(web::Data::new(PasskeyService::new(pool.clone(), &config.webauthn)))
+            .app_data(web::Data::new(PendingLoginService::new(pool.clone())))
+            .app_data(web::Data::new(LoginThrottle::new(pool, config.login_throttle)))
+            .app_data(web::Data::new(PasswordPolicy::new(&config.password_policy)))
+            .app_data(web::Data::from(hashing_pool))
+            .app_data(web::Data::new(config));
+    }
+}
+
+// Тестовое приложение: сервисы из app_services, cookie-сессия и маршруты из $configure.
+// Без явного пула используется ленивый, без явной конфигурации — test_config()
+macro_rules! test_app {
+    ($configure:expr) => {
+        test_app!($crate::common::lazy_pool(), $configure)
+    };
+    ($pool:expr, $configure:expr) => {
+        test_app!($pool, $crate::common::test_config(), $configure)
+    };
+    ($pool:expr, $config:expr, $configure:expr) => {
+        actix_web::test::init_service(
+            actix_web::App::new()
+                .configure($crate::common::app_services($pool, $config))
+                .wrap($crate::common::session_middleware())
+                .configure($configure),
+        )
+        .await
+    };
+}
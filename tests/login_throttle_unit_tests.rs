@@ -0,0 +1,294 @@
+#[macro_use]
+mod common;
+
+#[cfg(test)]
+mod login_throttle_tests {
+    use auth_service::config::LoginThrottlePolicy;
+    use auth_service::login_throttle::account_key;
+    use std::time::Duration;
+
+    #[test]
+    fn test_failure_delay_grows_and_is_capped() {
+        let policy = LoginThrottlePolicy { base_delay_ms: 250, max_delay_ms: 2000, ..LoginThrottlePolicy::default() };
+
+        assert_eq!(policy.failure_delay(0), Duration::ZERO);
+        assert_eq!(policy.failure_delay(1), Duration::from_millis(250));
+        assert_eq!(policy.failure_delay(2), Duration::from_millis(500));
+        assert_eq!(policy.failure_delay(4), Duration::from_millis(2000));
+        assert_eq!(policy.failure_delay(100), Duration::from_millis(2000));
+    }
+
+    #[test]
+    fn test_account_key_ignores_case_and_whitespace() {
+        assert_eq!(account_key(" User@Example.COM "), "user@example.com");
+    }
+}
+
+#[cfg(test)]
+mod client_ip_tests {
+    use actix_web::test::TestRequest;
+    use actix_web::web;
+    use auth_service::config::AppConfig;
+    use auth_service::login_throttle::client_ip;
+    use crate::common::test_config;
+
+    fn config_with_proxies(proxies: &[&str]) -> web::Data<AppConfig> {
+        web::Data::new(AppConfig {
+            trusted_proxies: proxies.iter().map(|proxy| proxy.parse().unwrap()).collect(),
+            ..test_config()
+        })
+    }
+
+    #[test]
+    fn test_forwarded_headers_ignored_without_trusted_proxy() {
+        let req = TestRequest::default()
+            .peer_addr("203.0.113.7:50000".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "198.51.100.1"))
+            .insert_header(("Forwarded", "for=198.51.100.2"))
+            .app_data(config_with_proxies(&[]))
+            .to_http_request();
+
+        assert_eq!(client_ip(&req).as_deref(), Some("203.0.113.7"));
+    }
+
+    #[test]
+    fn test_forwarded_for_honored_behind_trusted_proxy() {
+        // Клиент подставил свой адрес в начало цепочки; учитывается адрес, добавленный прокси
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.2:443".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "198.51.100.1, 203.0.113.7, 10.0.0.1"))
+            .app_data(config_with_proxies(&["10.0.0.1", "10.0.0.2"]))
+            .to_http_request();
+
+        assert_eq!(client_ip(&req).as_deref(), Some("203.0.113.7"));
+    }
+
+    #[test]
+    fn test_untrusted_peer_is_used_as_is() {
+        let req = TestRequest::default()
+            .peer_addr("203.0.113.7:50000".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "198.51.100.1"))
+            .app_data(config_with_proxies(&["10.0.0.1"]))
+            .to_http_request();
+
+        assert_eq!(client_ip(&req).as_deref(), Some("203.0.113.7"));
+    }
+}
+
+#[cfg(test)]
+mod lockout_tests {
+    use actix_web::{http::StatusCode, test};
+    use auth_service::auth_handlers::configure_auth_routes;
+    use auth_service::config::{AppConfig, LoginThrottlePolicy};
+    use auth_service::login_throttle::LoginThrottle;
+    use crate::common::{create_user, test_config, test_database, TEST_PASSWORD};
+    use uuid::Uuid;
+
+    // Политика без задержек ответа, чтобы тесты не ждали
+    fn policy() -> LoginThrottlePolicy {
+        LoginThrottlePolicy { base_delay_ms: 0, max_delay_ms: 0, ..LoginThrottlePolicy::default() }
+    }
+
+    fn unique_email() -> String {
+        format!("throttle_{}@example.com", Uuid::new_v4().simple())
+    }
+
+    #[actix_web::test]
+    async fn test_account_locked_after_max_failures() {
+        let Some(pool) = test_database().await else { return };
+        let throttle = LoginThrottle::new(pool, policy());
+        let email = unique_email();
+
+        for _ in 1..throttle.policy().max_account_failures {
+            throttle.record_failure(&email, None).await.unwrap();
+        }
+        assert!(!throttle.is_locked(&email, None).await.unwrap());
+        assert!(throttle.account_locked_until(&email).await.unwrap().is_none());
+
+        throttle.record_failure(&email, None).await.unwrap();
+        assert!(throttle.is_locked(&email, None).await.unwrap());
+        // Блокировка по ключу аккаунта, а не по написанию email
+        assert!(throttle.is_locked(&email.to_uppercase(), None).await.unwrap());
+        assert!(throttle.account_locked_until(&email).await.unwrap().is_some());
+    }
+
+    #[actix_web::test]
+    async fn test_ip_locked_after_max_failures() {
+        let Some(pool) = test_database().await else { return };
+        let throttle = LoginThrottle::new(pool, LoginThrottlePolicy { max_ip_failures: 3, ..policy() });
+        // Уникальный ключ IP, чтобы не пересекаться с другими тестами
+        let ip = format!("test-ip-{}", Uuid::new_v4().simple());
+
+        for _ in 0..3 {
+            throttle.record_failure(&unique_email(), Some(&ip)).await.unwrap();
+        }
+        assert!(throttle.is_locked(&unique_email(), Some(&ip)).await.unwrap());
+        assert!(!throttle.is_locked(&unique_email(), None).await.unwrap());
+    }
+
+    #[actix_web::test]
+    async fn test_unlock_account_clears_lockout() {
+        let Some(pool) = test_database().await else { return };
+        let throttle = LoginThrottle::new(pool, policy());
+        let email = unique_email();
+
+        assert!(!throttle.unlock_account(&email).await.unwrap());
+
+        for _ in 0..throttle.policy().max_account_failures {
+            throttle.record_failure(&email, None).await.unwrap();
+        }
+        assert!(throttle.is_locked(&email, None).await.unwrap());
+
+        assert!(throttle.unlock_account(&email).await.unwrap());
+        assert!(!throttle.is_locked(&email, None).await.unwrap());
+        assert!(throttle.account_locked_until(&email).await.unwrap().is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_success_resets_failure_counter() {
+        let Some(pool) = test_database().await else { return };
+        let throttle = LoginThrottle::new(pool, policy());
+        let email = unique_email();
+        let max_failures = throttle.policy().max_account_failures;
+
+        for _ in 1..max_failures {
+            throttle.record_failure(&email, None).await.unwrap();
+        }
+        throttle.record_success(&email).await.unwrap();
+
+        // После сброса счет начинается заново: до блокировки снова max_failures неудач
+        for _ in 1..max_failures {
+            throttle.record_failure(&email, None).await.unwrap();
+        }
+        assert!(!throttle.is_locked(&email, None).await.unwrap());
+    }
+
+    #[actix_web::test]
+    async fn test_locked_account_rejects_correct_password() {
+        let Some(pool) = test_database().await else { return };
+        let config = AppConfig { login_throttle: policy(), ..test_config() };
+        let user = create_user(&pool).await;
+        let app = test_app!(pool.clone(), config.clone(), configure_auth_routes);
+
+        let login = |password: &str| {
+            test::TestRequest::post()
+                .uri("/auth/login")
+                .set_json(serde_json::json!({ "email": user.email, "password": password }))
+                .to_request()
+        };
+
+        for _ in 0..config.login_throttle.max_account_failures {
+            let response = test::call_service(&app, login("wrong password")).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        // Ответ при блокировке не отличается от неверного пароля
+        let response = test::call_service(&app, login(TEST_PASSWORD)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["error"], "Invalid credentials");
+
+        LoginThrottle::new(pool, config.login_throttle).unlock_account(&user.email).await.unwrap();
+        let response = test::call_service(&app, login(TEST_PASSWORD)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
+
+#[cfg(test)]
+mod admin_unlock_tests {
+    use actix_web::{http::StatusCode, test, web, App};
+    use auth_service::admin_handlers::configure_admin_routes;
+    use auth_service::config::LoginThrottlePolicy;
+    use auth_service::login_throttle::LoginThrottle;
+    use auth_service::middleware::{AdminGuard, AuthMiddleware, ScopeValidator};
+    use auth_service::token_service::TokenService;
+    use crate::common::{app_services, create_user, test_config, test_database, TEST_SECRET};
+    use sqlx::{Pool, Postgres};
+    use uuid::Uuid;
+
+    // Access token, сохраненный в БД так же, как при выдаче через /oauth/token
+    async fn access_token(pool: &Pool<Postgres>, user_id: Option<Uuid>, scope: &str) -> String {
+        let tokens = TokenService::new(pool.clone(), TEST_SECRET.to_string(), &test_config());
+        let claims = tokens.build_claims(user_id, "client_test", scope, None, 300);
+        let jwt = tokens.create_jwt(&claims).unwrap();
+        tokens.store_tokens(&jwt, None, user_id, &claims, scope).await.unwrap();
+        jwt
+    }
+
+    #[actix_web::test]
+    async fn test_unlock_requires_admin_scope_and_role() {
+        let Some(pool) = test_database().await else { return };
+        let config = test_config();
+        let admin = create_user(&pool).await;
+        let user = create_user(&pool).await;
+        sqlx::query("UPDATE users SET is_admin = true WHERE id = $1")
+            .bind(admin.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let throttle = LoginThrottle::new(pool.clone(), LoginThrottlePolicy::default());
+        for _ in 0..throttle.policy().max_account_failures {
+            throttle.record_failure(&user.email, None).await.unwrap();
+        }
+
+        // Защита /api/admin как в main.rs
+        let app = test::init_service(
+            App::new()
+                .configure(app_services(pool.clone(), config.clone()))
+                .service(
+                    web::scope("/api/admin")
+                        .wrap(AdminGuard)
+                        .wrap(ScopeValidator::new(vec!["admin".to_string()]))
+                        .wrap(AuthMiddleware::new(TokenService::new(pool.clone(), TEST_SECRET.to_string(), &config)))
+                        .configure(configure_admin_routes),
+                ),
+        )
+        .await;
+        let uri = format!("/api/admin/users/{}/unlock", user.id);
+        let unlock = |token: Option<&str>| {
+            let request = test::TestRequest::post().uri(&uri);
+            match token {
+                Some(token) => request.insert_header(("Authorization", format!("Bearer {}", token))),
+                None => request,
+            }
+            .to_request()
+        };
+
+        let response = test::call_service(&app, unlock(None)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = test::call_service(&app, unlock(Some("not-a-token"))).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let user_token = access_token(&pool, Some(admin.id), "openid profile").await;
+        let response = test::call_service(&app, unlock(Some(&user_token))).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(throttle.is_locked(&user.email, None).await.unwrap());
+
+        // Scope admin без роли администратора не дает доступа
+        let non_admin_token = access_token(&pool, Some(user.id), "openid admin").await;
+        let response = test::call_service(&app, unlock(Some(&non_admin_token))).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["error"], "Administrator role required");
+        assert!(throttle.is_locked(&user.email, None).await.unwrap());
+
+        let admin_token = access_token(&pool, Some(admin.id), "openid admin").await;
+        let response = test::call_service(&app, unlock(Some(&admin_token))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["user_id"], user.id.to_string());
+        assert_eq!(body["was_locked"], true);
+        assert!(!throttle.is_locked(&user.email, None).await.unwrap());
+
+        let response = test::call_service(&app, unlock(Some(&admin_token))).await;
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["was_locked"], false);
+
+        // Токен client_credentials проверяется только по scope
+        let client_token = access_token(&pool, None, "admin").await;
+        let response = test::call_service(&app, unlock(Some(&client_token))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
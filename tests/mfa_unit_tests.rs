@@ -0,0 +1,568 @@
+#[macro_use]
+mod common;
+
+use auth_service::config::{AppConfig, WebAuthnConfig};
+use common::test_config;
+
+#[cfg(test)]
+mod totp_tests {
+    use auth_service::mfa_service::{normalize_recovery_code, verify_totp_code};
+    use totp_rs::{Algorithm, Secret, TOTP};
+
+    const SECRET: &str = "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP";
+
+    fn code_at(time: u64) -> String {
+        let secret = Secret::Encoded(SECRET.to_string()).to_bytes().unwrap();
+        TOTP::new_unchecked(Algorithm::SHA1, 6, 0, 30, secret, None, String::new()).generate(time)
+    }
+
+    #[test]
+    fn test_totp_accepts_adjacent_steps() {
+        let now = 1_700_000_000;
+
+        assert_eq!(verify_totp_code(SECRET, &code_at(now), now, None), Some((now / 30) as i64));
+        assert!(verify_totp_code(SECRET, &code_at(now - 30), now, None).is_some());
+        assert!(verify_totp_code(SECRET, &code_at(now + 30), now, None).is_some());
+        assert!(verify_totp_code(SECRET, &code_at(now - 90), now, None).is_none());
+    }
+
+    #[test]
+    fn test_totp_rejects_replayed_code() {
+        let now = 1_700_000_000;
+        let step = verify_totp_code(SECRET, &code_at(now), now, None).unwrap();
+
+        assert!(verify_totp_code(SECRET, &code_at(now), now, Some(step)).is_none());
+        assert!(verify_totp_code(SECRET, &code_at(now + 30), now, Some(step)).is_some());
+    }
+
+    #[test]
+    fn test_totp_rejects_malformed_code() {
+        assert!(verify_totp_code(SECRET, "12345", 1_700_000_000, None).is_none());
+        assert!(verify_totp_code(SECRET, "abcdef", 1_700_000_000, None).is_none());
+    }
+
+    #[test]
+    fn test_recovery_code_normalization() {
+        assert_eq!(normalize_recovery_code(" AbCd1-eFgH2 "), "abcd1efgh2");
+        assert_eq!(normalize_recovery_code("abcd1 efgh2"), normalize_recovery_code("ABCD1-EFGH2"));
+    }
+}
+
+#[cfg(test)]
+mod mfa_login_tests {
+    use actix_web::{http::StatusCode, test};
+    use auth_service::auth_handlers::configure_auth_routes;
+
+    #[actix_web::test]
+    async fn test_second_step_requires_pending_login() {
+        let app = test_app!(configure_auth_routes);
+
+        let response = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/auth/login/mfa")
+                .set_json(serde_json::json!({ "code": "123456" }))
+                .to_request(),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_mfa_management_requires_session() {
+        let app = test_app!(configure_auth_routes);
+
+        let status = test::call_service(&app, test::TestRequest::get().uri("/auth/mfa/status").to_request()).await;
+        assert_eq!(status.status(), StatusCode::UNAUTHORIZED);
+
+        let enroll = test::call_service(&app, test::TestRequest::post().uri("/auth/mfa/totp/enroll").to_request()).await;
+        assert_eq!(enroll.status(), StatusCode::UNAUTHORIZED);
+
+        let setup = test::call_service(&app, test::TestRequest::get().uri("/auth/mfa/setup").to_request()).await;
+        assert_eq!(setup.status(), StatusCode::FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_passkey_management_requires_session() {
+        let app = test_app!(configure_auth_routes);
+
+        let list = test::call_service(&app, test::TestRequest::get().uri("/auth/passkeys").to_request()).await;
+        assert_eq!(list.status(), StatusCode::UNAUTHORIZED);
+
+        let options = test::call_service(
+            &app,
+            test::TestRequest::post().uri("/auth/passkeys/register/options").to_request(),
+        )
+        .await;
+        assert_eq!(options.status(), StatusCode::UNAUTHORIZED);
+
+        let setup = test::call_service(&app, test::TestRequest::get().uri("/auth/passkeys/setup").to_request()).await;
+        assert_eq!(setup.status(), StatusCode::FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_passkey_login_rejects_malformed_assertion() {
+        let app = test_app!(configure_auth_routes);
+
+        let response = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/auth/login/passkey")
+                .set_json(serde_json::json!({
+                    "id": "credential",
+                    "client_data_json": "not base64!",
+                    "authenticator_data": "AAAA",
+                    "signature": "AAAA"
+                }))
+                .to_request(),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_passkey_login_challenge_is_kept_in_session() {
+        // Ленивый пул без базы: параметры входа без пароля не обращаются к БД
+        let app = test_app!(configure_auth_routes);
+
+        let options = test::call_service(
+            &app,
+            test::TestRequest::post().uri("/auth/login/passkey/options").to_request(),
+        )
+        .await;
+        assert_eq!(options.status(), StatusCode::OK);
+        assert!(options.response().cookies().next().is_some());
+        let body: serde_json::Value = test::read_body_json(options).await;
+        assert!(body["challenge"].as_str().is_some_and(|c| !c.is_empty()));
+
+        // Без challenge в сессии ответ аутентификатора не проверяется
+        let response = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/auth/login/passkey")
+                .set_json(serde_json::json!({
+                    "id": "credential",
+                    "client_data_json": "AAAA",
+                    "authenticator_data": "AAAA",
+                    "signature": "AAAA"
+                }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["error"], "Invalid or expired challenge");
+    }
+}
+
+#[cfg(test)]
+mod passkey_tests {
+    use super::*;
+    use auth_service::passkey_service::{LoginChallenge, PasskeyError, PasskeyService};
+    use auth_service::webauthn::{
+        encode_b64url, verify_assertion, verify_registration, Assertion, WebAuthnError,
+    };
+    use ciborium::value::Value;
+    use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+    use rand::rngs::OsRng;
+    use sha2::{Digest, Sha256};
+
+    const CHALLENGE: &str = "dGVzdC1jaGFsbGVuZ2UtMzItYnl0ZXMtbG9uZy4uLi4";
+    const FLAGS_UP: u8 = 0x01;
+    const FLAGS_UP_UV: u8 = 0x05;
+
+    // Программный аутентификатор: ключ P-256 и счетчик подписей
+    struct SoftAuthenticator {
+        key: SigningKey,
+        credential_id: Vec<u8>,
+        sign_count: u32,
+    }
+
+    impl SoftAuthenticator {
+        fn new() -> Self {
+            Self { key: SigningKey::random(&mut OsRng), credential_id: vec![7; 16], sign_count: 0 }
+        }
+
+        fn client_data(ceremony: &str, challenge: &str, origin: &str) -> Vec<u8> {
+            serde_json::json!({ "type": ceremony, "challenge": challenge, "origin": origin })
+                .to_string()
+                .into_bytes()
+        }
+
+        fn auth_data(&self, rp_id: &str, flags: u8) -> Vec<u8> {
+            let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+            data.push(flags);
+            data.extend_from_slice(&self.sign_count.to_be_bytes());
+            data
+        }
+
+        fn cose_key(&self) -> Vec<u8> {
+            let point = self.key.verifying_key().to_encoded_point(false);
+            let key = Value::Map(vec![
+                (Value::from(1), Value::from(2)),
+                (Value::from(3), Value::from(-7)),
+                (Value::from(-1), Value::from(1)),
+                (Value::from(-2), Value::Bytes(point.x().unwrap().to_vec())),
+                (Value::from(-3), Value::Bytes(point.y().unwrap().to_vec())),
+            ]);
+            let mut encoded = Vec::new();
+            ciborium::ser::into_writer(&key, &mut encoded).unwrap();
+            encoded
+        }
+
+        // navigator.credentials.create: clientDataJSON и attestationObject (fmt "none")
+        fn create(&self, rp_id: &str, origin: &str, challenge: &str) -> (Vec<u8>, Vec<u8>) {
+            let mut auth_data = self.auth_data(rp_id, FLAGS_UP_UV | 0x40);
+            auth_data.extend_from_slice(&[0; 16]);
+            auth_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+            auth_data.extend_from_slice(&self.credential_id);
+            auth_data.extend_from_slice(&self.cose_key());
+
+            let attestation = Value::Map(vec![
+                (Value::from("fmt"), Value::from("none")),
+                (Value::from("attStmt"), Value::Map(vec![])),
+                (Value::from("authData"), Value::Bytes(auth_data)),
+            ]);
+            let mut encoded = Vec::new();
+            ciborium::ser::into_writer(&attestation, &mut encoded).unwrap();
+
+            (Self::client_data("webauthn.create", challenge, origin), encoded)
+        }
+
+        // navigator.credentials.get: clientDataJSON, authenticatorData и подпись (DER)
+        fn get(&mut self, rp_id: &str, origin: &str, challenge: &str, flags: u8) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+            self.sign_count += 1;
+            let client_data = Self::client_data("webauthn.get", challenge, origin);
+            let auth_data = self.auth_data(rp_id, flags);
+
+            let mut signed = auth_data.clone();
+            signed.extend_from_slice(&Sha256::digest(&client_data));
+            let signature: Signature = self.key.sign(&signed);
+
+            (client_data, auth_data, signature.to_der().as_bytes().to_vec())
+        }
+    }
+
+    fn register(config: &AppConfig, authenticator: &SoftAuthenticator) -> Vec<u8> {
+        let webauthn = &config.webauthn;
+        let (client_data, attestation) = authenticator.create(&webauthn.rp_id, &webauthn.origin, CHALLENGE);
+        verify_registration(webauthn, CHALLENGE, &client_data, &attestation).unwrap().public_key
+    }
+
+    #[test]
+    fn test_webauthn_defaults_follow_issuer() {
+        let webauthn = WebAuthnConfig::for_issuer("https://auth.example.com/", "AuthService");
+        assert_eq!(webauthn.rp_id, "auth.example.com");
+        assert_eq!(webauthn.origin, "https://auth.example.com");
+
+        let local = WebAuthnConfig::for_issuer("http://localhost:8080", "AuthService");
+        assert_eq!(local.rp_id, "localhost");
+        assert_eq!(local.origin, "http://localhost:8080");
+    }
+
+    #[test]
+    fn test_registration_extracts_credential() {
+        let config = test_config();
+        let authenticator = SoftAuthenticator::new();
+        let (client_data, attestation) =
+            authenticator.create(&config.webauthn.rp_id, &config.webauthn.origin, CHALLENGE);
+
+        let credential = verify_registration(&config.webauthn, CHALLENGE, &client_data, &attestation).unwrap();
+
+        assert_eq!(credential.credential_id, encode_b64url(&authenticator.credential_id));
+        assert_eq!(credential.public_key.len(), 65);
+        assert!(credential.user_verified);
+    }
+
+    #[test]
+    fn test_registration_rejects_wrong_origin_and_rp() {
+        let config = test_config();
+        let authenticator = SoftAuthenticator::new();
+
+        let (client_data, attestation) =
+            authenticator.create(&config.webauthn.rp_id, "https://evil.example.com", CHALLENGE);
+        assert_eq!(
+            verify_registration(&config.webauthn, CHALLENGE, &client_data, &attestation).unwrap_err(),
+            WebAuthnError::OriginMismatch
+        );
+
+        let (client_data, attestation) =
+            authenticator.create("evil.example.com", &config.webauthn.origin, CHALLENGE);
+        assert_eq!(
+            verify_registration(&config.webauthn, CHALLENGE, &client_data, &attestation).unwrap_err(),
+            WebAuthnError::RpIdMismatch
+        );
+    }
+
+    #[test]
+    fn test_assertion_verifies_signature_and_counter() {
+        let config = test_config();
+        let mut authenticator = SoftAuthenticator::new();
+        let public_key = register(&config, &authenticator);
+
+        let (client_data, auth_data, signature) =
+            authenticator.get(&config.webauthn.rp_id, &config.webauthn.origin, CHALLENGE, FLAGS_UP_UV);
+        let assertion = Assertion { client_data_json: &client_data, authenticator_data: &auth_data, signature: &signature };
+
+        assert_eq!(verify_assertion(&config.webauthn, CHALLENGE, &assertion, &public_key, 0, true), Ok(1));
+        // Повтор того же ответа: счетчик не вырос
+        assert_eq!(
+            verify_assertion(&config.webauthn, CHALLENGE, &assertion, &public_key, 1, true),
+            Err(WebAuthnError::CounterRegression)
+        );
+    }
+
+    #[test]
+    fn test_assertion_rejects_foreign_key_and_challenge() {
+        let config = test_config();
+        let mut authenticator = SoftAuthenticator::new();
+        let other_key = register(&config, &SoftAuthenticator::new());
+
+        let (client_data, auth_data, signature) =
+            authenticator.get(&config.webauthn.rp_id, &config.webauthn.origin, CHALLENGE, FLAGS_UP_UV);
+        let assertion = Assertion { client_data_json: &client_data, authenticator_data: &auth_data, signature: &signature };
+
+        assert_eq!(
+            verify_assertion(&config.webauthn, CHALLENGE, &assertion, &other_key, 0, false),
+            Err(WebAuthnError::InvalidSignature)
+        );
+        assert_eq!(
+            verify_assertion(&config.webauthn, "b3RoZXItY2hhbGxlbmdl", &assertion, &other_key, 0, false),
+            Err(WebAuthnError::ChallengeMismatch)
+        );
+    }
+
+    #[test]
+    fn test_passwordless_assertion_requires_user_verification() {
+        let config = test_config();
+        let mut authenticator = SoftAuthenticator::new();
+        let public_key = register(&config, &authenticator);
+
+        let (client_data, auth_data, signature) =
+            authenticator.get(&config.webauthn.rp_id, &config.webauthn.origin, CHALLENGE, FLAGS_UP);
+        let assertion = Assertion { client_data_json: &client_data, authenticator_data: &auth_data, signature: &signature };
+
+        // Вторым фактором достаточно присутствия пользователя, без пароля — нужна проверка на устройстве
+        assert!(verify_assertion(&config.webauthn, CHALLENGE, &assertion, &public_key, 0, false).is_ok());
+        assert_eq!(
+            verify_assertion(&config.webauthn, CHALLENGE, &assertion, &public_key, 0, true),
+            Err(WebAuthnError::UserNotVerified)
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_passwordless_login_with_session_challenge() {
+        let Some(pool) = common::test_database().await else { return };
+        let config = test_config();
+        let user = common::create_user(&pool).await;
+        let service = PasskeyService::new(pool, &config.webauthn);
+        let mut authenticator = SoftAuthenticator::new();
+        authenticator.credential_id = uuid::Uuid::new_v4().as_bytes().to_vec();
+        let credential_id = encode_b64url(&authenticator.credential_id);
+
+        let options = service.registration_options(&user).await.unwrap();
+        let (client_data, attestation) = authenticator.create(
+            &config.webauthn.rp_id,
+            &config.webauthn.origin,
+            options["challenge"].as_str().unwrap(),
+        );
+        service.finish_registration(user.id, None, &client_data, &attestation).await.unwrap();
+
+        let (options, challenge) = service.authentication_options(None).await.unwrap();
+        assert_eq!(options["challenge"], challenge.challenge);
+        assert_eq!(challenge.user_id, None);
+
+        let (client_data, auth_data, signature) =
+            authenticator.get(&config.webauthn.rp_id, &config.webauthn.origin, &challenge.challenge, FLAGS_UP_UV);
+        let assertion = Assertion { client_data_json: &client_data, authenticator_data: &auth_data, signature: &signature };
+
+        // Challenge выдан для входа без пароля: как второй фактор он не принимается
+        assert!(matches!(
+            service.finish_authentication(&challenge, Some(user.id), &credential_id, None, &assertion).await,
+            Err(PasskeyError::InvalidChallenge)
+        ));
+        let expired = LoginChallenge { expires_at: 0, ..challenge.clone() };
+        assert!(matches!(
+            service.finish_authentication(&expired, None, &credential_id, None, &assertion).await,
+            Err(PasskeyError::InvalidChallenge)
+        ));
+
+        let user_id = service
+            .finish_authentication(&challenge, None, &credential_id, Some(user.id.as_bytes()), &assertion)
+            .await
+            .unwrap();
+        assert_eq!(user_id, user.id);
+    }
+}
+
+#[cfg(test)]
+mod pending_login_tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test};
+    use auth_service::auth_handlers::configure_auth_routes;
+    use auth_service::config::LoginThrottlePolicy;
+    use auth_service::login_throttle::LoginThrottle;
+    use auth_service::mfa_service::MfaService;
+    use auth_service::pending_login::{PendingLoginService, PENDING_LOGIN_MAX_ATTEMPTS};
+    use common::{create_user, test_database, totp_cipher, TEST_PASSWORD};
+    use totp_rs::{Algorithm, Secret, TOTP};
+
+    #[actix_web::test]
+    async fn test_attempts_are_counted_server_side() {
+        let Some(pool) = test_database().await else { return };
+        let user = create_user(&pool).await;
+        let service = PendingLoginService::new(pool);
+
+        let token = service.start(user.id).await.unwrap();
+        assert_eq!(service.user_id(&token).await.unwrap(), Some(user.id));
+
+        for attempt in 1..=PENDING_LOGIN_MAX_ATTEMPTS {
+            let pending = service.begin_attempt(&token).await.unwrap().unwrap();
+            assert_eq!(pending.user_id, user.id);
+            assert_eq!(pending.is_last(), attempt == PENDING_LOGIN_MAX_ATTEMPTS);
+        }
+        assert!(service.begin_attempt(&token).await.unwrap().is_none());
+        assert_eq!(service.user_id(&token).await.unwrap(), None);
+
+        let token = service.start(user.id).await.unwrap();
+        service.finish(&token).await.unwrap();
+        assert!(service.begin_attempt(&token).await.unwrap().is_none());
+        assert!(service.begin_attempt("unknown").await.unwrap().is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_replayed_cookie_does_not_reset_attempts() {
+        let Some(pool) = test_database().await else { return };
+        let config = AppConfig {
+            login_throttle: LoginThrottlePolicy { base_delay_ms: 0, max_delay_ms: 0, ..LoginThrottlePolicy::default() },
+            ..test_config()
+        };
+        let user = create_user(&pool).await;
+        let mfa = MfaService::new(pool.clone(), &config.totp_issuer, totp_cipher());
+        let enrollment = mfa.start_enrollment(&user).await.unwrap();
+        let secret = Secret::Encoded(enrollment.secret).to_bytes().unwrap();
+        let code = TOTP::new_unchecked(Algorithm::SHA1, 6, 1, 30, secret, None, String::new())
+            .generate_current()
+            .unwrap();
+        mfa.activate(user.id, &code).await.unwrap();
+
+        let app = test_app!(pool.clone(), config.clone(), configure_auth_routes);
+        let login = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/auth/login")
+                .set_json(serde_json::json!({ "email": user.email, "password": TEST_PASSWORD }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(login.status(), StatusCode::OK);
+        let cookie = login.response().cookies().next().unwrap().into_owned();
+
+        // Каждый запрос отправляет исходную cookie, как при повторе перехваченного ответа
+        let wrong_code = || {
+            test::TestRequest::post()
+                .uri("/auth/login/mfa")
+                .cookie(cookie.clone())
+                .set_json(serde_json::json!({ "code": "000000" }))
+                .to_request()
+        };
+        for _ in 0..PENDING_LOGIN_MAX_ATTEMPTS {
+            let response = test::call_service(&app, wrong_code()).await;
+            let body: serde_json::Value = test::read_body_json(response).await;
+            assert_eq!(body["error"], "Invalid verification code");
+        }
+        let response = test::call_service(&app, wrong_code()).await;
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["error"], "Login session expired");
+
+        // Неудачи второго фактора учитываются в блокировке аккаунта
+        let throttle = LoginThrottle::new(pool, config.login_throttle);
+        assert!(throttle.account_locked_until(&user.email).await.unwrap().is_some());
+    }
+}
+
+#[cfg(test)]
+mod totp_secret_encryption_tests {
+    use super::*;
+    use auth_service::mfa_service::MfaService;
+    use auth_service::secrets::SecretCipher;
+    use common::{create_user, test_database, totp_cipher};
+    use totp_rs::{Algorithm, Secret, TOTP};
+    use uuid::Uuid;
+
+    const SECRET: &str = "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP";
+
+    fn current_code(secret: &str) -> String {
+        let secret = Secret::Encoded(secret.to_string()).to_bytes().unwrap();
+        TOTP::new_unchecked(Algorithm::SHA1, 6, 1, 30, secret, None, String::new())
+            .generate_current()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_cipher_roundtrip_is_bound_to_owner() {
+        let cipher = totp_cipher();
+        let owner = Uuid::new_v4();
+        let stored = cipher.encrypt(SECRET, owner.as_bytes());
+
+        assert!(SecretCipher::is_encrypted(&stored));
+        assert!(!stored.contains(SECRET));
+        assert_ne!(stored, cipher.encrypt(SECRET, owner.as_bytes()));
+        assert_eq!(cipher.decrypt(&stored, owner.as_bytes()).as_deref(), Some(SECRET));
+
+        // Чужой владелец, другой ключ или поврежденное значение не расшифровываются
+        assert!(cipher.decrypt(&stored, Uuid::new_v4().as_bytes()).is_none());
+        assert!(SecretCipher::new(&[8; 32]).decrypt(&stored, owner.as_bytes()).is_none());
+        assert!(cipher.decrypt(&stored[..stored.len() - 2], owner.as_bytes()).is_none());
+        assert!(cipher.decrypt(SECRET, owner.as_bytes()).is_none());
+    }
+
+    #[test]
+    fn test_key_from_base64() {
+        assert!(SecretCipher::from_base64("BwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwc=").is_ok());
+        assert!(SecretCipher::from_base64("c2hvcnQ=").is_err());
+        assert!(SecretCipher::from_base64("not base64!").is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_enrollment_stores_encrypted_secret() {
+        let Some(pool) = test_database().await else { return };
+        let user = create_user(&pool).await;
+        let mfa = MfaService::new(pool.clone(), "AuthService", totp_cipher());
+
+        let enrollment = mfa.start_enrollment(&user).await.unwrap();
+        let stored: String = sqlx::query_scalar("SELECT secret FROM user_totp WHERE user_id = $1")
+            .bind(user.id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert!(SecretCipher::is_encrypted(&stored));
+        assert!(!stored.contains(&enrollment.secret));
+        assert!(mfa.activate(user.id, &current_code(&enrollment.secret)).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_legacy_plaintext_secret_is_encrypted() {
+        let Some(pool) = test_database().await else { return };
+        let user = create_user(&pool).await;
+        sqlx::query("INSERT INTO user_totp (user_id, secret, enabled_at, created_at) VALUES ($1, $2, now(), now())")
+            .bind(user.id)
+            .bind(SECRET)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let mfa = MfaService::new(pool.clone(), "AuthService", totp_cipher());
+
+        assert!(mfa.encrypt_legacy_secrets().await.unwrap() >= 1);
+        let stored: String = sqlx::query_scalar("SELECT secret FROM user_totp WHERE user_id = $1")
+            .bind(user.id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(SecretCipher::is_encrypted(&stored));
+        assert!(mfa.verify(user.id, &current_code(SECRET)).await.is_ok());
+    }
+}
@@ -1,6 +1,8 @@
-use MIREA_Backend_Courcer_paper::models::{
+use auth_service::models::{
     RegisterUserRequest, RegisterUserResponse, User, ErrorResponse
 };
+use auth_service::config::PasswordPolicyConfig;
+use auth_service::password_policy::check_rules;
 use validator::Validate;
 use uuid::Uuid;
 use chrono::Utc;
@@ -143,11 +145,10 @@ mod model_validation_tests {
             password: "short".to_string(), // Только 5 символов, нужно минимум 8
         };
 
-        let result = request.validate();
-        assert!(result.is_err());
-
-        let errors = result.unwrap_err();
-        assert!(errors.field_errors().contains_key("password"));
+        // Длину проверяет политика паролей, а не валидация DTO
+        assert!(request.validate().is_ok());
+        let violations = check_rules(&PasswordPolicyConfig::default(), &request.password, &request.username, &request.email);
+        assert!(violations.iter().any(|v| v.rule == "min_length"));
     }
 
     #[test]
@@ -191,7 +192,9 @@ mod model_validation_tests {
             password: "".to_string(),
         };
 
-        assert!(request.validate().is_err());
+        assert!(request.validate().is_ok());
+        let violations = check_rules(&PasswordPolicyConfig::default(), &request.password, &request.username, &request.email);
+        assert!(violations.iter().any(|v| v.rule == "min_length"));
     }
 
     #[test]
@@ -208,7 +211,7 @@ mod model_validation_tests {
         let errors = result.unwrap_err();
         assert!(errors.field_errors().contains_key("username"));
         assert!(errors.field_errors().contains_key("email"));
-        assert!(errors.field_errors().contains_key("password"));
+        assert!(!errors.field_errors().contains_key("password"));
     }
 
     #[test]
@@ -262,6 +265,10 @@ mod model_conversion_tests {
             password_hash: "$2b$12$hashedpassword".to_string(),
             created_at,
             updated_at,
+            email_verified: false,
+            email_verified_at: None,
+            status: "active".to_string(),
+            password_reset_required: false,
         };
 
         let response: RegisterUserResponse = user.into();
@@ -270,6 +277,7 @@ mod model_conversion_tests {
         assert_eq!(response.username, "test_user");
         assert_eq!(response.email, "test@example.com");
         assert_eq!(response.created_at, created_at);
+        assert!(!response.email_verified);
         // Проверяем, что пароль НЕ включен в response
     }
 
@@ -285,12 +293,20 @@ mod model_conversion_tests {
             password_hash: "$2b$12$somehash".to_string(),
             created_at: now,
             updated_at: now,
+            email_verified: true,
+            email_verified_at: Some(now),
+            status: "active".to_string(),
+            password_reset_required: false,
         };
 
         assert_eq!(user.id, user_id);
         assert_eq!(user.username, "john_doe");
         assert_eq!(user.email, "john@example.com");
         assert!(user.password_hash.starts_with("$2b$"));
+        assert!(!user.is_suspended());
+
+        let suspended = User { status: "suspended".to_string(), ..user };
+        assert!(suspended.is_suspended());
     }
 
     #[test]
@@ -325,6 +341,7 @@ mod model_conversion_tests {
             id: Uuid::new_v4(),
             username: "test_user".to_string(),
             email: "test@example.com".to_string(),
+            email_verified: false,
             created_at: Utc::now(),
         };
 
@@ -334,6 +351,7 @@ mod model_conversion_tests {
         let json_str = json.unwrap();
         assert!(json_str.contains("test_user"));
         assert!(json_str.contains("test@example.com"));
+        assert!(json_str.contains("\"email_verified\":false"));
     }
 }
 
@@ -0,0 +1,393 @@
+#[macro_use]
+mod common;
+
+use common::{app_services, lazy_pool, session_middleware, test_config};
+use uuid::Uuid;
+
+#[cfg(test)]
+mod secrets_tests {
+    use auth_service::email_verification::{sign_verification_token, verify_verification_token};
+    use auth_service::secrets::{derive_key, hash_secret, random_secret};
+
+    #[test]
+    fn test_reset_secret_is_stored_hashed() {
+        let secret = random_secret(48);
+
+        assert_eq!(secret.len(), 48);
+        assert_ne!(hash_secret(&secret), secret);
+        assert_eq!(hash_secret(&secret), hash_secret(&secret));
+        assert_eq!(hash_secret(&secret).len(), 43);
+    }
+
+    #[test]
+    fn test_derived_key_is_separated_by_purpose() {
+        let key = derive_key("jwt-secret", "email-verification");
+
+        assert_eq!(key, derive_key("jwt-secret", "email-verification"));
+        assert_ne!(key, "jwt-secret");
+        assert_ne!(key, derive_key("jwt-secret", "other"));
+        assert_ne!(key, derive_key("other-secret", "email-verification"));
+
+        // Ссылка, подписанная общим секретом, не проходит проверку производным ключом
+        let token = sign_verification_token(b"jwt-secret", "nonce");
+        assert!(verify_verification_token(key.as_bytes(), &token).is_none());
+        let token = sign_verification_token(key.as_bytes(), "nonce");
+        assert_eq!(verify_verification_token(key.as_bytes(), &token), Some("nonce"));
+    }
+}
+
+#[cfg(test)]
+mod password_reset_tests {
+    use super::*;
+    use actix_session::Session;
+    use actix_web::{http::StatusCode, test, web, App, HttpResponse};
+    use auth_service::auth_handlers::configure_auth_routes;
+    use auth_service::mail::InMemoryMailSender;
+    use auth_service::middleware::SessionGuard;
+    use auth_service::password_reset::PasswordResetService;
+    use auth_service::session_service::SessionService;
+    use common::{create_user, test_database};
+    use std::sync::Arc;
+
+    macro_rules! auth_app {
+        () => {{
+            let pool = lazy_pool();
+            test::init_service(
+                App::new()
+                    .configure(app_services(pool.clone(), test_config()))
+                    .route("/test/legacy-login", web::post().to(|session: Session| async move {
+                        session.insert("user_id", Uuid::new_v4().to_string()).unwrap();
+                        HttpResponse::Ok().finish()
+                    }))
+                    .wrap(SessionGuard::new(SessionService::new(pool)))
+                    .wrap(session_middleware())
+                    .configure(configure_auth_routes),
+            )
+            .await
+        }};
+    }
+
+    #[actix_web::test]
+    async fn test_reset_rejects_short_password() {
+        let Some(pool) = test_database().await else { return };
+        let user = create_user(&pool).await;
+        let mailer = Arc::new(InMemoryMailSender::new());
+        PasswordResetService::new(pool.clone(), mailer.clone(), &test_config())
+            .request_reset(&user)
+            .await
+            .unwrap();
+        let body = mailer.last_message_to(&user.email).unwrap().body;
+        let encoded = body[body.find("token=").unwrap() + "token=".len()..].split_whitespace().next().unwrap();
+        let token = urlencoding::decode(encoded).unwrap().into_owned();
+
+        let app = test_app!(pool.clone(), configure_auth_routes);
+        let response = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/auth/password/reset")
+                .set_json(serde_json::json!({ "token": token, "password": "short" }))
+                .to_request(),
+        )
+        .await;
+
+        // Короткий пароль отклоняет политика паролей со списком нарушений, ссылка остается действительной
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        let rules: Vec<&str> = body["violations"].as_array().unwrap().iter().filter_map(|v| v["rule"].as_str()).collect();
+        assert!(rules.contains(&"min_length"));
+        let reset = PasswordResetService::new(pool, mailer, &test_config());
+        assert_eq!(reset.find_token_user(&token).await.unwrap(), Some(user.id));
+    }
+
+    #[actix_web::test]
+    async fn test_reset_pages_are_served() {
+        let app = auth_app!();
+
+        for uri in ["/auth/password/forgot", "/auth/password/reset?token=abc"] {
+            let response = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_session_without_server_record_is_dropped() {
+        let app = auth_app!();
+
+        let login = test::call_service(&app, test::TestRequest::post().uri("/test/legacy-login").to_request()).await;
+        let cookie = login.response().cookies().next().unwrap().into_owned();
+
+        // Сессия без session_id не проходит SessionGuard
+        let me = test::call_service(&app, test::TestRequest::get().uri("/auth/me").cookie(cookie).to_request()).await;
+        assert_eq!(me.status(), StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[cfg(test)]
+mod password_policy_tests {
+    use auth_service::config::PasswordPolicyConfig;
+    use auth_service::password_policy::{check_rules, is_breached, is_similar_to_identity, PasswordPolicy};
+
+    fn rules(violations: &[auth_service::password_policy::PasswordViolation]) -> Vec<&str> {
+        violations.iter().map(|v| v.rule).collect()
+    }
+
+    #[test]
+    fn test_length_rules() {
+        let config = PasswordPolicyConfig::default();
+
+        assert!(check_rules(&config, "correct horse battery", "john", "john@example.com").is_empty());
+        assert_eq!(rules(&check_rules(&config, "short", "john", "john@example.com")), vec!["min_length"]);
+        // Многобайтовые символы считаются по байтам
+        assert!(check_rules(&config, &"ж".repeat(100), "john", "john@example.com").is_empty());
+        assert_eq!(rules(&check_rules(&config, &"ж".repeat(200), "john", "john@example.com")), vec!["max_length"]);
+    }
+
+    #[test]
+    fn test_similarity_to_identity() {
+        assert!(is_similar_to_identity("John.Smith2024", "john.smith", "js@example.com"));
+        assert!(is_similar_to_identity("maria_example", "m", "maria@example.com"));
+        assert!(is_similar_to_identity("johnsmiht", "johnsmith", "x@example.com"));
+        assert!(!is_similar_to_identity("correct horse battery", "john", "john@example.com"));
+
+        let config = PasswordPolicyConfig { reject_similar_to_identity: false, ..PasswordPolicyConfig::default() };
+        assert!(check_rules(&config, "john12345", "john", "john@example.com").is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_breached_corpus_lookup() {
+        // SHA-1("password") = 5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8
+        let dir = std::env::temp_dir().join(format!("breached-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("5BAA6.txt"), "003D68EB55068C33ACE09247EE4C639306B:3\r\n1E4C9B93F3F0682250B6CF8331B7EE68FD8:9545824\r\n").unwrap();
+
+        assert!(is_breached(&dir, "password").await.unwrap());
+        assert!(!is_breached(&dir, "password1").await.unwrap());
+
+        let config = PasswordPolicyConfig {
+            min_length: 1,
+            breached_corpus_dir: Some(dir.clone()),
+            ..PasswordPolicyConfig::default()
+        };
+        let violations = PasswordPolicy::new(&config).check("password", "john", "john@example.com").await;
+        assert_eq!(rules(&violations), vec!["breached"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod password_hasher_tests {
+    use auth_service::config::PasswordHashingConfig;
+    use auth_service::password_hasher::{PasswordHashError, PasswordHasher};
+
+    // Небольшие параметры, чтобы тесты не тратили время на хеширование
+    fn config(memory_kib: u32, iterations: u32) -> PasswordHashingConfig {
+        PasswordHashingConfig { memory_kib, iterations, parallelism: 1 }
+    }
+
+    #[test]
+    fn test_argon2id_hash_and_verify() {
+        let hasher = PasswordHasher::new(&config(1024, 1)).unwrap();
+        let hash = hasher.hash("correct horse battery staple").unwrap();
+
+        assert!(hash.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"));
+        assert!(hasher.verify("correct horse battery staple", &hash).unwrap());
+        assert!(!hasher.verify("wrong password", &hash).unwrap());
+        assert!(!hasher.needs_rehash(&hash));
+    }
+
+    #[test]
+    fn test_long_passphrase_is_not_truncated() {
+        let hasher = PasswordHasher::new(&config(1024, 1)).unwrap();
+        let passphrase = "a".repeat(100);
+        let hash = hasher.hash(&passphrase).unwrap();
+
+        // bcrypt не различает пароли, совпадающие в первых 72 байтах
+        assert!(!hasher.verify(&"a".repeat(101), &hash).unwrap());
+    }
+
+    #[test]
+    fn test_bcrypt_hash_verified_and_marked_for_rehash() {
+        let hasher = PasswordHasher::new(&config(1024, 1)).unwrap();
+        let hash = bcrypt::hash("legacy_password", 4).unwrap();
+
+        assert!(hasher.verify("legacy_password", &hash).unwrap());
+        assert!(!hasher.verify("other_password", &hash).unwrap());
+        assert!(hasher.needs_rehash(&hash));
+    }
+
+    #[test]
+    fn test_changed_params_require_rehash() {
+        let old = PasswordHasher::new(&config(1024, 1)).unwrap();
+        let current = PasswordHasher::new(&config(2048, 2)).unwrap();
+        let hash = old.hash("some password").unwrap();
+
+        // Старый хеш проверяется по своим параметрам
+        assert!(current.verify("some password", &hash).unwrap());
+        assert!(current.needs_rehash(&hash));
+        assert!(!current.needs_rehash(&current.hash("some password").unwrap()));
+    }
+
+    #[test]
+    fn test_argon2i_hash_requires_rehash() {
+        let hasher = PasswordHasher::new(&config(1024, 1)).unwrap();
+        let argon2i = "$argon2i$v=19$m=1024,t=1,p=1$c29tZXNhbHQ$iWh06vD8Fy27wf9npn6FXWiCX4K6pW6Ue1Bnzz07Z8A";
+
+        assert!(hasher.needs_rehash(argon2i));
+    }
+
+    #[test]
+    fn test_invalid_hash_and_params() {
+        let hasher = PasswordHasher::new(&config(1024, 1)).unwrap();
+
+        assert!(matches!(hasher.verify("password", "not-a-hash"), Err(PasswordHashError::UnsupportedHash)));
+        assert!(matches!(
+            PasswordHasher::new(&config(1024, 0)),
+            Err(PasswordHashError::InvalidParams(_))
+        ));
+    }
+
+    #[test]
+    fn test_default_config() {
+        assert_eq!(PasswordHashingConfig::default(), config(19 * 1024, 2));
+    }
+}
+
+#[cfg(test)]
+mod hashing_pool_tests {
+    use auth_service::config::HashingPoolConfig;
+    use auth_service::hashing_pool::HashingPool;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[actix_web::test]
+    async fn test_concurrency_limit() {
+        let pool = Arc::new(HashingPool::new(&HashingPoolConfig { max_concurrency: 2 }));
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let tasks = (0..6).map(|i| {
+            let (pool, running, peak) = (pool.clone(), running.clone(), peak.clone());
+            async move {
+                pool.run(move || {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(30));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    i * 2
+                })
+                .await
+                .unwrap()
+            }
+        });
+        let results = futures::future::join_all(tasks).await;
+
+        assert_eq!(results, vec![0, 2, 4, 6, 8, 10]);
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+
+        let metrics = pool.metrics();
+        assert_eq!(metrics.max_concurrency, 2);
+        assert_eq!(metrics.completed, 6);
+        assert_eq!((metrics.in_flight, metrics.queued), (0, 0));
+        // Задачи сверх лимита ждали в очереди
+        assert!(metrics.max_queue_time_us >= 20_000);
+        assert!(metrics.total_run_time_us >= 6 * 30_000);
+    }
+
+    #[actix_web::test]
+    async fn test_panicking_task_is_reported() {
+        let pool = HashingPool::new(&HashingPoolConfig { max_concurrency: 1 });
+
+        assert!(pool.run(|| -> u32 { panic!("hash failure") }).await.is_err());
+        // Слот освобожден, следующие задачи выполняются
+        assert_eq!(pool.run(|| 42).await.unwrap(), 42);
+
+        let metrics = pool.metrics();
+        assert_eq!((metrics.completed, metrics.failed), (1, 1));
+    }
+
+    #[actix_web::test]
+    async fn test_cancelled_wait_leaves_queue() {
+        let pool = HashingPool::new(&HashingPoolConfig { max_concurrency: 1 });
+
+        let busy = pool.run(|| std::thread::sleep(Duration::from_millis(100)));
+        let waiting = pool.run(|| ());
+        let cancelled = actix_web::rt::time::timeout(Duration::from_millis(20), async {
+            futures::join!(busy, waiting)
+        })
+        .await;
+        assert!(cancelled.is_err());
+
+        let metrics = pool.metrics();
+        assert_eq!(metrics.queued, 0);
+        // Отмена ожидания не освобождает слот, пока задача в потоке не завершится
+        assert_eq!(metrics.in_flight, 1);
+    }
+
+    #[test]
+    fn test_zero_concurrency_is_clamped() {
+        let pool = HashingPool::new(&HashingPoolConfig { max_concurrency: 0 });
+        assert_eq!(pool.metrics().max_concurrency, 1);
+    }
+}
+
+#[cfg(test)]
+mod change_password_request_tests {
+    use auth_service::models::ChangePasswordRequest;
+    use validator::Validate;
+
+    #[test]
+    fn test_change_password_request_defaults() {
+        let request: ChangePasswordRequest = serde_json::from_value(serde_json::json!({
+            "current_password": "oldpassword",
+            "new_password": "newpassword1"
+        })).unwrap();
+        assert!(!request.sign_out_other_sessions);
+
+        let request: ChangePasswordRequest = serde_json::from_value(serde_json::json!({
+            "current_password": "oldpassword",
+            "new_password": "newpassword1",
+            "sign_out_other_sessions": true
+        })).unwrap();
+        assert!(request.sign_out_other_sessions);
+    }
+
+    #[test]
+    fn test_change_password_request_validation() {
+        let request = ChangePasswordRequest {
+            current_password: String::new(),
+            new_password: "newpassword1".to_string(),
+            sign_out_other_sessions: false,
+        };
+        assert!(request.validate().is_err());
+
+        // Длину нового пароля проверяет политика паролей, а не валидация DTO
+        let request = ChangePasswordRequest {
+            current_password: "oldpassword".to_string(),
+            new_password: "short".to_string(),
+            sign_out_other_sessions: false,
+        };
+        assert!(request.validate().is_ok());
+    }
+}
+
+#[cfg(test)]
+mod change_password_tests {
+    use actix_web::{http::StatusCode, test};
+    use auth_service::auth_handlers::configure_auth_routes;
+
+    #[actix_web::test]
+    async fn test_change_password_requires_session() {
+        let app = test_app!(configure_auth_routes);
+
+        let req = test::TestRequest::post()
+            .uri("/auth/password/change")
+            .set_json(serde_json::json!({
+                "current_password": "oldpassword",
+                "new_password": "newpassword1"
+            }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,190 @@
+#[macro_use]
+mod common;
+
+#[cfg(test)]
+mod account_deletion_request_tests {
+    use auth_service::account_deletion::AccountDeletionError;
+    use auth_service::config::{AccountDeletionPolicy, AppConfig};
+    use auth_service::models::DeleteAccountRequest;
+    use validator::Validate;
+
+    #[test]
+    fn test_deletion_defaults() {
+        let policy = AppConfig::default().account_deletion;
+        assert_eq!(policy, AccountDeletionPolicy::default());
+        assert_eq!(policy.grace_period, 2592000);
+        assert_eq!(policy.purge_interval, 3600);
+    }
+
+    #[test]
+    fn test_delete_request_requires_password() {
+        let request = DeleteAccountRequest { current_password: String::new() };
+        assert!(request.validate().is_err());
+
+        let request = DeleteAccountRequest { current_password: "password".to_string() };
+        assert!(request.validate().is_ok());
+        assert_eq!(AccountDeletionError::AlreadyScheduled.to_string(), "Account deletion is already scheduled");
+    }
+}
+
+#[cfg(test)]
+mod privacy_tests {
+    use actix_web::{http::StatusCode, test};
+    use auth_service::account_handlers::configure_account_routes;
+
+    #[actix_web::test]
+    async fn test_privacy_page_redirects_to_login() {
+        let app = test_app!(configure_account_routes);
+
+        let response = test::call_service(&app, test::TestRequest::get().uri("/account/privacy").to_request()).await;
+
+        assert_eq!(response.status(), StatusCode::FOUND);
+        let location = response.headers().get("Location").unwrap().to_str().unwrap();
+        assert_eq!(location, "/auth/login?return_to=%2Faccount%2Fprivacy");
+    }
+
+    #[actix_web::test]
+    async fn test_export_and_deletion_require_session() {
+        let app = test_app!(configure_account_routes);
+
+        let export = test::call_service(&app, test::TestRequest::get().uri("/account/api/export").to_request()).await;
+        assert_eq!(export.status(), StatusCode::UNAUTHORIZED);
+
+        let status = test::call_service(&app, test::TestRequest::get().uri("/account/api/deletion").to_request()).await;
+        assert_eq!(status.status(), StatusCode::UNAUTHORIZED);
+
+        let cancel = test::call_service(&app, test::TestRequest::delete().uri("/account/api/deletion").to_request()).await;
+        assert_eq!(cancel.status(), StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[cfg(test)]
+mod account_deletion_tests {
+    use super::*;
+    use auth_service::account_deletion::{AccountDeletionError, AccountDeletionService};
+    use auth_service::config::AppConfig;
+    use auth_service::login_throttle::LoginThrottle;
+    use auth_service::mail::InMemoryMailSender;
+    use auth_service::session_service::SessionService;
+    use chrono::Duration;
+    use common::{create_user, test_config, test_database};
+    use sqlx::{Pool, Postgres};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    fn config_with_grace_period(grace_period: i64) -> AppConfig {
+        let mut config = test_config();
+        config.account_deletion.grace_period = grace_period;
+        config
+    }
+
+    async fn count(pool: &Pool<Postgres>, query: &str, user_id: Uuid) -> i64 {
+        sqlx::query_scalar(query).bind(user_id).fetch_one(pool).await.unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_schedule_and_cancel() {
+        let Some(pool) = test_database().await else { return };
+        let config = test_config();
+        let mailer = Arc::new(InMemoryMailSender::new());
+        let service = AccountDeletionService::new(pool.clone(), mailer.clone(), &config);
+        let user = create_user(&pool).await;
+
+        let status = service.schedule(&user).await.unwrap();
+        assert_eq!(status.scheduled_for - status.requested_at, Duration::seconds(config.account_deletion.grace_period));
+        assert!(mailer.last_message_to(&user.email).unwrap().body.contains("/account/privacy"));
+        assert!(service.get_status(user.id).await.unwrap().is_some());
+
+        // Повторный запрос не сдвигает дату удаления
+        assert!(matches!(service.schedule(&user).await, Err(AccountDeletionError::AlreadyScheduled)));
+
+        service.cancel(user.id).await.unwrap();
+        assert!(service.get_status(user.id).await.unwrap().is_none());
+        assert!(matches!(service.cancel(user.id).await, Err(AccountDeletionError::NotScheduled)));
+    }
+
+    #[actix_web::test]
+    async fn test_purge_due_removes_only_expired_accounts() {
+        let Some(pool) = test_database().await else { return };
+        let mailer = Arc::new(InMemoryMailSender::new());
+        let config = config_with_grace_period(0);
+        let expired = AccountDeletionService::new(pool.clone(), mailer.clone(), &config);
+        let pending = AccountDeletionService::new(pool.clone(), mailer, &test_config());
+
+        let due_user = create_user(&pool).await;
+        let kept_user = create_user(&pool).await;
+        SessionService::new(pool.clone()).create_session(due_user.id, None, Some("127.0.0.1")).await.unwrap();
+        LoginThrottle::new(pool.clone(), config.login_throttle)
+            .record_failure(&due_user.email, None)
+            .await
+            .unwrap();
+
+        expired.schedule(&due_user).await.unwrap();
+        pending.schedule(&kept_user).await.unwrap();
+
+        assert!(expired.purge_due().await.unwrap() >= 1);
+
+        assert_eq!(count(&pool, "SELECT COUNT(*) FROM users WHERE id = $1", due_user.id).await, 0);
+        assert_eq!(count(&pool, "SELECT COUNT(*) FROM user_sessions WHERE user_id = $1", due_user.id).await, 0);
+        let failures: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM login_failures WHERE key = $1")
+            .bind(due_user.email.to_lowercase())
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(failures, 0);
+
+        // Срок отмены еще не истек — аккаунт остается
+        assert_eq!(count(&pool, "SELECT COUNT(*) FROM users WHERE id = $1", kept_user.id).await, 1);
+        pending.cancel(kept_user.id).await.unwrap();
+    }
+}
+
+#[cfg(test)]
+mod data_export_tests {
+    use super::*;
+    use auth_service::account_deletion::AccountDeletionService;
+    use auth_service::data_export::DataExportService;
+    use auth_service::mail::InMemoryMailSender;
+    use auth_service::session_service::SessionService;
+    use common::{create_user, test_config, test_database};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    #[actix_web::test]
+    async fn test_export_shape() {
+        let Some(pool) = test_database().await else { return };
+        let service = DataExportService::new(pool.clone());
+        let deletion = AccountDeletionService::new(pool.clone(), Arc::new(InMemoryMailSender::new()), &test_config());
+        let user = create_user(&pool).await;
+        SessionService::new(pool.clone()).create_session(user.id, Some("test-agent"), Some("127.0.0.1")).await.unwrap();
+        deletion.schedule(&user).await.unwrap();
+
+        let export = service.export(user.id).await.unwrap().unwrap();
+        let json = serde_json::to_value(&export).unwrap();
+
+        let mut keys: Vec<&str> = json.as_object().unwrap().keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["audit_history", "connected_apps", "consents", "exported_at", "profile", "sessions"]);
+
+        assert_eq!(json["profile"]["id"], user.id.to_string());
+        assert_eq!(json["profile"]["email"], user.email);
+        assert!(json["profile"].get("password_hash").is_none());
+        assert_eq!(json["sessions"].as_array().unwrap().len(), 1);
+        assert_eq!(json["sessions"][0]["user_agent"], "test-agent");
+        assert!(json["consents"].as_array().unwrap().is_empty());
+        assert!(json["connected_apps"].as_array().unwrap().is_empty());
+
+        let events: Vec<&str> = json["audit_history"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| event["event"].as_str().unwrap())
+            .collect();
+        for event in ["account_created", "session_started", "account_deletion_requested"] {
+            assert!(events.contains(&event), "нет события {}", event);
+        }
+
+        deletion.cancel(user.id).await.unwrap();
+        assert!(service.export(Uuid::new_v4()).await.unwrap().is_none());
+    }
+}
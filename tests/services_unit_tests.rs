@@ -1,4 +1,4 @@
-use MIREA_Backend_Courcer_paper::services::RegistrationError;
+use auth_service::services::{RegistrationError, UserError};
 
 #[cfg(test)]
 mod registration_error_tests {
@@ -51,6 +51,12 @@ mod registration_error_tests {
 
         assert!(!display.is_empty());
     }
+
+    #[test]
+    fn test_user_error_display() {
+        assert!(format!("{}", UserError::NotFound).contains("не найден"));
+        assert!(format!("{}", UserError::HashError).contains("хеширования"));
+    }
 }
 
 #[cfg(test)]
@@ -311,8 +317,7 @@ mod string_operations_tests {
 
 #[cfg(test)]
 mod json_serialization_tests {
-    use serde_json;
-    use MIREA_Backend_Courcer_paper::models::{RegisterUserRequest, ErrorResponse};
+    use auth_service::models::{RegisterUserRequest, ErrorResponse};
 
     #[test]
     fn test_register_request_to_json() {
@@ -154,6 +154,7 @@ mod boolean_logic_tests {
 #[cfg(test)]
 mod option_and_result_tests {
     #[test]
+    #[allow(clippy::unnecessary_literal_unwrap)]
     fn test_option_handling() {
         let some_value: Option<String> = Some("test".to_string());
         let none_value: Option<String> = None;
@@ -169,6 +170,7 @@ mod option_and_result_tests {
     }
 
     #[test]
+    #[allow(clippy::unnecessary_literal_unwrap)]
     fn test_result_handling() {
         let ok_result: Result<i32, String> = Ok(42);
         let err_result: Result<i32, String> = Err("error".to_string());
@@ -211,6 +213,7 @@ mod option_and_result_tests {
 #[cfg(test)]
 mod collection_tests {
     #[test]
+    #[allow(clippy::vec_init_then_push)]
     fn test_vec_operations() {
         let mut users = Vec::new();
         users.push("user1");
@@ -223,6 +226,7 @@ mod collection_tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_vec_filtering() {
         let numbers = vec![1, 2, 3, 4, 5, 6];
         let even: Vec<i32> = numbers.iter()
@@ -234,6 +238,7 @@ mod collection_tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_string_vector() {
         let usernames = vec![
             "alice".to_string(),
@@ -293,6 +298,7 @@ mod error_message_formatting_tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_multiple_error_messages() {
         let errors = vec![
             "Username слишком короткий",
@@ -309,7 +315,7 @@ mod error_message_formatting_tests {
 
 #[cfg(test)]
 mod date_time_logic_tests {
-    use chrono::{Utc, Duration, Timelike, Datelike};
+    use chrono::{Utc, Duration, Datelike};
 
     #[test]
     fn test_timestamp_creation() {
@@ -329,6 +335,7 @@ mod date_time_logic_tests {
     }
 
     #[test]
+    #[allow(clippy::manual_range_contains)]
     fn test_date_components() {
         let now = Utc::now();
 
@@ -362,6 +369,7 @@ mod conversion_tests {
     }
 
     #[test]
+    #[allow(clippy::len_zero)]
     fn test_bytes_conversion() {
         let text = "Hello, World!";
         let bytes = text.as_bytes();
@@ -401,6 +409,7 @@ mod pattern_matching_tests {
 #[cfg(test)]
 mod iterator_tests {
     #[test]
+    #[allow(clippy::iter_count, clippy::useless_vec)]
     fn test_iterator_count() {
         let numbers = vec![1, 2, 3, 4, 5];
         let count = numbers.iter().count();
@@ -409,6 +418,7 @@ mod iterator_tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_iterator_find() {
         let users = vec!["alice", "bob", "charlie"];
         let found = users.iter().find(|&&u| u == "bob");
@@ -418,6 +428,7 @@ mod iterator_tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_iterator_all() {
         let numbers = vec![2, 4, 6, 8];
         let all_even = numbers.iter().all(|&n| n % 2 == 0);
@@ -426,6 +437,7 @@ mod iterator_tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_iterator_any() {
         let numbers = vec![1, 2, 3];
         let has_even = numbers.iter().any(|&n| n % 2 == 0);